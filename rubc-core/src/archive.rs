@@ -0,0 +1,324 @@
+// Compressed ROM images.
+//
+// ROMs are often distributed as .zip or .gz files. Archives are recognised by
+// their magic bytes, so a renamed file or an in-memory buffer works as well;
+// anything else is treated as a plain ROM. Zip archives yield their first
+// .gb/.gbc entry. Only stored and deflated data is supported, which covers
+// what zip and gzip tools write by default.
+
+use crate::globals::ROM_MAX_SIZE;
+use crate::{utils, Error};
+
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+const ZIP_LOCAL_HEADER: u32 = 0x0403_4B50;
+const ZIP_CENTRAL_HEADER: u32 = 0x0201_4B50;
+const ZIP_END_OF_DIRECTORY: u32 = 0x0605_4B50;
+
+const GZIP_HEADER_SIZE: usize = 10;
+const GZIP_FHCRC: u8 = 0x02;
+const GZIP_FEXTRA: u8 = 0x04;
+const GZIP_FNAME: u8 = 0x08;
+const GZIP_FCOMMENT: u8 = 0x10;
+
+// returns the ROM inside an archive, or the data itself
+pub fn extract_rom(data: &[u8]) -> crate::Result<Vec<u8>> {
+    if data.starts_with(&GZIP_MAGIC) {
+        log::debug!("Extracting gzip archive");
+        gunzip(data)
+    } else if read_u32(data, 0).ok() == Some(ZIP_LOCAL_HEADER) {
+        log::debug!("Extracting zip archive");
+        unzip(data)
+    } else {
+        Ok(data.to_vec())
+    }
+}
+
+fn malformed(message: &str) -> Error {
+    Error::Archive(message.to_string())
+}
+
+fn too_large() -> Error {
+    Error::Archive(format!("Inflated data exceeds {} bytes", ROM_MAX_SIZE))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> crate::Result<u16> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| malformed("Unexpected end of archive"))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> crate::Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| malformed("Unexpected end of archive"))
+}
+
+fn check_crc(data: &[u8], expected: u32) -> crate::Result<()> {
+    match utils::crc32(data) == expected {
+        true => Ok(()),
+        false => Err(malformed("CRC mismatch in archive")),
+    }
+}
+
+pub fn gunzip(data: &[u8]) -> crate::Result<Vec<u8>> {
+    if data.len() < GZIP_HEADER_SIZE {
+        return Err(malformed("Unexpected end of archive"));
+    }
+    if !data.starts_with(&GZIP_MAGIC) || data[2] != 8 {
+        return Err(malformed("Not a deflated gzip file"));
+    }
+    let flags = data[3];
+    let mut offset = GZIP_HEADER_SIZE;
+    if flags & GZIP_FEXTRA != 0 {
+        offset += 2 + read_u16(data, offset)? as usize;
+    }
+    for flag in [GZIP_FNAME, GZIP_FCOMMENT] {
+        if flags & flag != 0 {
+            let end = data[offset.min(data.len())..]
+                .iter()
+                .position(|b| *b == 0)
+                .ok_or_else(|| malformed("Unterminated gzip header"))?;
+            offset += end + 1;
+        }
+    }
+    if flags & GZIP_FHCRC != 0 {
+        offset += 2;
+    }
+
+    let (rom, used) = inflate(data.get(offset..).unwrap_or_default())?;
+    let trailer = offset + used;
+    check_crc(&rom, read_u32(data, trailer)?)?;
+    if read_u32(data, trailer + 4)? != rom.len() as u32 {
+        return Err(malformed("Size mismatch in gzip trailer"));
+    }
+    Ok(rom)
+}
+
+pub fn unzip(data: &[u8]) -> crate::Result<Vec<u8>> {
+    // the end of central directory record is at least 22 bytes from the end
+    let end = (0..data.len().saturating_sub(21))
+        .rev()
+        .find(|offset| read_u32(data, *offset).ok() == Some(ZIP_END_OF_DIRECTORY))
+        .ok_or_else(|| malformed("Missing zip central directory"))?;
+    let entries = read_u16(data, end + 10)?;
+    let mut offset = read_u32(data, end + 16)? as usize;
+
+    for _ in 0..entries {
+        if read_u32(data, offset)? != ZIP_CENTRAL_HEADER {
+            return Err(malformed("Invalid zip central directory"));
+        }
+        let method = read_u16(data, offset + 10)?;
+        let crc = read_u32(data, offset + 16)?;
+        let compressed_size = read_u32(data, offset + 20)? as usize;
+        let name_len = read_u16(data, offset + 28)? as usize;
+        let extra_len = read_u16(data, offset + 30)? as usize;
+        let comment_len = read_u16(data, offset + 32)? as usize;
+        let local = read_u32(data, offset + 42)? as usize;
+        let name = data
+            .get(offset + 46..offset + 46 + name_len)
+            .map(String::from_utf8_lossy)
+            .ok_or_else(|| malformed("Unexpected end of archive"))?
+            .to_lowercase();
+        offset += 46 + name_len + extra_len + comment_len;
+
+        if !name.ends_with(".gb") && !name.ends_with(".gbc") {
+            continue;
+        }
+        log::debug!("Loading {} from zip archive", name);
+
+        if read_u32(data, local)? != ZIP_LOCAL_HEADER {
+            return Err(malformed("Invalid zip local header"));
+        }
+        let start = local + 30 + read_u16(data, local + 26)? as usize + read_u16(data, local + 28)? as usize;
+        let compressed = data
+            .get(start..start + compressed_size)
+            .ok_or_else(|| malformed("Unexpected end of archive"))?;
+        let rom = match method {
+            0 => compressed.to_vec(),
+            8 => inflate(compressed)?.0,
+            _ => return Err(Error::Archive(format!("Unsupported zip compression method {}", method))),
+        };
+        check_crc(&rom, crc)?;
+        return Ok(rom);
+    }
+    Err(malformed("No .gb or .gbc file in zip archive"))
+}
+
+// DEFLATE (RFC 1951)
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+// order in which code length code lengths are stored
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    bit: usize,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u8) -> crate::Result<u32> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = self
+                .data
+                .get(self.bit / 8)
+                .ok_or_else(|| malformed("Unexpected end of deflate stream"))?;
+            value |= ((byte >> (self.bit % 8)) as u32 & 1) << i;
+            self.bit += 1;
+        }
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        self.bit = self.bit.div_ceil(8) * 8;
+    }
+}
+
+// canonical Huffman code, decoded one bit at a time
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        lengths.iter().for_each(|length| counts[*length as usize] += 1);
+        counts[0] = 0;
+
+        let mut symbols = Vec::with_capacity(lengths.len());
+        for length in 1..16 {
+            symbols.extend((0..lengths.len() as u16).filter(|symbol| lengths[*symbol as usize] == length));
+        }
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> crate::Result<u16> {
+        let (mut code, mut first, mut index) = (0u32, 0u32, 0u32);
+        for count in &self.counts[1..] {
+            code |= reader.bits(1)?;
+            let count = *count as u32;
+            if code < first + count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(malformed("Invalid Huffman code"))
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [8u8; 288];
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> crate::Result<(Huffman, Huffman)> {
+    let literals = reader.bits(5)? as usize + 257;
+    let distances = reader.bits(5)? as usize + 1;
+    let code_lengths = reader.bits(4)? as usize + 4;
+
+    let mut lengths = [0u8; 19];
+    for index in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[*index] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&lengths);
+
+    let mut lengths = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let (value, repeat) = match code_length_code.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths.last().ok_or_else(|| malformed("Repeat without a code length"))?;
+                (previous, 3 + reader.bits(2)?)
+            }
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > literals + distances {
+        return Err(malformed("Too many code lengths"));
+    }
+    Ok((Huffman::new(&lengths[..literals]), Huffman::new(&lengths[literals..])))
+}
+
+// returns the inflated data and the number of compressed bytes consumed
+pub fn inflate(data: &[u8]) -> crate::Result<(Vec<u8>, usize)> {
+    let mut reader = BitReader { data, bit: 0 };
+    let mut out = Vec::new();
+
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let start = reader.bit / 8;
+                let len = read_u16(data, start)? as usize;
+                if read_u16(data, start + 2)? != !(len as u16) {
+                    return Err(malformed("Invalid stored block length"));
+                }
+                let block = data
+                    .get(start + 4..start + 4 + len)
+                    .ok_or_else(|| malformed("Unexpected end of deflate stream"))?;
+                if out.len() + len > ROM_MAX_SIZE {
+                    return Err(too_large());
+                }
+                out.extend_from_slice(block);
+                reader.bit = (start + 4 + len) * 8;
+            }
+            kind @ (1 | 2) => {
+                let (literal, distance) = match kind {
+                    1 => fixed_codes(),
+                    _ => dynamic_codes(&mut reader)?,
+                };
+                loop {
+                    let symbol = literal.decode(&mut reader)? as usize;
+                    match symbol {
+                        0..=255 => out.push(symbol as u8),
+                        256 => break,
+                        257..=285 => {
+                            let index = symbol - 257;
+                            let length = LENGTH_BASE[index] as usize + reader.bits(LENGTH_EXTRA[index])? as usize;
+                            let index = distance.decode(&mut reader)? as usize;
+                            if index >= DISTANCE_BASE.len() {
+                                return Err(malformed("Invalid distance code"));
+                            }
+                            let distance =
+                                DISTANCE_BASE[index] as usize + reader.bits(DISTANCE_EXTRA[index])? as usize;
+                            if distance > out.len() {
+                                return Err(malformed("Distance beyond start of output"));
+                            }
+                            let start = out.len() - distance;
+                            // the copy may overlap the bytes it produces
+                            for i in 0..length {
+                                out.push(out[start + i]);
+                            }
+                        }
+                        _ => return Err(malformed("Invalid literal/length code")),
+                    }
+                    if out.len() > ROM_MAX_SIZE {
+                        return Err(too_large());
+                    }
+                }
+            }
+            _ => return Err(malformed("Invalid deflate block type")),
+        }
+        if last {
+            return Ok((out, reader.bit.div_ceil(8)));
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use crate::{expr::Expr, gameboy::Gameboy, symbols::SymbolTable, utils, Error};
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakAction {
+    // log a one line CPU snapshot
+    Log,
+    // stop emulation until the frontend resumes it
+    Pause,
+    // log registers, flags, stack and the memory around HL
+    Dump,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    // PC range, `None` evaluates the condition before every instruction
+    pub range: Option<(u16, u16)>,
+    // only fire while this ROM bank is mapped at PC
+    pub bank: Option<usize>,
+    pub condition: Option<Expr>,
+    // ignore the first `after - 1` hits
+    pub after: u64,
+    pub actions: Vec<BreakAction>,
+    hits: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakHit {
+    pub index: usize,
+    pub bank: usize,
+    pub pc: u16,
+    pub hits: u64,
+}
+
+impl Breakpoint {
+    pub fn at(address: u16) -> Self {
+        Self::range(address, address)
+    }
+
+    pub fn range(start: u16, end: u16) -> Self {
+        Self {
+            range: Some((start, end)),
+            bank: None,
+            condition: None,
+            after: 0,
+            actions: vec![BreakAction::Log],
+            hits: 0,
+        }
+    }
+
+    pub fn when(condition: Expr) -> Self {
+        Self {
+            range: None,
+            bank: None,
+            condition: Some(condition),
+            after: 0,
+            actions: vec![BreakAction::Log],
+            hits: 0,
+        }
+    }
+
+    pub fn with_bank(mut self, bank: usize) -> Self {
+        self.bank = Some(bank);
+        self
+    }
+
+    pub fn with_condition(mut self, condition: Expr) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+
+    pub fn with_after(mut self, after: u64) -> Self {
+        self.after = after;
+        self
+    }
+
+    pub fn with_actions(mut self, actions: Vec<BreakAction>) -> Self {
+        self.actions = actions;
+        self
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn reset_hits(&mut self) {
+        self.hits = 0;
+    }
+
+    // Checks the breakpoint against the current CPU state, counting the hit.
+    // Returns true once the hit count reached `after`.
+    pub fn check(&mut self, gb: &Gameboy) -> bool {
+        let pc = gb.cpu.pc;
+        if let Some((start, end)) = self.range {
+            if pc < start || pc > end {
+                return false;
+            }
+        }
+
+        if let Some(bank) = self.bank {
+            if gb.cart.bank(pc) != bank {
+                return false;
+            }
+        }
+
+        if let Some(condition) = &self.condition {
+            if !condition.is_true(gb) {
+                return false;
+            }
+        }
+
+        self.hits += 1;
+        self.hits >= self.after
+    }
+
+    // Parses a breakpoint, resolving symbol names in locations and conditions.
+    //
+    //   [LOCATION] [if CONDITION] [after N] [do ACTION[+ACTION...]]
+    //
+    //   LOCATION   symbol, addr, bank:addr, addr-addr or bank:addr-bank:addr (hex)
+    //   CONDITION  expression, see `expr`
+    //   N          only break from the Nth hit on
+    //   ACTION     log (default), pause or dump
+    //
+    // A spec without a location is a condition checked before every
+    // instruction, i.e. `PC==$0150 && A>=$10 && [$C000]==$FF && bank==3`.
+    pub fn parse_with(s: &str, symbols: &SymbolTable) -> crate::Result<Self> {
+        let resolve = |name: &str| symbols.resolve(name).map(|(_, address)| address as i64);
+        let resolve = &resolve;
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let keyword = |w: &&str| matches!(*w, "if" | "after" | "do");
+
+        let head_len = words.iter().position(keyword).unwrap_or(words.len());
+        let head = words[..head_len].join(" ");
+
+        let mut breakpoint = match (head.as_str(), symbols.resolve(&head)) {
+            ("" | "*", _) => None,
+            (_, Some((bank, address))) => Some(Breakpoint::at(address).with_bank(bank)),
+            (head, None) if head_len == 1 => match utils::parse_bank_range(head) {
+                Ok((bank, start, end)) => {
+                    let breakpoint = Breakpoint::range(start, end);
+                    Some(match bank {
+                        Some(bank) => breakpoint.with_bank(bank),
+                        None => breakpoint,
+                    })
+                }
+                Err(_) => Some(Breakpoint::when(Expr::parse_with(head, resolve)?)),
+            },
+            (head, None) => Some(Breakpoint::when(Expr::parse_with(head, resolve)?)),
+        };
+
+        let mut rest = &words[head_len..];
+        while let Some((keyword, tail)) = rest.split_first() {
+            let len = tail.iter().position(|w| matches!(*w, "if" | "after" | "do"));
+            let (args, next) = tail.split_at(len.unwrap_or(tail.len()));
+            let args = args.join(" ");
+            rest = next;
+
+            match *keyword {
+                "if" => {
+                    let condition = Expr::parse_with(&args, resolve)?;
+                    breakpoint = Some(match breakpoint {
+                        Some(b) if b.condition.is_some() => {
+                            return Err(Error::Parse(format!("Duplicate condition: {:?}", s)))
+                        }
+                        Some(b) => b.with_condition(condition),
+                        None => Breakpoint::when(condition),
+                    });
+                }
+                "after" => {
+                    let after = args
+                        .parse::<u64>()
+                        .map_err(|_| Error::Parse(format!("Invalid hit count: {:?}", args)))?;
+                    breakpoint = breakpoint.map(|b| b.with_after(after));
+                }
+                _ => {
+                    let actions = args
+                        .split('+')
+                        .map(|a| match a.trim().to_lowercase().as_str() {
+                            "log" => Ok(BreakAction::Log),
+                            "pause" => Ok(BreakAction::Pause),
+                            "dump" => Ok(BreakAction::Dump),
+                            _ => Err(Error::Parse(format!("Invalid breakpoint action: {:?}", a))),
+                        })
+                        .collect::<crate::Result<Vec<_>>>()?;
+                    breakpoint = breakpoint.map(|b| b.with_actions(actions));
+                }
+            }
+        }
+
+        breakpoint.ok_or_else(|| Error::Parse(format!("Breakpoint needs a location or condition: {:?}", s)))
+    }
+}
+
+impl FromStr for Breakpoint {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Breakpoint::parse_with(s, &SymbolTable::new())
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bank = match self.bank {
+            Some(bank) => format!("{:02X}:", bank),
+            None => String::new(),
+        };
+
+        match self.range {
+            Some((start, end)) if start == end => write!(f, "{}{:04X}", bank, start)?,
+            Some((start, end)) => write!(f, "{}{:04X}-{}{:04X}", bank, start, bank, end)?,
+            None => write!(f, "*")?,
+        }
+
+        if let Some(condition) = &self.condition {
+            write!(f, " if {}", condition)?;
+        }
+        if self.after > 1 {
+            write!(f, " after {}", self.after)?;
+        }
+
+        let actions: Vec<&str> = self
+            .actions
+            .iter()
+            .map(|a| match a {
+                BreakAction::Log => "log",
+                BreakAction::Pause => "pause",
+                BreakAction::Dump => "dump",
+            })
+            .collect();
+        write!(f, " do {}", actions.join("+"))
+    }
+}
@@ -0,0 +1,159 @@
+// Shadow call stack and recent instruction history.
+//
+// CALL, RST and interrupt dispatch push a frame holding the stack slot of the
+// return address. A frame is dropped as soon as SP moves above that slot, so
+// RET/RETI as well as POP, `ld sp, hl` or `add sp, e` tricks that discard a
+// return address unwind it.
+
+use crate::disasm::Instruction;
+use crate::symbols::SymbolTable;
+
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+// how many executed instructions are remembered
+pub const HISTORY_LEN: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    Call,
+    Rst,
+    Interrupt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackFrame {
+    pub kind: FrameKind,
+    // bank:address of the subroutine or vector entered
+    pub bank: usize,
+    pub target: u16,
+    // bank:address of the call, or of the interrupted instruction
+    pub caller_bank: usize,
+    pub caller: u16,
+    pub return_addr: u16,
+    // stack slot holding the return address
+    pub sp: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Executed {
+    pub bank: usize,
+    pub pc: u16,
+    pub bytes: [u8; 3],
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CallStack {
+    frames: Vec<StackFrame>,
+    history: VecDeque<Executed>,
+}
+
+impl CallStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reset(&mut self) {
+        self.frames.clear();
+        self.history.clear();
+    }
+
+    // frames from the outermost call to the innermost
+    pub fn frames(&self) -> &[StackFrame] {
+        &self.frames
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn push(&mut self, frame: StackFrame) {
+        self.frames.push(frame);
+    }
+
+    // Drops every frame whose return address is no longer on the stack.
+    #[inline]
+    pub fn unwind(&mut self, sp: u16) {
+        while let Some(frame) = self.frames.last() {
+            if frame.sp >= sp {
+                break;
+            }
+            log::trace!(
+                "Unwound frame {:02X}:{:04X} returning to {:04X} (SP {:04X})",
+                frame.bank,
+                frame.target,
+                frame.return_addr,
+                sp
+            );
+            self.frames.pop();
+        }
+    }
+
+    #[inline]
+    pub fn record(&mut self, bank: usize, pc: u16, bytes: [u8; 3]) {
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(Executed { bank, pc, bytes });
+    }
+
+    // executed instructions, oldest first
+    pub fn history(&self) -> impl Iterator<Item = &Executed> + '_ {
+        self.history.iter()
+    }
+
+    // One line per frame, innermost first, starting at bank:pc.
+    pub fn backtrace(&self, bank: usize, pc: u16, symbols: Option<&SymbolTable>) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "#0  {}", location(symbols, bank, pc));
+        for (depth, frame) in self.frames.iter().rev().enumerate() {
+            let note = match frame.kind {
+                FrameKind::Interrupt => " (interrupted)",
+                _ => "",
+            };
+            let _ = writeln!(
+                out,
+                "#{:<2} {}{}",
+                depth + 1,
+                location(symbols, frame.caller_bank, frame.caller),
+                note
+            );
+        }
+        out
+    }
+
+    // The last executed instructions, disassembled, oldest first.
+    pub fn history_listing(&self, symbols: Option<&SymbolTable>) -> String {
+        let mut out = String::new();
+        for executed in self.history.iter() {
+            let instruction = Instruction::decode(executed.bank, executed.pc, &executed.bytes);
+            let (bytes, text) = match &instruction {
+                Some(instruction) => (&executed.bytes[..instruction.length as usize], instruction.to_string()),
+                None => (&executed.bytes[..1], format!("db ${:02X}", executed.bytes[0])),
+            };
+            let bytes: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+            let line = format!(
+                "{:<8} {:<9} {}",
+                format!("{:02X}:{:04X}", executed.bank, executed.pc),
+                bytes.join(" "),
+                text
+            );
+            match symbols.and_then(|s| s.name_at(executed.bank, executed.pc)) {
+                Some(name) => {
+                    let _ = writeln!(out, "{:<30} ; {}", line, name);
+                }
+                None => {
+                    let _ = writeln!(out, "{}", line);
+                }
+            }
+        }
+        out
+    }
+}
+
+fn location(symbols: Option<&SymbolTable>, bank: usize, addr: u16) -> String {
+    match symbols.and_then(|s| s.describe(bank, addr)) {
+        Some(symbol) => format!("{:02X}:{:04X} <{}>", bank, addr, symbol),
+        None => format!("{:02X}:{:04X}", bank, addr),
+    }
+}
@@ -1,9 +1,27 @@
-use crate::{globals::*, mbc::*, utils, Error};
+use crate::archive;
+use crate::header::{self, CartridgeHeader, Mapper};
+use crate::validation::{ValidationPolicy, ValidationReport, NINTENDO_LOGO};
+use crate::{globals::*, mbc::*, Error};
+
+use std::path::{Path, PathBuf};
+
+// MBC1M compilation carts are 1 MiB with a game, header included, every 16
+// banks; a second logo at bank 0x10 gives them away
+fn is_mbc1_multicart(rom: &[u8]) -> bool {
+    let logo = 0x10 * ROM_BANK_SIZE + CART_NINTENDO_LOGO_START as usize;
+    rom.len() == 64 * ROM_BANK_SIZE && rom[logo..logo + NINTENDO_LOGO.len()] == NINTENDO_LOGO
+}
+
 pub enum Cartridge {
     Empty,
     DummyMBC(DummyMBC), // used for testing only
     MBC0(MBC0),
     MBC1(MBC1),
+    MMM01(MMM01),
+    HuC1(HuC1),
+    HuC3(HuC3),
+    MBC7(MBC7),
+    PocketCamera(PocketCamera),
 }
 
 impl Cartridge {
@@ -11,26 +29,52 @@ impl Cartridge {
         match self {
             Self::MBC0(mbc) => &mbc.rom,
             Self::MBC1(mbc) => &mbc.rom,
-            _ => panic!("Invalid cart type"),
+            Self::MMM01(mbc) => &mbc.rom,
+            Self::HuC1(mbc) => &mbc.rom,
+            Self::HuC3(mbc) => &mbc.rom,
+            Self::MBC7(mbc) => &mbc.rom,
+            Self::PocketCamera(mbc) => &mbc.rom,
+            Self::DummyMBC(mbc) => &mbc.rom,
+            Self::Empty => &[],
         }
     }
 
-    pub fn load_rom(&mut self, rom: &[u8]) {
+    pub fn load_rom(&mut self, rom: &[u8]) -> crate::Result<()> {
         log::trace!("Loading ROM into cartridge");
         log::trace!("ROM length: {} bytes", rom.len());
 
-        match self {
-            Self::MBC0(mbc) => mbc.rom[..rom.len()].copy_from_slice(&rom),
-            Self::MBC1(mbc) => mbc.rom[..rom.len()].copy_from_slice(&rom),
-            _ => log::error!("Invalid cart type"),
+        let target: &mut [u8] = match self {
+            Self::MBC0(mbc) => &mut mbc.rom,
+            Self::MBC1(mbc) => &mut mbc.rom,
+            Self::MMM01(mbc) => &mut mbc.rom,
+            Self::HuC1(mbc) => &mut mbc.rom,
+            Self::HuC3(mbc) => &mut mbc.rom,
+            Self::MBC7(mbc) => &mut mbc.rom,
+            Self::PocketCamera(mbc) => &mut mbc.rom,
+            Self::DummyMBC(mbc) => &mut mbc.rom,
+            Self::Empty => &mut [],
+        };
+        if rom.len() > target.len() {
+            return Err(Error::SizeMismatch {
+                expected: target.len(),
+                actual: rom.len(),
+            });
         }
+        target[..rom.len()].copy_from_slice(rom);
+        Ok(())
     }
 
     pub fn rom_banks(&self) -> usize {
         match self {
             Self::MBC0(mbc) => mbc.rom_banks(),
             Self::MBC1(mbc) => mbc.rom_banks(),
-            _ => panic!("Invalid cart type"),
+            Self::MMM01(mbc) => mbc.rom_banks(),
+            Self::HuC1(mbc) => mbc.rom_banks(),
+            Self::HuC3(mbc) => mbc.rom_banks(),
+            Self::MBC7(mbc) => mbc.rom_banks(),
+            Self::PocketCamera(mbc) => mbc.rom_banks(),
+            Self::DummyMBC(mbc) => mbc.rom_banks(),
+            Self::Empty => 0,
         }
     }
 
@@ -38,7 +82,42 @@ impl Cartridge {
         match self {
             Self::MBC0(mbc) => mbc.ram_banks(),
             Self::MBC1(mbc) => mbc.ram_banks(),
-            _ => panic!("Invalid cart type"),
+            Self::MMM01(mbc) => mbc.ram_banks(),
+            Self::HuC1(mbc) => mbc.ram_banks(),
+            Self::HuC3(mbc) => mbc.ram_banks(),
+            Self::MBC7(mbc) => mbc.ram_banks(),
+            Self::PocketCamera(mbc) => mbc.ram_banks(),
+            Self::DummyMBC(mbc) => mbc.ram_banks(),
+            Self::Empty => 0,
+        }
+    }
+
+    // bank currently mapped at address, 0 for unbanked regions
+    pub fn bank(&self, address: u16) -> usize {
+        match address {
+            0x0000..=0x7FFF => match self {
+                Self::DummyMBC(mbc) => mbc.rom_bank(address as usize),
+                Self::MBC0(mbc) => mbc.rom_bank(address as usize),
+                Self::MBC1(mbc) => mbc.rom_bank(address as usize),
+                Self::MMM01(mbc) => mbc.rom_bank(address as usize),
+                Self::HuC1(mbc) => mbc.rom_bank(address as usize),
+                Self::HuC3(mbc) => mbc.rom_bank(address as usize),
+                Self::MBC7(mbc) => mbc.rom_bank(address as usize),
+                Self::PocketCamera(mbc) => mbc.rom_bank(address as usize),
+                _ => 0,
+            },
+            0xA000..=0xBFFF => match self {
+                Self::DummyMBC(mbc) => mbc.ram_bank(),
+                Self::MBC0(mbc) => mbc.ram_bank(),
+                Self::MBC1(mbc) => mbc.ram_bank(),
+                Self::MMM01(mbc) => mbc.ram_bank(),
+                Self::HuC1(mbc) => mbc.ram_bank(),
+                Self::HuC3(mbc) => mbc.ram_bank(),
+                Self::MBC7(mbc) => mbc.ram_bank(),
+                Self::PocketCamera(mbc) => mbc.ram_bank(),
+                _ => 0,
+            },
+            _ => 0,
         }
     }
 
@@ -52,9 +131,12 @@ impl Cartridge {
                     Self::DummyMBC(mbc) => mbc.read(address as usize),
                     Self::MBC0(mbc) => mbc.read(address as usize),
                     Self::MBC1(mbc) => mbc.read(address as usize),
-                    _ => {
-                        panic!("Cart type not supported for reading ROM bank")
-                    }
+                    Self::MMM01(mbc) => mbc.read(address as usize),
+                    Self::HuC1(mbc) => mbc.read(address as usize),
+                    Self::HuC3(mbc) => mbc.read(address as usize),
+                    Self::MBC7(mbc) => mbc.read(address as usize),
+                    Self::PocketCamera(mbc) => mbc.read(address as usize),
+                    Self::Empty => 0xFF,
                 }
             }
             0xA000..=0xBFFF => {
@@ -63,9 +145,12 @@ impl Cartridge {
                     Self::DummyMBC(mbc) => mbc.read_sram(address as usize - 0xA000),
                     Self::MBC0(mbc) => mbc.read_sram(address as usize - 0xA000),
                     Self::MBC1(mbc) => mbc.read_sram(address as usize - 0xA000),
-                    _ => {
-                        panic!("Cart type not supported for reading SRAM bank")
-                    }
+                    Self::MMM01(mbc) => mbc.read_sram(address as usize - 0xA000),
+                    Self::HuC1(mbc) => mbc.read_sram(address as usize - 0xA000),
+                    Self::HuC3(mbc) => mbc.read_sram(address as usize - 0xA000),
+                    Self::MBC7(mbc) => mbc.read_sram(address as usize - 0xA000),
+                    Self::PocketCamera(mbc) => mbc.read_sram(address as usize - 0xA000),
+                    Self::Empty => 0xFF,
                 }
             }
             _ => 0,
@@ -82,7 +167,12 @@ impl Cartridge {
                     Self::DummyMBC(mbc) => mbc.write(address as usize, value),
                     Self::MBC0(mbc) => mbc.write(address as usize, value),
                     Self::MBC1(mbc) => mbc.write(address as usize, value),
-                    _ => panic!("Cart type not supported for writing ROM bank"),
+                    Self::MMM01(mbc) => mbc.write(address as usize, value),
+                    Self::HuC1(mbc) => mbc.write(address as usize, value),
+                    Self::HuC3(mbc) => mbc.write(address as usize, value),
+                    Self::MBC7(mbc) => mbc.write(address as usize, value),
+                    Self::PocketCamera(mbc) => mbc.write(address as usize, value),
+                    Self::Empty => {}
                 }
             }
             0xA000..=0xBFFF => {
@@ -91,7 +181,12 @@ impl Cartridge {
                     Self::DummyMBC(mbc) => mbc.write_sram(address as usize - 0xA000, value),
                     Self::MBC0(mbc) => mbc.write_sram(address as usize - 0xA000, value),
                     Self::MBC1(mbc) => mbc.write_sram(address as usize - 0xA000, value),
-                    _ => panic!("Cart type not supported for writing SRAM bank"),
+                    Self::MMM01(mbc) => mbc.write_sram(address as usize - 0xA000, value),
+                    Self::HuC1(mbc) => mbc.write_sram(address as usize - 0xA000, value),
+                    Self::HuC3(mbc) => mbc.write_sram(address as usize - 0xA000, value),
+                    Self::MBC7(mbc) => mbc.write_sram(address as usize - 0xA000, value),
+                    Self::PocketCamera(mbc) => mbc.write_sram(address as usize - 0xA000, value),
+                    Self::Empty => {}
                 }
             }
             _ => log::error!("Invalid cart type"),
@@ -101,88 +196,252 @@ impl Cartridge {
         Self::Empty
     }
 
-    pub fn new(filename: &str) -> anyhow::Result<Cartridge> {
-        log::debug!("Reading ROM: {}", filename);
+    pub fn header(&self) -> crate::Result<CartridgeHeader> {
+        CartridgeHeader::parse(header::header_area(self.rom()))
+    }
 
-        let rom = std::fs::read(filename).expect("Unable to read file");
+    pub fn has_battery(&self) -> bool {
+        self.header().map(|header| header.features.battery).unwrap_or(false)
+    }
 
-        // Cache ROM contents into the global ROM heap
-        log::debug!("ROM length: {} bytes", rom.len());
-        let calc_rom_banks = rom.len() / ROM_BANK_SIZE;
-        log::debug!("Calculated ROM banks: {}", calc_rom_banks);
-
-        log::debug!("Cache ROM into global ROM heap");
-        // ROM.lock().unwrap()[..rom.len()].copy_from_slice(&rom);
-
-        let ram_banks = match rom[CART_SRAM_SIZE as usize] {
-            0x00 => None,
-            0x01 => panic!("Invalid RAM bank size"),
-            0x02 => Some(1),
-            0x03 => Some(4),
-            0x04 => Some(16),
-            0x05 => Some(8),
-            _ => panic!("Invalid RAM bank size"),
+    // RAM the cart has, None when it has none
+    pub fn sram(&self) -> Option<&[u8]> {
+        let sram: &[u8] = match self {
+            Self::MBC1(mbc) => &mbc.sram,
+            Self::MMM01(mbc) => &mbc.sram,
+            Self::HuC1(mbc) => &mbc.sram,
+            Self::HuC3(mbc) => &mbc.sram,
+            Self::PocketCamera(mbc) => &mbc.sram,
+            // the EEPROM stands in for RAM
+            Self::MBC7(mbc) => return Some(&mbc.eeprom),
+            _ => return None,
+        };
+        let size = (self.ram_banks() * RAM_BANK_SIZE).min(sram.len());
+        (size > 0).then(|| &sram[..size])
+    }
+
+    fn sram_mut(&mut self) -> Option<&mut [u8]> {
+        let size = self.sram()?.len();
+        let sram: &mut [u8] = match self {
+            Self::MBC1(mbc) => &mut mbc.sram,
+            Self::MMM01(mbc) => &mut mbc.sram,
+            Self::HuC1(mbc) => &mut mbc.sram,
+            Self::HuC3(mbc) => &mut mbc.sram,
+            Self::PocketCamera(mbc) => &mut mbc.sram,
+            Self::MBC7(mbc) => &mut mbc.eeprom,
+            _ => return None,
         };
+        Some(&mut sram[..size])
+    }
+
+    // tilt in g along each axis for carts with an accelerometer
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        if let Self::MBC7(mbc) = self {
+            mbc.set_tilt(x, y);
+        }
+    }
 
-        match ram_banks {
-            Some(ram_banks) => log::debug!("Detected {} RAM banks", ram_banks),
-            None => log::debug!("No RAM banks detected"),
+    // image seen by the Pocket Camera's sensor, ignored by other carts
+    pub fn set_camera_image(&mut self, image: &[u8]) -> crate::Result<()> {
+        match self {
+            Self::PocketCamera(mbc) => mbc.set_image(image),
+            _ => Ok(()),
         }
+    }
+
+    // `.sav` next to the ROM, as other emulators name it
+    pub fn save_path<P: AsRef<Path>>(rom_path: P) -> PathBuf {
+        rom_path.as_ref().with_extension("sav")
+    }
 
-        let rom_banks = match rom[CART_ROM_SIZE as usize] {
-            0x00 => 2,
-            0x01 => 4,
-            0x02 => 8,
-            0x03 => 16,
-            0x04 => 32,
-            0x05 => 64,
-            0x06 => 128,
-            0x07 => 256,
-            0x08 => 512,
-            _ => panic!("Invalid ROM bank size"),
+    // Battery backed RAM followed by the clock state on carts with one,
+    // None for carts without a battery.
+    pub fn save_data(&self) -> Option<Vec<u8>> {
+        if !self.has_battery() {
+            return None;
+        }
+        let mut data = self.sram().unwrap_or_default().to_vec();
+        if let Self::HuC3(mbc) = self {
+            data.extend(mbc.rtc_save_data());
+        }
+        Some(data)
+    }
+
+    // A save without the clock state leaves the clock running from now.
+    pub fn load_save_data(&mut self, data: &[u8]) -> crate::Result<()> {
+        let size = match self.sram_mut() {
+            Some(sram) if data.len() < sram.len() => {
+                return Err(Error::SizeMismatch {
+                    expected: sram.len(),
+                    actual: data.len(),
+                })
+            }
+            Some(sram) => {
+                let size = sram.len();
+                sram.copy_from_slice(&data[..size]);
+                size
+            }
+            None => 0,
         };
-        log::debug!("Detected {} ROM banks", rom_banks);
+        if let Self::HuC3(mbc) = self {
+            if data.len() > size {
+                mbc.load_rtc_save_data(&data[size..])?;
+            }
+        }
+        Ok(())
+    }
 
-        assert_eq!(rom_banks, calc_rom_banks);
+    // does nothing for carts without a battery
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        if let Some(data) = self.save_data() {
+            std::fs::write(path, data)?;
+        }
+        Ok(())
+    }
+
+    pub fn load_save<P: AsRef<Path>>(&mut self, path: P) -> crate::Result<()> {
+        self.load_save_data(&std::fs::read(path)?)
+    }
 
-        let checksum = utils::calculate_checksum(
-            &rom[CART_TITLE_START as usize..CART_MASK_ROM_VERSION_NUMBER as usize],
-        );
+    pub fn new(filename: &str) -> crate::Result<Cartridge> {
+        Self::load(filename, ValidationPolicy::default())
+    }
 
-        let header_checksum = rom[CART_HEADER_CHECKSUM as usize];
+    // Loads a ROM, handling a bad logo or checksum as the policy says.
+    pub fn load(filename: &str, policy: ValidationPolicy) -> crate::Result<Cartridge> {
+        log::debug!("Reading ROM: {}", filename);
+        Self::from_bytes_with_policy(&std::fs::read(filename)?, policy)
+    }
 
-        match header_checksum == checksum {
-            true => log::debug!("Checksums match"),
-            false => {
-                log::error!("Checksums do not match");
-                return Err(Error::msg("Checksums do not match"));
-            }
+    // Loads a ROM image, or a .zip/.gz archive holding one, from memory.
+    pub fn from_bytes(data: &[u8]) -> crate::Result<Cartridge> {
+        Self::from_bytes_with_policy(data, ValidationPolicy::default())
+    }
+
+    pub fn from_bytes_with_policy(data: &[u8], policy: ValidationPolicy) -> crate::Result<Cartridge> {
+        Self::from_rom(&archive::extract_rom(data)?, policy)
+    }
+
+    // Loads an already extracted ROM image.
+    pub(crate) fn from_rom(rom: &[u8], policy: ValidationPolicy) -> crate::Result<Cartridge> {
+        log::debug!("ROM length: {} bytes", rom.len());
+        // MMM01 carts keep their header in the last 32 KiB
+        let header_area = header::header_area(rom);
+        let header = CartridgeHeader::parse(header_area)?;
+
+        let ram_banks = header.ram_banks().ok_or(Error::InvalidHeader {
+            field: "RAM size",
+            value: header_area[CART_SRAM_SIZE as usize],
+        })?;
+        log::debug!("Detected {} RAM banks", ram_banks);
+
+        let rom_size = header_area[CART_ROM_SIZE as usize];
+        let invalid_rom_size = Error::InvalidHeader {
+            field: "ROM size",
+            value: rom_size,
+        };
+        let rom_banks = header.rom_banks().ok_or(invalid_rom_size)?;
+        log::debug!("Detected {} ROM banks", rom_banks);
+
+        if rom.len() != rom_banks * ROM_BANK_SIZE {
+            return Err(Error::SizeMismatch {
+                expected: rom_banks * ROM_BANK_SIZE,
+                actual: rom.len(),
+            });
         }
 
-        // let sram = vec![0u8; (RAM_BANK_SIZE * RAM_MAX_BANKS) + 1];
-        let mut cart = Cartridge::Empty;
+        let report = ValidationReport::new(header_area)?;
+        report.apply(policy)?;
+        if report.is_valid() {
+            log::debug!("Logo and checksums match");
+        }
 
-        let cart_type_id = rom[CART_TYPE as usize];
-        log::debug!("Cart type: {}", cart_type_map(cart_type_id));
-        match cart_type_id {
-            0x00 => {
+        log::debug!("Cart type: {}", cart_type_map(header.cart_type));
+        let mut cart = match header.mapper {
+            Mapper::RomOnly if !header.features.ram => {
                 log::debug!("Initializing MBC0 cartridge type");
-                cart = Cartridge::MBC0(MBC0::new());
+                if rom_banks > ROM_MAX_BANKS_MBC0 {
+                    return Err(Error::InvalidHeader {
+                        field: "ROM size",
+                        value: rom_size,
+                    });
+                }
+                Cartridge::MBC0(MBC0::new())
             }
-            0x01 | 0x02 | 0x03 => {
+            Mapper::Mbc1 => {
                 log::debug!("Initializing MBC1 cartridge type");
-                cart = Cartridge::MBC1(MBC1::new(rom_banks, ram_banks.unwrap_or(0)))
+                if rom_banks > ROM_MAX_BANKS_MBC1 {
+                    return Err(Error::InvalidHeader {
+                        field: "ROM size",
+                        value: rom_size,
+                    });
+                }
+                match is_mbc1_multicart(rom) {
+                    true => {
+                        log::debug!("Detected MBC1 multicart");
+                        Cartridge::MBC1(MBC1::multicart(rom_banks, ram_banks))
+                    }
+                    false => Cartridge::MBC1(MBC1::new(rom_banks, ram_banks)),
+                }
+            }
+            Mapper::Mmm01 => {
+                log::debug!("Initializing MMM01 cartridge type");
+                if rom_banks > ROM_MAX_BANKS_MMM01 {
+                    return Err(Error::InvalidHeader {
+                        field: "ROM size",
+                        value: rom_size,
+                    });
+                }
+                Cartridge::MMM01(MMM01::new(rom_banks, ram_banks))
+            }
+            Mapper::HuC1 => {
+                log::debug!("Initializing HuC1 cartridge type");
+                if rom_banks > ROM_MAX_BANKS_HUC1 {
+                    return Err(Error::InvalidHeader {
+                        field: "ROM size",
+                        value: rom_size,
+                    });
+                }
+                Cartridge::HuC1(HuC1::new(rom_banks, ram_banks))
+            }
+            Mapper::HuC3 => {
+                log::debug!("Initializing HuC3 cartridge type");
+                if rom_banks > ROM_MAX_BANKS_HUC3 {
+                    return Err(Error::InvalidHeader {
+                        field: "ROM size",
+                        value: rom_size,
+                    });
+                }
+                Cartridge::HuC3(HuC3::new(rom_banks, ram_banks))
+            }
+            Mapper::Mbc7 => {
+                log::debug!("Initializing MBC7 cartridge type");
+                if rom_banks > ROM_MAX_BANKS_MBC7 {
+                    return Err(Error::InvalidHeader {
+                        field: "ROM size",
+                        value: rom_size,
+                    });
+                }
+                Cartridge::MBC7(MBC7::new(rom_banks))
+            }
+            Mapper::PocketCamera => {
+                log::debug!("Initializing Pocket Camera cartridge type");
+                if rom_banks > ROM_MAX_BANKS_CAMERA {
+                    return Err(Error::InvalidHeader {
+                        field: "ROM size",
+                        value: rom_size,
+                    });
+                }
+                Cartridge::PocketCamera(PocketCamera::new(rom_banks, ram_banks))
             }
-
             _ => {
                 log::error!("Unsupported cartridge type");
-                return Err(Error::msg("Unsupported cartridge type"));
+                return Err(Error::UnsupportedMapper(header.cart_type));
             }
-        }
-        cart.load_rom(&rom);
+        };
+        cart.load_rom(rom)?;
 
-        let metadata = utils::get_metadata(&cart);
-        log::debug!("Cartridge metadata:\n{}", metadata);
+        log::debug!("Cartridge metadata:\n{}", header.to_table());
         Ok(cart)
     }
 }
@@ -0,0 +1,244 @@
+// Game Genie and GameShark cheats.
+//
+// Game Genie codes (`ABC-DEF-GHI` or `ABC-DEF`) substitute a ROM byte as it is
+// read, optionally only while the original byte matches a compare value so
+// the code only hits the intended bank. GameShark codes (`ttvvllhh`) write a
+// RAM byte once per frame; types $90-$97 only apply while that CGB WRAM bank
+// is mapped at $D000-$DFFF.
+//
+// Cheats are kept next to the ROM in `<rom>.cht`, one per line as
+// `+|- CODE [name]` where `-` marks a disabled code.
+
+use crate::{globals::*, Error};
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatCode {
+    GameGenie {
+        address: u16,
+        value: u8,
+        compare: Option<u8>,
+    },
+    GameShark {
+        // WRAM bank for $D000-$DFFF, any bank when None
+        bank: Option<u8>,
+        address: u16,
+        value: u8,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub code: String,
+    pub name: String,
+    pub enabled: bool,
+    pub kind: CheatCode,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+}
+
+fn hex_digits(code: &str) -> Option<Vec<u8>> {
+    code.chars().map(|c| c.to_digit(16).map(|d| d as u8)).collect()
+}
+
+impl Cheat {
+    pub fn with_name(mut self, name: &str) -> Cheat {
+        self.name = name.to_string();
+        self
+    }
+}
+
+impl FromStr for Cheat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s.trim().to_uppercase();
+        let invalid = || Error::Parse(format!("Invalid cheat code: {:?}", s));
+        let digits = hex_digits(&code.replace('-', "")).ok_or_else(invalid)?;
+
+        let kind = match digits.len() {
+            6 | 9 => {
+                let address = ((digits[5] as u16 ^ 0xF) << 12)
+                    | (digits[2] as u16) << 8
+                    | (digits[3] as u16) << 4
+                    | digits[4] as u16;
+                if address > ROM1_ADDRESS_END {
+                    return Err(invalid());
+                }
+                // the compare byte is stored rotated and scrambled, digit H is unused
+                let compare = match digits.len() {
+                    9 => Some((digits[6] << 4 | digits[8]).rotate_right(2) ^ 0xBA),
+                    _ => None,
+                };
+                CheatCode::GameGenie {
+                    address,
+                    value: digits[0] << 4 | digits[1],
+                    compare,
+                }
+            }
+            8 if !code.contains('-') => {
+                let byte = |i: usize| digits[i] << 4 | digits[i + 1];
+                let bank = match byte(0) {
+                    0x00 | 0x01 => None,
+                    kind @ 0x90..=0x97 => Some((kind & 0x07).max(1)),
+                    kind => return Err(Error::Parse(format!("Unsupported GameShark code type: {:02X}", kind))),
+                };
+                let address = (byte(6) as u16) << 8 | byte(4) as u16;
+                if address <= ROM1_ADDRESS_END {
+                    return Err(invalid());
+                }
+                CheatCode::GameShark {
+                    bank,
+                    address,
+                    value: byte(2),
+                }
+            }
+            _ => return Err(invalid()),
+        };
+
+        Ok(Cheat {
+            code,
+            name: String::new(),
+            enabled: true,
+            kind,
+        })
+    }
+}
+
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.enabled {
+            true => '+',
+            false => '-',
+        };
+        match self.name.is_empty() {
+            true => write!(f, "{} {}", state, self.code),
+            false => write!(f, "{} {} {}", state, self.code, self.name),
+        }
+    }
+}
+
+impl Cheats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn sidecar_path<P: AsRef<Path>>(rom_path: P) -> PathBuf {
+        let mut path = rom_path.as_ref().as_os_str().to_owned();
+        path.push(".cht");
+        PathBuf::from(path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> crate::Result<Cheats> {
+        Cheats::parse(&std::fs::read_to_string(path)?)
+    }
+
+    // Loads `<rom>.cht` if present. A file that fails to parse is an error
+    // rather than an empty list, which would be saved over it on exit.
+    pub fn load_for_rom<P: AsRef<Path>>(rom_path: P) -> crate::Result<Option<Cheats>> {
+        let path = Cheats::sidecar_path(rom_path);
+        match path.is_file() {
+            true => Cheats::load(&path).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        std::fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    pub fn parse(contents: &str) -> crate::Result<Cheats> {
+        let mut cheats = Cheats::new();
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (enabled, line) = match line.chars().next() {
+                Some('+') => (true, line[1..].trim_start()),
+                Some('-') => (false, line[1..].trim_start()),
+                _ => (true, line),
+            };
+            let (code, name) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let mut cheat = code
+                .parse::<Cheat>()
+                .map_err(|e| Error::Parse(format!("Line {}: {}", line_number + 1, e)))?
+                .with_name(name.trim());
+            cheat.enabled = enabled;
+            cheats.add(cheat);
+        }
+        Ok(cheats)
+    }
+
+    pub fn add(&mut self, cheat: Cheat) {
+        self.cheats.push(cheat);
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Cheat> {
+        (index < self.cheats.len()).then(|| self.cheats.remove(index))
+    }
+
+    // returns false if there is no cheat at index
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        match self.cheats.get_mut(index) {
+            Some(cheat) => {
+                cheat.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cheats.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cheats.len()
+    }
+
+    fn enabled(&self) -> impl Iterator<Item = &CheatCode> {
+        self.cheats.iter().filter(|cheat| cheat.enabled).map(|cheat| &cheat.kind)
+    }
+
+    // substitutes a byte read from ROM at address
+    #[inline]
+    pub fn read_rom(&self, address: u16, value: u8) -> u8 {
+        self.enabled()
+            .find_map(|kind| match *kind {
+                CheatCode::GameGenie {
+                    address: target,
+                    value: patched,
+                    compare,
+                } if target == address && compare.is_none_or(|compare| compare == value) => Some(patched),
+                _ => None,
+            })
+            .unwrap_or(value)
+    }
+
+    // GameShark writes for a frame as (bank, address, value)
+    pub fn writes(&self) -> impl Iterator<Item = (Option<u8>, u16, u8)> + '_ {
+        self.enabled().filter_map(|kind| match *kind {
+            CheatCode::GameShark { bank, address, value } => Some((bank, address, value)),
+            _ => None,
+        })
+    }
+}
+
+impl fmt::Display for Cheats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.cheats.iter().try_for_each(|cheat| writeln!(f, "{}", cheat))
+    }
+}
@@ -0,0 +1,180 @@
+// Executed-code coverage, recorded per ROM byte while the emulator runs.
+//
+// Every ROM byte fetched as an opcode is marked `EXECUTED`, the bytes that
+// follow it as `OPERAND` and any other read as `DATA`. Coverage is kept in a
+// sidecar file next to the ROM (`<rom>.cov`) and merged across runs. The file
+// stores the CRC-32 of the ROM it was recorded for so a stale sidecar left by
+// another ROM is rejected instead of merged.
+
+use crate::{globals::*, utils, Error};
+
+use std::path::{Path, PathBuf};
+
+pub const EXECUTED: u8 = 0b001;
+pub const OPERAND: u8 = 0b010;
+pub const DATA: u8 = 0b100;
+
+const MAGIC: &[u8; 8] = b"RUBCCOV2";
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    // CRC-32 of the ROM, set by `bind_rom` or when loaded
+    rom_crc: Option<u32>,
+    // flags by ROM offset, grows as banks are reached
+    flags: Vec<u8>,
+}
+
+// `bank` is the one mapped at `address`, which in either window need not be
+// bank 0 for $0000-$3FFF (MBC1 mode 1, multicarts, MMM01)
+#[inline]
+fn rom_offset(bank: usize, address: u16) -> usize {
+    bank * ROM_BANK_SIZE + (address & 0x3FFF) as usize
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn sidecar_path<P: AsRef<Path>>(rom_path: P) -> PathBuf {
+        let mut path = rom_path.as_ref().as_os_str().to_owned();
+        path.push(".cov");
+        PathBuf::from(path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> crate::Result<Coverage> {
+        let contents = std::fs::read(path.as_ref())?;
+        let (magic, rest) = contents.split_at(MAGIC.len().min(contents.len()));
+        if magic != MAGIC || rest.len() < 4 {
+            return Err(Error::Parse(format!(
+                "Not a coverage file: {}",
+                path.as_ref().display()
+            )));
+        }
+
+        let (crc, flags) = rest.split_at(4);
+        Ok(Coverage {
+            rom_crc: Some(u32::from_le_bytes([crc[0], crc[1], crc[2], crc[3]])),
+            flags: flags.to_vec(),
+        })
+    }
+
+    // ties coverage to `rom`, fails if it was recorded for a different ROM
+    pub fn bind_rom(&mut self, rom: &[u8]) -> crate::Result<()> {
+        let crc = utils::crc32(rom);
+        match self.rom_crc {
+            Some(rom_crc) if rom_crc != crc => Err(Error::Parse(format!(
+                "Coverage was recorded for a different ROM (CRC-32 {:08X}, ROM is {:08X})",
+                rom_crc, crc
+            ))),
+            _ => {
+                self.rom_crc = Some(crc);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn rom_crc(&self) -> Option<u32> {
+        self.rom_crc
+    }
+
+    // Loads `<rom>.cov` if present.
+    pub fn load_for_rom<P: AsRef<Path>>(rom_path: P) -> Option<Coverage> {
+        let path = Coverage::sidecar_path(rom_path);
+        if !path.is_file() {
+            return None;
+        }
+        Coverage::load(&path)
+            .map_err(|e| log::warn!("Unable to load coverage from {}: {}", path.display(), e))
+            .ok()
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        let crc = self
+            .rom_crc
+            .ok_or_else(|| Error::Parse("Coverage is not bound to a ROM".to_string()))?;
+        let mut contents = Vec::with_capacity(MAGIC.len() + 4 + self.flags.len());
+        contents.extend_from_slice(MAGIC);
+        contents.extend_from_slice(&crc.to_le_bytes());
+        contents.extend_from_slice(&self.flags);
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn mark(&mut self, bank: usize, address: u16, flag: u8) {
+        if address > ROM1_ADDRESS_END {
+            return;
+        }
+
+        let offset = rom_offset(bank, address);
+        if offset >= self.flags.len() {
+            self.flags.resize((offset / ROM_BANK_SIZE + 1) * ROM_BANK_SIZE, 0);
+        }
+        self.flags[offset] |= flag;
+    }
+
+    pub fn flags(&self, bank: usize, address: u16) -> u8 {
+        match address {
+            ROM_ADDRESS_START..=ROM1_ADDRESS_END => {
+                self.flags.get(rom_offset(bank, address)).copied().unwrap_or(0)
+            }
+            _ => 0,
+        }
+    }
+
+    pub fn is_executed(&self, bank: usize, address: u16) -> bool {
+        self.flags(bank, address) & EXECUTED != 0
+    }
+
+    // bank:addr of every executed opcode, in ROM order
+    pub fn executed(&self) -> impl Iterator<Item = (usize, u16)> + '_ {
+        self.flags
+            .iter()
+            .enumerate()
+            .filter(|(_, flags)| *flags & EXECUTED != 0)
+            .map(|(offset, _)| {
+                let bank = offset / ROM_BANK_SIZE;
+                let address = match bank {
+                    0 => offset as u16,
+                    _ => ROM1_ADDRESS_START + (offset % ROM_BANK_SIZE) as u16,
+                };
+                (bank, address)
+            })
+    }
+
+    // banks in which code at a $4000-$7FFF address was executed
+    pub fn executed_banks(&self, address: u16) -> impl Iterator<Item = usize> + '_ {
+        (1..self.flags.len().div_ceil(ROM_BANK_SIZE)).filter(move |bank| self.is_executed(*bank, address))
+    }
+
+    // number of ROM bytes with the flag set
+    pub fn count(&self, flag: u8) -> usize {
+        self.flags.iter().filter(|flags| *flags & flag != 0).count()
+    }
+
+    pub fn merge(&mut self, other: &Coverage) -> crate::Result<()> {
+        match (self.rom_crc, other.rom_crc) {
+            (Some(crc), Some(other)) if crc != other => {
+                return Err(Error::Parse(format!(
+                    "Coverage was recorded for a different ROM (CRC-32 {:08X}, expected {:08X})",
+                    other, crc
+                )))
+            }
+            (None, crc) => self.rom_crc = crc,
+            _ => (),
+        }
+
+        if other.flags.len() > self.flags.len() {
+            self.flags.resize(other.flags.len(), 0);
+        }
+        for (flags, other) in self.flags.iter_mut().zip(other.flags.iter()) {
+            *flags |= other;
+        }
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.flags.clear();
+    }
+}
@@ -0,0 +1,932 @@
+// Recursive-traversal disassembler.
+//
+// Code is discovered by following control flow from the entry point, the
+// interrupt vectors and any extra entry points, everything that is never
+// reached is treated as data. Writes of a constant to the MBC bank register
+// (`ld a, n` / `xor a` followed by `ld [$2000-$3FFF], a`) are tracked so jumps
+// and calls into $4000-$7FFF can be resolved to a bank where possible.
+//
+// With coverage recorded by the emulator only executed code is decoded.
+
+use crate::{coverage, coverage::Coverage, globals::*, symbols::SymbolTable};
+
+use prettytable::{format, row, Table};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+
+const R8: [&str; 8] = ["b", "c", "d", "e", "h", "l", "[hl]", "a"];
+const R16: [&str; 4] = ["bc", "de", "hl", "sp"];
+const R16_STACK: [&str; 4] = ["bc", "de", "hl", "af"];
+const CONDITIONS: [&str; 4] = ["nz", "z", "nc", "c"];
+const ALU: [(&str, &str); 8] = [
+    ("add", "a, "),
+    ("adc", "a, "),
+    ("sub", ""),
+    ("sbc", "a, "),
+    ("and", ""),
+    ("xor", ""),
+    ("or", ""),
+    ("cp", ""),
+];
+const ROTATIONS: [&str; 8] = ["rlc", "rrc", "rl", "rr", "sla", "sra", "swap", "srl"];
+const ACCUMULATOR_OPS: [&str; 8] = ["rlca", "rrca", "rla", "rra", "daa", "cpl", "scf", "ccf"];
+
+// address and name of the hardware entry points
+const VECTORS: [(u16, &str); 6] = [
+    (INTR_VBLANK, "VBlankInterrupt"),
+    (INTR_LCD_STAT, "LCDCInterrupt"),
+    (INTR_TIMER, "TimerOverflowInterrupt"),
+    (INTR_SERIAL, "SerialTransferCompleteInterrupt"),
+    (INTR_HIGH_TO_LOW, "JoypadTransitionInterrupt"),
+    (CART_HEADER_START, "Boot"),
+];
+
+// cartridge header fields, never decoded as code
+const HEADER_FIELDS: [(u16, u16, &str); 13] = [
+    (CART_NINTENDO_LOGO_START, CART_NINTENDO_LOGO_END, "Nintendo logo"),
+    (CART_TITLE_START, CART_TITLE_END, "Title"),
+    (CART_CBG_FLAG, CART_CBG_FLAG, "CGB flag"),
+    (CART_NEW_LICENSEE_CODE_START, CART_NEW_LICENSEE_CODE_END, "New licensee code"),
+    (CART_SGB_FLAG, CART_SGB_FLAG, "SGB flag"),
+    (CART_TYPE, CART_TYPE, "Cartridge type"),
+    (CART_ROM_SIZE, CART_ROM_SIZE, "ROM size"),
+    (CART_SRAM_SIZE, CART_SRAM_SIZE, "RAM size"),
+    (CART_DESTINATION_CODE, CART_DESTINATION_CODE, "Destination code"),
+    (CART_OLD_LICENSEE_CODE, CART_OLD_LICENSEE_CODE, "Old licensee code"),
+    (CART_MASK_ROM_VERSION_NUMBER, CART_MASK_ROM_VERSION_NUMBER, "Mask ROM version"),
+    (CART_HEADER_CHECKSUM, CART_HEADER_CHECKSUM, "Header checksum"),
+    (CART_GLOBAL_CHECKSUM_START, CART_GLOBAL_CHECKSUM_END, "Global checksum"),
+];
+
+// T-cycles per opcode, branches taken, $CB is the cost of the prefix itself
+#[rustfmt::skip]
+static CYCLES: [u8; 256] = [
+     4, 12,  8,  8,  4,  4,  8,  4, 20,  8,  8,  8,  4,  4,  8,  4, //  $00 - $0F
+     4, 12,  8,  8,  4,  4,  8,  4, 12,  8,  8,  8,  4,  4,  8,  4, //  $10 - $1F
+    12, 12,  8,  8,  4,  4,  8,  4, 12,  8,  8,  8,  4,  4,  8,  4, //  $20 - $2F
+    12, 12,  8,  8, 12, 12, 12,  4, 12,  8,  8,  8,  4,  4,  8,  4, //  $30 - $3F
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, //  $40 - $4F
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, //  $50 - $5F
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, //  $60 - $6F
+     8,  8,  8,  8,  8,  8,  4,  8,  4,  4,  4,  4,  4,  4,  8,  4, //  $70 - $7F
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, //  $80 - $8F
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, //  $90 - $9F
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, //  $A0 - $AF
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, //  $B0 - $BF
+    20, 12, 16, 16, 24, 16,  8, 16, 20, 16, 16,  4, 24, 24,  8, 16, //  $C0 - $CF
+    20, 12, 16,  0, 24, 16,  8, 16, 20, 16, 16,  0, 24,  0,  8, 16, //  $D0 - $DF
+    12, 12,  8,  0,  0, 16,  8, 16, 16,  4, 16,  0,  0,  0,  8, 16, //  $E0 - $EF
+    12, 12,  8,  4,  0, 16,  8, 16, 12,  8, 16,  4,  0,  0,  8, 16, //  $F0 - $FF
+];
+
+// T-cycles of a conditional branch that is not taken
+fn cycles_not_taken(opcode: u8) -> Option<u8> {
+    match opcode {
+        0x20 | 0x28 | 0x30 | 0x38 | 0xC0 | 0xC8 | 0xD0 | 0xD8 => Some(8),
+        0xC2 | 0xCA | 0xD2 | 0xDA | 0xC4 | 0xCC | 0xD4 | 0xDC => Some(12),
+        _ => None,
+    }
+}
+
+// data lines hold at most this many bytes, longer runs of one value become `ds`
+const DATA_LINE_LEN: usize = 8;
+const FILL_MIN_LEN: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "target")]
+pub enum Flow {
+    // falls through to the next instruction
+    Next,
+    Jump(u16),
+    CondJump(u16),
+    Call(u16),
+    CondCall(u16),
+    Return,
+    CondReturn,
+    // jp hl, destination unknown
+    Indirect,
+}
+
+impl Flow {
+    pub fn target(&self) -> Option<u16> {
+        match *self {
+            Flow::Jump(t) | Flow::CondJump(t) | Flow::Call(t) | Flow::CondCall(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    // can execution continue with the next instruction
+    pub fn falls_through(&self) -> bool {
+        !matches!(self, Flow::Jump(_) | Flow::Return | Flow::Indirect)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Instruction {
+    pub bank: usize,
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+    pub mnemonic: &'static str,
+    // RGBDS syntax, immediates as `$XX`/`$XXXX`
+    pub operand: String,
+    pub length: u8,
+    // T-cycles, with any branch taken
+    pub cycles: u8,
+    // T-cycles of conditional branches when not taken
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cycles_not_taken: Option<u8>,
+    pub flow: Flow,
+}
+
+impl Instruction {
+    // Decodes the instruction at the start of `bytes`, located at bank:addr.
+    // Returns `None` for illegal opcodes or when `bytes` is too short.
+    pub fn decode(bank: usize, addr: u16, bytes: &[u8]) -> Option<Instruction> {
+        let opcode = *bytes.first()?;
+        let n8 = || bytes.get(1).copied();
+        let n16 = || Some(u16::from_le_bytes([*bytes.get(1)?, *bytes.get(2)?]));
+        let e8 = || n8().map(|n| n as i8);
+        let relative = |e: i8| addr.wrapping_add(2).wrapping_add(e as u16);
+        let signed = |e: i8| match e {
+            e if e < 0 => format!("- {}", -(e as i16)),
+            e => format!("+ {}", e),
+        };
+
+        let (x, y, z) = (opcode >> 6, ((opcode >> 3) & 7) as usize, (opcode & 7) as usize);
+        let (p, q) = (y >> 1, y & 1);
+
+        use Flow::*;
+        let (mnemonic, operand, length, flow): (&str, String, u8, Flow) = match (x, z) {
+            (0, 0) => match y {
+                0 => ("nop", String::new(), 1, Next),
+                1 => ("ld", format!("[${:04X}], sp", n16()?), 3, Next),
+                2 => ("stop", String::new(), 2, Next),
+                3 => {
+                    let target = relative(e8()?);
+                    ("jr", format!("${:04X}", target), 2, Jump(target))
+                }
+                _ => {
+                    let target = relative(e8()?);
+                    let operand = format!("{}, ${:04X}", CONDITIONS[y - 4], target);
+                    ("jr", operand, 2, CondJump(target))
+                }
+            },
+            (0, 1) if q == 0 => ("ld", format!("{}, ${:04X}", R16[p], n16()?), 3, Next),
+            (0, 1) => ("add", format!("hl, {}", R16[p]), 1, Next),
+            (0, 2) => {
+                let indirect = ["[bc]", "[de]", "[hli]", "[hld]"][p];
+                match q {
+                    0 => ("ld", format!("{}, a", indirect), 1, Next),
+                    _ => ("ld", format!("a, {}", indirect), 1, Next),
+                }
+            }
+            (0, 3) => (["inc", "dec"][q], R16[p].to_string(), 1, Next),
+            (0, 4) => ("inc", R8[y].to_string(), 1, Next),
+            (0, 5) => ("dec", R8[y].to_string(), 1, Next),
+            (0, 6) => ("ld", format!("{}, ${:02X}", R8[y], n8()?), 2, Next),
+            (0, _) => (ACCUMULATOR_OPS[y], String::new(), 1, Next),
+            (1, _) if y == 6 && z == 6 => ("halt", String::new(), 1, Next),
+            (1, _) => ("ld", format!("{}, {}", R8[y], R8[z]), 1, Next),
+            (2, _) => (ALU[y].0, format!("{}{}", ALU[y].1, R8[z]), 1, Next),
+            (_, 0) => match y {
+                0..=3 => ("ret", CONDITIONS[y].to_string(), 1, CondReturn),
+                4 => ("ldh", format!("[${:04X}], a", 0xFF00 | n8()? as u16), 2, Next),
+                5 => ("add", format!("sp, {}", e8()?), 2, Next),
+                6 => ("ldh", format!("a, [${:04X}]", 0xFF00 | n8()? as u16), 2, Next),
+                _ => ("ld", format!("hl, sp {}", signed(e8()?)), 2, Next),
+            },
+            (_, 1) if q == 0 => ("pop", R16_STACK[p].to_string(), 1, Next),
+            (_, 1) => match p {
+                0 => ("ret", String::new(), 1, Return),
+                1 => ("reti", String::new(), 1, Return),
+                2 => ("jp", "hl".to_string(), 1, Indirect),
+                _ => ("ld", "sp, hl".to_string(), 1, Next),
+            },
+            (_, 2) => match y {
+                0..=3 => {
+                    let target = n16()?;
+                    let operand = format!("{}, ${:04X}", CONDITIONS[y], target);
+                    ("jp", operand, 3, CondJump(target))
+                }
+                4 => ("ldh", "[c], a".to_string(), 1, Next),
+                5 => ("ld", format!("[${:04X}], a", n16()?), 3, Next),
+                6 => ("ldh", "a, [c]".to_string(), 1, Next),
+                _ => ("ld", format!("a, [${:04X}]", n16()?), 3, Next),
+            },
+            (_, 3) => match y {
+                0 => {
+                    let target = n16()?;
+                    ("jp", format!("${:04X}", target), 3, Jump(target))
+                }
+                1 => {
+                    let cb = n8()? as usize;
+                    let (op, bit, reg) = (cb >> 6, (cb >> 3) & 7, R8[cb & 7]);
+                    match op {
+                        0 => (ROTATIONS[bit], reg.to_string(), 2, Next),
+                        _ => (["", "bit", "res", "set"][op], format!("{}, {}", bit, reg), 2, Next),
+                    }
+                }
+                6 => ("di", String::new(), 1, Next),
+                7 => ("ei", String::new(), 1, Next),
+                _ => return None,
+            },
+            (_, 4) if y < 4 => {
+                let target = n16()?;
+                let operand = format!("{}, ${:04X}", CONDITIONS[y], target);
+                ("call", operand, 3, CondCall(target))
+            }
+            (_, 5) if q == 0 => ("push", R16_STACK[p].to_string(), 1, Next),
+            (_, 5) if p == 0 => {
+                let target = n16()?;
+                ("call", format!("${:04X}", target), 3, Call(target))
+            }
+            (_, 6) => (ALU[y].0, format!("{}${:02X}", ALU[y].1, n8()?), 2, Next),
+            (_, 7) => {
+                let target = (y * 8) as u16;
+                ("rst", format!("${:02X}", target), 1, Call(target))
+            }
+            _ => return None,
+        };
+
+        Some(Instruction {
+            bank,
+            addr,
+            bytes: bytes.get(..length as usize)?.to_vec(),
+            mnemonic,
+            operand,
+            length,
+            cycles: match opcode {
+                0xCB => match bytes[1] {
+                    cb if cb & 7 != 6 => 8,
+                    0x40..=0x7F => 12,
+                    _ => 16,
+                },
+                _ => CYCLES[opcode as usize],
+            },
+            cycles_not_taken: cycles_not_taken(opcode),
+            flow,
+        })
+    }
+
+    // RGBDS assemblers may pick a different encoding for some instructions,
+    // those are emitted as `db` to keep the output byte exact
+    fn is_reassemblable(&self) -> bool {
+        match self.bytes[0] {
+            // `stop` always assembles to $10 $00
+            0x10 => self.bytes[1] == 0x00,
+            // `ld [$FFxx], a` may be optimized to `ldh`
+            0xEA | 0xFA => self.bytes[2] != 0xFF,
+            _ => true,
+        }
+    }
+}
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.operand.is_empty() {
+            true => write!(f, "{}", self.mnemonic),
+            false => write!(f, "{} {}", self.mnemonic, self.operand),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Byte {
+    Data,
+    Start,
+    Continuation,
+}
+
+pub struct Disassembler<'a> {
+    rom: &'a [u8],
+    symbols: Option<&'a SymbolTable>,
+    coverage: Option<&'a Coverage>,
+    entry_points: Vec<(usize, u16)>,
+}
+
+impl<'a> Disassembler<'a> {
+    pub fn new(rom: &'a [u8]) -> Self {
+        Self {
+            rom,
+            symbols: None,
+            coverage: None,
+            entry_points: Vec::new(),
+        }
+    }
+
+    pub fn with_symbols(mut self, symbols: Option<&'a SymbolTable>) -> Self {
+        self.symbols = symbols;
+        self
+    }
+
+    // Decode only code proven by execution, everything else is shown as data.
+    // Entry points are ignored in this mode.
+    pub fn with_coverage(mut self, coverage: Option<&'a Coverage>) -> Self {
+        self.coverage = coverage;
+        self
+    }
+
+    // additional code known to be reachable, i.e. the targets of jump tables
+    pub fn with_entry_point(mut self, bank: usize, addr: u16) -> Self {
+        self.entry_points.push((bank, addr));
+        self
+    }
+
+    fn banks(&self) -> usize {
+        self.rom.len().div_ceil(ROM_BANK_SIZE).max(1)
+    }
+
+    // ROM offset of addr, given the bank currently mapped at $4000
+    fn offset(&self, addr: u16, mapped: Option<usize>) -> Option<usize> {
+        let offset = match addr {
+            ROM_ADDRESS_START..=ROM_ADDRESS_END => addr as usize,
+            ROM1_ADDRESS_START..=ROM1_ADDRESS_END => {
+                mapped? * ROM_BANK_SIZE + (addr - ROM1_ADDRESS_START) as usize
+            }
+            _ => return None,
+        };
+        (offset < self.rom.len()).then_some(offset)
+    }
+
+    pub fn disassemble(self) -> Disassembly<'a> {
+        let mut listing = Listing {
+            bytes: vec![Byte::Data; self.rom.len()],
+            ..Default::default()
+        };
+
+        match self.coverage {
+            Some(coverage) => self.decode_coverage(coverage, &mut listing),
+            None => self.traverse(&mut listing),
+        }
+
+        let mut labels = BTreeMap::new();
+        for (offset, kind) in listing.refs.iter() {
+            let bank = offset / ROM_BANK_SIZE;
+            let prefix = match kind {
+                Ref::Call => "Call",
+                Ref::Jump => "Jump",
+                Ref::Relative => "jr",
+            };
+            labels.insert(*offset, format!("{}_{:03X}_{:04X}", prefix, bank, bank_address(*offset)));
+        }
+        for (addr, name) in VECTORS.iter() {
+            if (*addr as usize) < self.rom.len() && listing.bytes[*addr as usize] == Byte::Start {
+                labels.insert(*addr as usize, name.to_string());
+            }
+        }
+        if let Some(symbols) = self.symbols {
+            for (bank, addr, name) in symbols.iter() {
+                let mapped = if addr >= ROM1_ADDRESS_START { Some(bank) } else { Some(1) };
+                if let Some(offset) = self.offset(addr, mapped) {
+                    labels.insert(offset, name.to_string());
+                }
+            }
+        }
+
+        Disassembly {
+            rom: self.rom,
+            bytes: listing.bytes,
+            instructions: listing.instructions,
+            labels,
+            targets: listing.targets,
+            unresolved: listing.unresolved,
+            coverage: self.coverage,
+        }
+    }
+
+    fn decode_at(&self, offset: usize) -> Option<Instruction> {
+        let bank = offset / ROM_BANK_SIZE;
+        let end = ((bank + 1) * ROM_BANK_SIZE).min(self.rom.len());
+        Instruction::decode(bank, bank_address(offset), &self.rom[offset..end])
+    }
+
+    // follows control flow from the entry points
+    fn traverse(&self, listing: &mut Listing) {
+        let banks = self.banks();
+        // with a single switchable bank the mapping is always known
+        let default_bank = (banks <= 2).then_some(1);
+        let mut visited = HashSet::new();
+
+        let mut queue: Vec<(usize, Option<usize>)> = Vec::new();
+        for (addr, _) in VECTORS.iter() {
+            let mapped = match *addr {
+                CART_HEADER_START => Some(1),
+                _ => default_bank,
+            };
+            match self.offset(*addr, mapped) {
+                // unused interrupt vectors are usually left as $00/$FF padding
+                Some(offset) if *addr != CART_HEADER_START && matches!(self.rom[offset], 0x00 | 0xFF) => {}
+                Some(offset) => queue.push((offset, mapped)),
+                None => {}
+            }
+        }
+        for (bank, addr) in self.entry_points.iter() {
+            let mapped = if *addr >= ROM1_ADDRESS_START { Some(*bank) } else { default_bank };
+            if let Some(offset) = self.offset(*addr, mapped) {
+                listing.refs.entry(offset).or_insert(Ref::Jump);
+                queue.push((offset, mapped));
+            }
+        }
+
+        while let Some((start, mapped)) = queue.pop() {
+            let mut offset = start;
+            let mut mapped = mapped;
+            let mut accumulator: Option<u8> = None;
+
+            loop {
+                let bank = offset / ROM_BANK_SIZE;
+                // banked code always runs with its own bank mapped
+                if bank > 0 {
+                    mapped = Some(bank);
+                }
+                // code in bank 0 may be reached with different banks mapped
+                if !visited.insert((offset, if bank == 0 { mapped } else { None })) {
+                    break;
+                }
+
+                let instruction = match self.decode_at(offset) {
+                    Some(instruction) => instruction,
+                    None => break,
+                };
+                // overlaps the header or another instruction, leave the earlier decoding alone
+                if !listing.claim(offset, instruction.length as usize) {
+                    break;
+                }
+
+                // track constant writes to the ROM bank register
+                match instruction.bytes[..] {
+                    [0x3E, n] => accumulator = Some(n),
+                    [0xAF] => accumulator = Some(0),
+                    [0xEA, lo, hi] => {
+                        if let (0x2000..=0x3FFF, Some(a)) = (u16::from_le_bytes([lo, hi]), accumulator) {
+                            mapped = Some(((a as usize) % banks).max(1));
+                        }
+                    }
+                    _ if writes_accumulator(&instruction.bytes) => accumulator = None,
+                    _ => {}
+                }
+
+                if let Some(target) = instruction.flow.target() {
+                    let target_mapped = if bank > 0 { Some(bank) } else { mapped };
+                    let target_offset = self.offset(target, target_mapped);
+                    listing.reference(offset, &instruction, target_offset);
+                    if let Some(target_offset) = target_offset {
+                        queue.push((target_offset, target_mapped));
+                    }
+                }
+
+                let falls_through = instruction.flow.falls_through();
+                if let Flow::Call(_) | Flow::CondCall(_) = instruction.flow {
+                    // the callee may clobber A
+                    accumulator = None;
+                }
+
+                let length = instruction.length as usize;
+                listing.instructions.insert(offset, instruction);
+                offset += length;
+                if !falls_through || offset % ROM_BANK_SIZE == 0 || offset >= self.rom.len() {
+                    break;
+                }
+            }
+        }
+    }
+
+    // only decodes instructions that were actually executed
+    fn decode_coverage(&self, coverage: &Coverage, listing: &mut Listing) {
+        for (bank, addr) in coverage.executed() {
+            let offset = rom_offset(bank, addr);
+            if offset >= self.rom.len() {
+                continue;
+            }
+
+            let instruction = match self.decode_at(offset) {
+                Some(instruction) => instruction,
+                None => continue,
+            };
+            if !listing.claim(offset, instruction.length as usize) {
+                continue;
+            }
+
+            if let Some(target) = instruction.flow.target() {
+                // jumps from bank 0 resolve when the target only ever ran in one bank
+                let mapped = match bank {
+                    0 => {
+                        let mut banks = coverage.executed_banks(target);
+                        match (banks.next(), banks.next()) {
+                            (Some(bank), None) => Some(bank),
+                            _ => None,
+                        }
+                    }
+                    bank => Some(bank),
+                };
+                listing.reference(offset, &instruction, self.offset(target, mapped));
+            }
+
+            listing.instructions.insert(offset, instruction);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Listing {
+    bytes: Vec<Byte>,
+    instructions: BTreeMap<usize, Instruction>,
+    refs: BTreeMap<usize, Ref>,
+    targets: BTreeMap<usize, usize>,
+    unresolved: BTreeMap<usize, u16>,
+}
+
+impl Listing {
+    // marks the bytes of an instruction as code, false if they overlap the
+    // header or a different instruction
+    fn claim(&mut self, offset: usize, length: usize) -> bool {
+        let span = offset..(offset + length).min(self.bytes.len());
+        let header = CART_NINTENDO_LOGO_START as usize..=CART_GLOBAL_CHECKSUM_END as usize;
+        if span.clone().any(|i| header.contains(&i)) {
+            return false;
+        }
+
+        match self.bytes[offset] {
+            Byte::Start => true,
+            Byte::Data if span.clone().skip(1).all(|i| self.bytes[i] == Byte::Data) => {
+                self.bytes[offset] = Byte::Start;
+                for i in span.skip(1) {
+                    self.bytes[i] = Byte::Continuation;
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn reference(&mut self, offset: usize, instruction: &Instruction, target_offset: Option<usize>) {
+        match (target_offset, instruction.flow.target()) {
+            (Some(target_offset), _) => {
+                self.targets.insert(offset, target_offset);
+                let kind = match instruction.flow {
+                    Flow::Call(_) | Flow::CondCall(_) => Ref::Call,
+                    _ if instruction.mnemonic == "jr" => Ref::Relative,
+                    _ => Ref::Jump,
+                };
+                let entry = self.refs.entry(target_offset).or_insert(kind);
+                *entry = (*entry).min(kind);
+            }
+            (None, Some(target)) if (ROM1_ADDRESS_START..=ROM1_ADDRESS_END).contains(&target) => {
+                self.unresolved.insert(offset, target);
+            }
+            _ => {}
+        }
+    }
+}
+
+// how a code address is referenced, the strongest reference names the label
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Ref {
+    Call,
+    Jump,
+    Relative,
+}
+
+// does the instruction overwrite A
+fn writes_accumulator(bytes: &[u8]) -> bool {
+    match bytes[..] {
+        [0xCB, cb] => cb & 7 == 7 && !(0x40..=0x7F).contains(&cb),
+        [op, ..] => matches!(
+            op,
+            0x07 | 0x0A | 0x0F | 0x17 | 0x1A | 0x1F | 0x27 | 0x2A | 0x2F | 0x3A | 0x3C | 0x3D
+                | 0x78..=0x7F
+                | 0x80..=0xB7
+                | 0xC6 | 0xCE | 0xD6 | 0xDE | 0xE6 | 0xEE | 0xF6
+                | 0xF0 | 0xF1 | 0xF2 | 0xFA
+        ),
+        [] => false,
+    }
+}
+
+// CPU address a ROM offset is visible at
+#[inline]
+fn bank_address(offset: usize) -> u16 {
+    match offset / ROM_BANK_SIZE {
+        0 => offset as u16,
+        _ => ROM1_ADDRESS_START + (offset % ROM_BANK_SIZE) as u16,
+    }
+}
+
+fn header_field(offset: usize) -> Option<(u16, u16, &'static str)> {
+    HEADER_FIELDS
+        .iter()
+        .find(|(start, end, _)| (*start as usize..=*end as usize).contains(&offset))
+        .copied()
+}
+
+pub enum Line<'a> {
+    Section(usize),
+    Label(&'a str),
+    Code(&'a Instruction),
+    // offset, bytes and an optional note
+    Data(usize, &'a [u8], Option<&'static str>),
+    // offset, length, value
+    Fill(usize, usize, u8),
+}
+
+pub struct Disassembly<'a> {
+    rom: &'a [u8],
+    bytes: Vec<Byte>,
+    instructions: BTreeMap<usize, Instruction>,
+    labels: BTreeMap<usize, String>,
+    // ROM offset of resolved jump/call targets, by instruction offset
+    targets: BTreeMap<usize, usize>,
+    // jumps into $4000-$7FFF from bank 0 whose bank could not be determined
+    unresolved: BTreeMap<usize, u16>,
+    coverage: Option<&'a Coverage>,
+}
+
+impl<'a> Disassembly<'a> {
+    pub fn instructions(&self) -> impl Iterator<Item = &Instruction> {
+        self.instructions.values()
+    }
+
+    pub fn instruction_at(&self, bank: usize, addr: u16) -> Option<&Instruction> {
+        self.instructions.get(&rom_offset(bank, addr))
+    }
+
+    pub fn label_at(&self, bank: usize, addr: u16) -> Option<&str> {
+        self.labels.get(&rom_offset(bank, addr)).map(|l| l.as_str())
+    }
+
+    pub fn is_code(&self, bank: usize, addr: u16) -> bool {
+        let offset = rom_offset(bank, addr);
+        offset < self.bytes.len() && self.bytes[offset] != Byte::Data
+    }
+
+    // number of bytes decoded as code
+    pub fn code_size(&self) -> usize {
+        self.bytes.iter().filter(|b| **b != Byte::Data).count()
+    }
+
+    // operand with the jump/call target replaced by its label
+    fn operand(&self, instruction: &Instruction) -> String {
+        let label = match instruction.mnemonic {
+            "rst" => None,
+            _ => self
+                .targets
+                .get(&rom_offset(instruction.bank, instruction.addr))
+                .and_then(|target| self.labels.get(target)),
+        };
+
+        match (label, instruction.operand.split_once(", ")) {
+            (Some(label), Some((condition, _))) => format!("{}, {}", condition, label),
+            (Some(label), None) => label.to_string(),
+            (None, _) => instruction.operand.clone(),
+        }
+    }
+
+    // whether data bytes were seen being read when coverage is available
+    fn data_note(&self, offset: usize, len: usize) -> &'static str {
+        let coverage = match self.coverage {
+            Some(coverage) => coverage,
+            None => return "data",
+        };
+
+        let read = (offset..offset + len).any(|offset| {
+            let bank = offset / ROM_BANK_SIZE;
+            coverage.flags(bank, bank_address(offset)) & coverage::DATA != 0
+        });
+        match read {
+            true => "data (read)",
+            false => "data (unreached)",
+        }
+    }
+
+    fn unresolved_note(&self, offset: usize) -> Option<String> {
+        self.unresolved
+            .get(&offset)
+            .map(|target| format!("bank unknown for ${:04X}", target))
+    }
+
+    pub fn lines(&self) -> Vec<Line<'_>> {
+        let mut lines = Vec::new();
+        let mut offset = 0;
+        while offset < self.rom.len() {
+            if offset % ROM_BANK_SIZE == 0 {
+                lines.push(Line::Section(offset / ROM_BANK_SIZE));
+            }
+            if let Some(label) = self.labels.get(&offset) {
+                lines.push(Line::Label(label));
+            }
+
+            if let Some(instruction) = self.instructions.get(&offset) {
+                let end = offset + instruction.length as usize;
+                // a label inside the instruction forces it out as data
+                if self.labels.range(offset + 1..end).next().is_none() && instruction.is_reassemblable() {
+                    lines.push(Line::Code(instruction));
+                    offset = end;
+                    continue;
+                }
+            }
+
+            // data up to the next label, instruction, header field or bank boundary
+            let field = header_field(offset);
+            let mut end = offset + 1;
+            let limit = match field {
+                Some((_, field_end, _)) => field_end as usize + 1,
+                None => ((offset / ROM_BANK_SIZE + 1) * ROM_BANK_SIZE).min(self.rom.len()),
+            };
+            while end < limit
+                && self.bytes[end] != Byte::Start
+                && !self.labels.contains_key(&end)
+                && (field.is_some() || header_field(end).is_none())
+            {
+                end += 1;
+            }
+
+            let run = self.rom[offset..end]
+                .iter()
+                .take_while(|b| **b == self.rom[offset])
+                .count();
+            if field.is_none() && run >= FILL_MIN_LEN {
+                lines.push(Line::Fill(offset, run, self.rom[offset]));
+                offset += run;
+                continue;
+            }
+
+            let end = match field {
+                Some(_) => end,
+                None => end.min(offset + DATA_LINE_LEN),
+            };
+            lines.push(Line::Data(offset, &self.rom[offset..end], field.map(|f| f.2)));
+            offset = end;
+        }
+        lines
+    }
+
+    // Re-assemblable RGBDS source, `rgbasm` + `rgblink` reproduce the ROM byte for byte.
+    pub fn to_rgbds(&self) -> String {
+        let mut out = String::from("; Disassembled by rubc\n");
+        let hex = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|b| format!("${:02X}", b))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        for line in self.lines() {
+            match line {
+                Line::Section(0) => {
+                    let _ = write!(out, "\nSECTION \"ROM Bank $000\", ROM0[$0000]\n\n");
+                }
+                Line::Section(bank) => {
+                    let _ = write!(
+                        out,
+                        "\nSECTION \"ROM Bank ${:03X}\", ROMX[$4000], BANK[${:X}]\n\n",
+                        bank, bank
+                    );
+                }
+                Line::Label(label) => {
+                    let _ = writeln!(out, "{}:", label);
+                }
+                Line::Code(instruction) => {
+                    let offset = rom_offset(instruction.bank, instruction.addr);
+                    let operand = self.operand(instruction);
+                    let text = match operand.is_empty() {
+                        true => instruction.mnemonic.to_string(),
+                        false => format!("{} {}", instruction.mnemonic, operand),
+                    };
+                    let _ = match self.unresolved_note(offset) {
+                        Some(note) => writeln!(out, "    {:<31} ; ${:04X} {}", text, instruction.addr, note),
+                        None => writeln!(out, "    {:<31} ; ${:04X}", text, instruction.addr),
+                    };
+                }
+                Line::Data(offset, bytes, note) => {
+                    let text = format!("db {}", hex(bytes));
+                    let comment = match (note, self.instructions.get(&offset)) {
+                        (Some(note), _) => note.to_string(),
+                        (None, Some(instruction)) => instruction.to_string(),
+                        (None, None) => String::new(),
+                    };
+                    let _ = match comment.is_empty() {
+                        true => writeln!(out, "    {}", text),
+                        false => writeln!(out, "    {:<31} ; {}", text, comment),
+                    };
+                }
+                Line::Fill(_, len, value) => {
+                    let _ = writeln!(out, "    ds {}, ${:02X}", len, value);
+                }
+            }
+        }
+        out
+    }
+
+    // every decoded instruction as a JSON array
+    pub fn to_json(&self) -> crate::Result<String> {
+        #[derive(Serialize)]
+        struct Entry<'a> {
+            #[serde(flatten)]
+            instruction: &'a Instruction,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            label: Option<&'a str>,
+        }
+
+        let entries: Vec<Entry> = self
+            .instructions()
+            .map(|instruction| Entry {
+                instruction,
+                label: self.label_at(instruction.bank, instruction.addr),
+            })
+            .collect();
+        Ok(serde_json::to_string_pretty(&entries)?)
+    }
+
+    // every decoded instruction as CSV with a header row
+    pub fn to_csv(&self) -> String {
+        let quote = |field: &str| match field.contains(',') || field.contains('"') {
+            true => format!("\"{}\"", field.replace('"', "\"\"")),
+            false => field.to_string(),
+        };
+
+        let mut out = String::from("bank,addr,bytes,mnemonic,operand,length,cycles,label\n");
+        for instruction in self.instructions() {
+            let bytes: Vec<String> = instruction.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+            let label = self.label_at(instruction.bank, instruction.addr).unwrap_or_default();
+            let cycles = match instruction.cycles_not_taken {
+                Some(not_taken) => format!("{}/{}", instruction.cycles, not_taken),
+                None => instruction.cycles.to_string(),
+            };
+            let _ = writeln!(
+                out,
+                "{},{:04X},{},{},{},{},{},{}",
+                instruction.bank,
+                instruction.addr,
+                bytes.join(" "),
+                instruction.mnemonic,
+                quote(&instruction.operand),
+                instruction.length,
+                cycles,
+                quote(label)
+            );
+        }
+        out
+    }
+
+    pub fn to_table(&self) -> String {
+        let mut table = Table::new();
+        table.set_titles(row!["ADDRESS", "BYTES", "INSTRUCTION", "NOTES"]);
+
+        let location = |offset: usize| format!("{:02X}:{:04X}", offset / ROM_BANK_SIZE, bank_address(offset));
+        let hex = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        for line in self.lines() {
+            match line {
+                Line::Section(_) => {}
+                Line::Label(label) => {
+                    table.add_row(row![format!("{}:", label), "", "", ""]);
+                }
+                Line::Code(instruction) => {
+                    let offset = rom_offset(instruction.bank, instruction.addr);
+                    let operand = self.operand(instruction);
+                    let text = match operand.is_empty() {
+                        true => instruction.mnemonic.to_string(),
+                        false => format!("{} {}", instruction.mnemonic, operand),
+                    };
+                    table.add_row(row![
+                        location(offset),
+                        hex(&instruction.bytes),
+                        text,
+                        self.unresolved_note(offset).unwrap_or_default()
+                    ]);
+                }
+                Line::Data(offset, bytes, note) => {
+                    let note = note.unwrap_or_else(|| self.data_note(offset, bytes.len()));
+                    table.add_row(row![location(offset), hex(bytes), "db", note]);
+                }
+                Line::Fill(offset, len, value) => {
+                    table.add_row(row![
+                        location(offset),
+                        format!("{:02X} x {}", value, len),
+                        "ds",
+                        self.data_note(offset, len)
+                    ]);
+                }
+            }
+        }
+
+        table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
+        table.to_string()
+    }
+}
+
+#[inline]
+fn rom_offset(bank: usize, addr: u16) -> usize {
+    match addr {
+        ROM_ADDRESS_START..=ROM_ADDRESS_END => addr as usize,
+        _ => bank * ROM_BANK_SIZE + (addr as usize).saturating_sub(ROM1_ADDRESS_START as usize),
+    }
+}
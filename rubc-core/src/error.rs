@@ -0,0 +1,107 @@
+// Errors returned by the core.
+//
+// Anything derived from ROM data (headers, sizes, opcodes) is reported through
+// `RubcError` rather than a panic, so embedding applications can reject a bad
+// ROM and keep running.
+
+use std::fmt;
+
+#[derive(Debug)]
+pub enum RubcError {
+    Io(std::io::Error),
+    // header byte with a value the emulator does not understand
+    InvalidHeader { field: &'static str, value: u8 },
+    // cartridge type byte of an unsupported memory bank controller
+    UnsupportedMapper(u8),
+    // ROM size does not match the size declared in the header
+    SizeMismatch { expected: usize, actual: usize },
+    ChecksumMismatch { expected: u8, actual: u8 },
+    GlobalChecksumMismatch { expected: u16, actual: u16 },
+    // Nintendo logo does not match, though the CGB only checks the top half
+    InvalidLogo { top_half_valid: bool },
+    IllegalOpcode { pc: u16, op: u8 },
+    // the CPU stopped making progress at pc
+    Stuck { pc: u16 },
+    // a CPU error with the state, backtrace and history at the time
+    Crash { error: Box<RubcError>, report: String },
+    // malformed user input: breakpoints, expressions, symbol files, ...
+    Parse(String),
+    // malformed GDB remote protocol packet
+    Protocol(String),
+    // corrupt or unsupported .zip/.gz file
+    Archive(String),
+    // malformed IPS/UPS/BPS patch, or one made for a different ROM
+    Patch(String),
+    Json(serde_json::Error),
+}
+
+impl RubcError {
+    // the underlying error of a `Crash`
+    pub fn cause(&self) -> &RubcError {
+        match self {
+            RubcError::Crash { error, .. } => error.cause(),
+            error => error,
+        }
+    }
+}
+
+impl fmt::Display for RubcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RubcError::Io(e) => write!(f, "{}", e),
+            RubcError::InvalidHeader { field, value } => {
+                write!(f, "Invalid cartridge header {}: {:#04x}", field, value)
+            }
+            RubcError::UnsupportedMapper(id) => write!(f, "Unsupported cartridge type: {:#04x}", id),
+            RubcError::SizeMismatch { expected, actual } => write!(
+                f,
+                "ROM size mismatch: header declares {} bytes, found {}",
+                expected, actual
+            ),
+            RubcError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "Header checksum mismatch: expected {:#04x}, found {:#04x}",
+                expected, actual
+            ),
+            RubcError::GlobalChecksumMismatch { expected, actual } => write!(
+                f,
+                "Global checksum mismatch: expected {:#06x}, found {:#06x}",
+                expected, actual
+            ),
+            RubcError::InvalidLogo { top_half_valid: true } => {
+                write!(f, "Nintendo logo mismatch: only the top half matches")
+            }
+            RubcError::InvalidLogo { top_half_valid: false } => write!(f, "Nintendo logo mismatch"),
+            RubcError::IllegalOpcode { pc, op } => write!(f, "Illegal opcode {:#04x} at {:#06x}", op, pc),
+            RubcError::Stuck { pc } => write!(f, "Stuck CPU at {:#06x}", pc),
+            RubcError::Crash { error, report } => write!(f, "{}\n{}", error, report),
+            RubcError::Parse(message) | RubcError::Protocol(message) => write!(f, "{}", message),
+            RubcError::Archive(message) => write!(f, "Invalid archive: {}", message),
+            RubcError::Patch(message) => write!(f, "Invalid patch: {}", message),
+            RubcError::Json(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for RubcError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RubcError::Io(e) => Some(e),
+            RubcError::Json(e) => Some(e),
+            RubcError::Crash { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for RubcError {
+    fn from(e: std::io::Error) -> Self {
+        RubcError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for RubcError {
+    fn from(e: serde_json::Error) -> Self {
+        RubcError::Json(e)
+    }
+}
@@ -0,0 +1,445 @@
+// Small expression language used by conditional breakpoints.
+//
+//   PC==$0150 && A>=$10 && [$C000]==$FF && bank==3
+//
+// Numbers are decimal unless prefixed with `$`/`0x` (hex) or `%` (binary).
+// Identifiers are the CPU registers (A, F, B, C, D, E, H, L, AF, BC, DE, HL,
+// SP, PC), the flags (ZF, NF, HF, CF) and `bank`, the ROM bank mapped at PC.
+// `[expr]` reads a byte from memory. Operators, loosest binding first:
+//
+//   ||   &&   == != < <= > >=   | ^ &   + -   unary ! - ~
+
+use crate::{bits, gameboy::Gameboy, globals::*, Error};
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    A,
+    F,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    AF,
+    BC,
+    DE,
+    HL,
+    SP,
+    PC,
+    FlagZ,
+    FlagN,
+    FlagH,
+    FlagC,
+    Bank,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Add,
+    Sub,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Not,
+    Neg,
+    BitNot,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Number(i64),
+    Register(Register),
+    Memory(Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+impl Register {
+    fn from_name(name: &str) -> Option<Register> {
+        let reg = match name.to_uppercase().as_str() {
+            "A" => Register::A,
+            "F" => Register::F,
+            "B" => Register::B,
+            "C" => Register::C,
+            "D" => Register::D,
+            "E" => Register::E,
+            "H" => Register::H,
+            "L" => Register::L,
+            "AF" => Register::AF,
+            "BC" => Register::BC,
+            "DE" => Register::DE,
+            "HL" => Register::HL,
+            "SP" => Register::SP,
+            "PC" => Register::PC,
+            "ZF" => Register::FlagZ,
+            "NF" => Register::FlagN,
+            "HF" => Register::FlagH,
+            "CF" => Register::FlagC,
+            "BANK" => Register::Bank,
+            _ => return None,
+        };
+        Some(reg)
+    }
+
+    fn value(&self, gb: &Gameboy) -> i64 {
+        let pair = |hi: u8, lo: u8| ((hi as u16) << 8 | lo as u16) as i64;
+        let flag = |bit: u8| bits::is_bit_set(gb.cpu.f, bit) as i64;
+        match self {
+            Register::A => gb.cpu.a as i64,
+            Register::F => gb.cpu.f as i64,
+            Register::B => gb.cpu.b as i64,
+            Register::C => gb.cpu.c as i64,
+            Register::D => gb.cpu.d as i64,
+            Register::E => gb.cpu.e as i64,
+            Register::H => gb.cpu.h as i64,
+            Register::L => gb.cpu.l as i64,
+            Register::AF => pair(gb.cpu.a, gb.cpu.f),
+            Register::BC => pair(gb.cpu.b, gb.cpu.c),
+            Register::DE => pair(gb.cpu.d, gb.cpu.e),
+            Register::HL => pair(gb.cpu.h, gb.cpu.l),
+            Register::SP => gb.cpu.sp as i64,
+            Register::PC => gb.cpu.pc as i64,
+            Register::FlagZ => flag(BIT_FLAGZ),
+            Register::FlagN => flag(BIT_FLAGN),
+            Register::FlagH => flag(BIT_FLAGH),
+            Register::FlagC => flag(BIT_FLAGC),
+            Register::Bank => gb.cart.bank(gb.cpu.pc) as i64,
+        }
+    }
+}
+
+impl Expr {
+    pub fn eval(&self, gb: &Gameboy) -> i64 {
+        match self {
+            Expr::Number(n) => *n,
+            Expr::Register(reg) => reg.value(gb),
+            Expr::Memory(addr) => gb.debug_read(addr.eval(gb) as u16) as i64,
+            Expr::Unary(op, e) => {
+                let v = e.eval(gb);
+                match op {
+                    UnaryOp::Not => (v == 0) as i64,
+                    UnaryOp::Neg => v.wrapping_neg(),
+                    UnaryOp::BitNot => !v,
+                }
+            }
+            Expr::Binary(BinaryOp::And, l, r) => (l.eval(gb) != 0 && r.eval(gb) != 0) as i64,
+            Expr::Binary(BinaryOp::Or, l, r) => (l.eval(gb) != 0 || r.eval(gb) != 0) as i64,
+            Expr::Binary(op, l, r) => {
+                let (l, r) = (l.eval(gb), r.eval(gb));
+                match op {
+                    BinaryOp::Eq => (l == r) as i64,
+                    BinaryOp::Ne => (l != r) as i64,
+                    BinaryOp::Lt => (l < r) as i64,
+                    BinaryOp::Le => (l <= r) as i64,
+                    BinaryOp::Gt => (l > r) as i64,
+                    BinaryOp::Ge => (l >= r) as i64,
+                    BinaryOp::BitOr => l | r,
+                    BinaryOp::BitXor => l ^ r,
+                    BinaryOp::BitAnd => l & r,
+                    BinaryOp::Add => l.wrapping_add(r),
+                    BinaryOp::Sub => l.wrapping_sub(r),
+                    BinaryOp::And | BinaryOp::Or => unreachable!(),
+                }
+            }
+        }
+    }
+
+    #[inline]
+    pub fn is_true(&self, gb: &Gameboy) -> bool {
+        self.eval(gb) != 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Ident(String),
+    Op(&'static str),
+    LBracket,
+    RBracket,
+    LParen,
+    RParen,
+}
+
+// longest operators first so `<=` is not lexed as `<` `=`
+const OPERATORS: &[&str] = &[
+    "||", "&&", "==", "!=", "<=", ">=", "<", ">", "|", "^", "&", "+", "-", "!", "~",
+];
+
+fn tokenize(s: &str) -> crate::Result<Vec<Token>> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        match c {
+            '[' => tokens.push(Token::LBracket),
+            ']' => tokens.push(Token::RBracket),
+            '(' => tokens.push(Token::LParen),
+            ')' => tokens.push(Token::RParen),
+            '$' | '%' | '0'..='9' => {
+                let start = i;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().filter(|c| **c != '_').collect();
+                tokens.push(Token::Number(parse_number(&text)?));
+                continue;
+            }
+            c if c.is_ascii_alphabetic() || c == '_' || c == '.' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '.')
+                {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+                continue;
+            }
+            _ => {
+                let rest: String = chars[i..].iter().take(2).collect();
+                match OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+                    Some(op) => {
+                        tokens.push(Token::Op(op));
+                        i += op.len();
+                        continue;
+                    }
+                    None => {
+                        return Err(Error::Parse(format!(
+                            "Unexpected character {:?} in expression {:?}",
+                            c, s
+                        )))
+                    }
+                }
+            }
+        }
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+fn parse_number(text: &str) -> crate::Result<i64> {
+    let result = if let Some(hex) = text.strip_prefix('$') {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(bin) = text.strip_prefix('%') {
+        i64::from_str_radix(bin, 2)
+    } else {
+        text.parse::<i64>()
+    };
+    result.map_err(|_| Error::Parse(format!("Invalid number: {:?}", text)))
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    resolve: &'a dyn Fn(&str) -> Option<i64>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat_op(&mut self, ops: &[&'static str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) if ops.contains(op) => {
+                let op = *op;
+                self.pos += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, token: Token) -> crate::Result<()> {
+        match self.next() {
+            Some(t) if t == token => Ok(()),
+            t => Err(Error::Parse(format!("Expected {:?}, found {:?}", token, t))),
+        }
+    }
+
+    fn binary(
+        &mut self,
+        ops: &[&'static str],
+        next: fn(&mut Self) -> crate::Result<Expr>,
+    ) -> crate::Result<Expr> {
+        let mut lhs = next(self)?;
+        while let Some(op) = self.eat_op(ops) {
+            let rhs = next(self)?;
+            let op = match op {
+                "||" => BinaryOp::Or,
+                "&&" => BinaryOp::And,
+                "==" => BinaryOp::Eq,
+                "!=" => BinaryOp::Ne,
+                "<" => BinaryOp::Lt,
+                "<=" => BinaryOp::Le,
+                ">" => BinaryOp::Gt,
+                ">=" => BinaryOp::Ge,
+                "|" => BinaryOp::BitOr,
+                "^" => BinaryOp::BitXor,
+                "&" => BinaryOp::BitAnd,
+                "+" => BinaryOp::Add,
+                _ => BinaryOp::Sub,
+            };
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn or(&mut self) -> crate::Result<Expr> {
+        self.binary(&["||"], Self::and)
+    }
+
+    fn and(&mut self) -> crate::Result<Expr> {
+        self.binary(&["&&"], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> crate::Result<Expr> {
+        self.binary(&["==", "!=", "<", "<=", ">", ">="], Self::bitwise)
+    }
+
+    fn bitwise(&mut self) -> crate::Result<Expr> {
+        self.binary(&["|", "^", "&"], Self::sum)
+    }
+
+    fn sum(&mut self) -> crate::Result<Expr> {
+        self.binary(&["+", "-"], Self::unary)
+    }
+
+    fn unary(&mut self) -> crate::Result<Expr> {
+        let op = match self.eat_op(&["!", "-", "~"]) {
+            Some("!") => UnaryOp::Not,
+            Some("-") => UnaryOp::Neg,
+            Some(_) => UnaryOp::BitNot,
+            None => return self.primary(),
+        };
+        Ok(Expr::Unary(op, Box::new(self.unary()?)))
+    }
+
+    fn primary(&mut self) -> crate::Result<Expr> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expr::Number(n)),
+            Some(Token::Ident(name)) => match Register::from_name(&name) {
+                Some(reg) => Ok(Expr::Register(reg)),
+                None => match (self.resolve)(&name) {
+                    Some(value) => Ok(Expr::Number(value)),
+                    None => Err(Error::Parse(format!("Unknown identifier: {:?}", name))),
+                },
+            },
+            Some(Token::LBracket) => {
+                let addr = self.or()?;
+                self.expect(Token::RBracket)?;
+                Ok(Expr::Memory(Box::new(addr)))
+            }
+            Some(Token::LParen) => {
+                let e = self.or()?;
+                self.expect(Token::RParen)?;
+                Ok(e)
+            }
+            t => Err(Error::Parse(format!("Unexpected token: {:?}", t))),
+        }
+    }
+}
+
+impl Expr {
+    // Parses an expression, looking up identifiers that are not registers
+    // with `resolve` (i.e. symbol names).
+    pub fn parse_with(s: &str, resolve: &dyn Fn(&str) -> Option<i64>) -> crate::Result<Expr> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+            resolve,
+        };
+
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            return Err(Error::Parse(format!(
+                "Unexpected token {:?} in expression {:?}",
+                token, s
+            )));
+        }
+        Ok(expr)
+    }
+}
+
+impl FromStr for Expr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Expr::parse_with(s, &|_| None)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Number(n) if *n > 9 => write!(f, "${:X}", n),
+            Expr::Number(n) => write!(f, "{}", n),
+            Expr::Register(Register::Bank) => write!(f, "bank"),
+            Expr::Register(Register::FlagZ) => write!(f, "ZF"),
+            Expr::Register(Register::FlagN) => write!(f, "NF"),
+            Expr::Register(Register::FlagH) => write!(f, "HF"),
+            Expr::Register(Register::FlagC) => write!(f, "CF"),
+            Expr::Register(reg) => write!(f, "{:?}", reg),
+            Expr::Memory(addr) => write!(f, "[{}]", addr),
+            Expr::Unary(op, e) => {
+                let op = match op {
+                    UnaryOp::Not => "!",
+                    UnaryOp::Neg => "-",
+                    UnaryOp::BitNot => "~",
+                };
+                write!(f, "{}({})", op, e)
+            }
+            Expr::Binary(op, l, r) => {
+                let op = match op {
+                    BinaryOp::Or => "||",
+                    BinaryOp::And => "&&",
+                    BinaryOp::Eq => "==",
+                    BinaryOp::Ne => "!=",
+                    BinaryOp::Lt => "<",
+                    BinaryOp::Le => "<=",
+                    BinaryOp::Gt => ">",
+                    BinaryOp::Ge => ">=",
+                    BinaryOp::BitOr => "|",
+                    BinaryOp::BitXor => "^",
+                    BinaryOp::BitAnd => "&",
+                    BinaryOp::Add => "+",
+                    BinaryOp::Sub => "-",
+                };
+                write!(f, "({} {} {})", l, op, r)
+            }
+        }
+    }
+}
@@ -1,21 +1,41 @@
 #![allow(clippy::new_without_default)]
 
-use anyhow::Error;
-
-use crate::{bits, cartridge::Cartridge, format_binary, globals::*, opcodes, opcodes_cb, utils};
+use crate::{archive, bits, cartridge::Cartridge, format_binary, globals::*, opcodes, opcodes_cb, patch, utils};
+use crate::breakpoints::{BreakAction, BreakHit, Breakpoint};
+use crate::callstack::{CallStack, FrameKind, StackFrame};
+use crate::cheats::Cheats;
+use crate::oam_bug::{self, Corruption, OAM_ROWS};
+use crate::coverage::{self, Coverage};
+use crate::profiler::Profiler;
+use crate::symbols::SymbolTable;
+use crate::validation::ValidationPolicy;
+use crate::watchpoints::{WatchAction, WatchHit, WatchKind, Watchpoint};
+use crate::Error;
 
+use std::cell::{Cell, Ref, RefCell};
 use std::default::Default;
+use std::path::PathBuf;
 use std::{fmt, io, io::Write};
 
 pub struct GameboyBuilder {
     cpu: Cpu,
     cart: Option<Cartridge>,
+    // ROM file name and its extracted image, loaded into `cart` by `build`
+    rom: Option<(String, Vec<u8>)>,
+    validation: ValidationPolicy,
+    patches: Vec<String>,
     cgb_mode: Option<bool>,
-    breakpoints: Option<Vec<usize>>,
+    breakpoints: Option<Vec<Breakpoint>>,
+    watchpoints: Vec<Watchpoint>,
+    symbols: Option<SymbolTable>,
+    coverage: Option<Coverage>,
+    profiler: Option<Profiler>,
+    cheats: Cheats,
     opcode_map: OpCodeMap,
     opcode_map_cb: OpCodeMap,
     test_mode: bool,
     panic_on_stuck: bool,
+    access_restrictions: bool,
 }
 
 impl GameboyBuilder {
@@ -23,19 +43,34 @@ impl GameboyBuilder {
         GameboyBuilder {
             cpu: Cpu::default(),
             cart: None,
+            rom: None,
+            validation: ValidationPolicy::default(),
+            patches: Vec::new(),
             cgb_mode: None,
             opcode_map: opcodes::init_opcodes(),
             opcode_map_cb: opcodes_cb::init_opcodes_cb(),
             breakpoints: None,
+            watchpoints: Vec::new(),
+            symbols: None,
+            coverage: None,
+            profiler: None,
+            cheats: Cheats::new(),
             test_mode: false,
             panic_on_stuck: false,
+            access_restrictions: true,
         }
     }
 
-    pub fn build(mut self) -> Gameboy {
+    pub fn build(mut self) -> crate::Result<Gameboy> {
+        if let Some((filename, rom)) = self.rom.take() {
+            self.cart = Some(self.load_cart(&filename, rom)?);
+        }
+        if let (Some(coverage), Some(cart)) = (&mut self.coverage, &self.cart) {
+            coverage.bind_rom(cart.rom())?;
+        }
         self.cpu.reset();
-        println!("Panic on stuck: {}", self.panic_on_stuck);
-        Gameboy {
+        log::debug!("Panic on stuck: {}", self.panic_on_stuck);
+        Ok(Gameboy {
             cpu: self.cpu,
             cart: self.cart.unwrap_or(Cartridge::empty()),
             double_speed: false,
@@ -48,18 +83,56 @@ impl GameboyBuilder {
             timer_div_counter: 0,
             timer_tima_counter: 0,
             breakpoints: self.breakpoints.unwrap_or_default(),
+            break_hit: None,
+            resume_pc: None,
+            watchpoints: self.watchpoints,
+            watch_hit: Cell::new(None),
+            symbols: self.symbols,
+            coverage: self.coverage.map(RefCell::new),
+            profiler: self.profiler,
+            cheats: self.cheats,
+            frame_cycles: 0,
+            call_stack: CallStack::new(),
             test_mode: self.test_mode,
             panic_on_stuck: self.panic_on_stuck,
+            access_restrictions: self.access_restrictions,
+        })
+    }
+
+    // Patches given with `with_patch` are applied in order. Without any,
+    // `<rom>.ips/.ups/.bps` next to the ROM is soft-patched in.
+    fn load_cart(&self, filename: &str, mut rom: Vec<u8>) -> crate::Result<Cartridge> {
+        let patches = match self.patches.is_empty() {
+            true => patch::find_for_rom(filename).into_iter().collect(),
+            false => self.patches.iter().map(PathBuf::from).collect::<Vec<_>>(),
+        };
+        for path in patches {
+            log::info!("Applying patch {}", path.display());
+            rom = patch::apply(&std::fs::read(&path)?, &rom)?;
         }
+        Cartridge::from_rom(&rom, self.validation)
     }
 
-    pub fn with_cart(mut self, filename: &str) -> anyhow::Result<GameboyBuilder> {
-        self.cart = Some(Cartridge::new(filename)?);
+    // reads the ROM now, patching and validation happen in `build`
+    pub fn with_cart(mut self, filename: &str) -> crate::Result<GameboyBuilder> {
+        let rom = archive::extract_rom(&std::fs::read(filename)?)?;
+        self.rom = Some((filename.to_string(), rom));
         Ok(self)
     }
 
+    pub fn with_patch(mut self, path: &str) -> GameboyBuilder {
+        self.patches.push(path.to_string());
+        self
+    }
+
+    pub fn with_validation_policy(mut self, policy: ValidationPolicy) -> GameboyBuilder {
+        self.validation = policy;
+        self
+    }
+
     pub fn set_cart(mut self, cart: Cartridge) -> GameboyBuilder {
         self.cart = Some(cart);
+        self.rom = None;
         self
     }
 
@@ -68,11 +141,38 @@ impl GameboyBuilder {
         self
     }
 
-    pub fn with_cpu_breakpoints(mut self, breakpoints: Vec<usize>) -> GameboyBuilder {
+    pub fn with_breakpoints(mut self, breakpoints: Vec<Breakpoint>) -> GameboyBuilder {
         self.breakpoints = Some(breakpoints);
         self
     }
 
+    pub fn with_watchpoints(mut self, watchpoints: Vec<Watchpoint>) -> GameboyBuilder {
+        self.watchpoints = watchpoints;
+        self
+    }
+
+    pub fn with_symbols(mut self, symbols: SymbolTable) -> GameboyBuilder {
+        self.symbols = Some(symbols);
+        self
+    }
+
+    // records executed code and ROM data reads, see `coverage`; `build` rejects
+    // coverage recorded for a different ROM
+    pub fn with_coverage(mut self, coverage: Coverage) -> GameboyBuilder {
+        self.coverage = Some(coverage);
+        self
+    }
+
+    pub fn with_profiler(mut self, profiler: Profiler) -> GameboyBuilder {
+        self.profiler = Some(profiler);
+        self
+    }
+
+    pub fn with_cheats(mut self, cheats: Cheats) -> GameboyBuilder {
+        self.cheats = cheats;
+        self
+    }
+
     pub fn enable_test_mode(mut self) -> GameboyBuilder {
         self.test_mode = true;
         self
@@ -82,6 +182,12 @@ impl GameboyBuilder {
         self.panic_on_stuck = true;
         self
     }
+
+    // lets the CPU access VRAM and OAM in every PPU mode, for debugging tools
+    pub fn disable_access_restrictions(mut self) -> GameboyBuilder {
+        self.access_restrictions = false;
+        self
+    }
 }
 
 pub struct Gameboy {
@@ -96,9 +202,38 @@ pub struct Gameboy {
     memory: Vec<u8>,
     opcode_map: OpCodeMap,
     opcode_map_cb: OpCodeMap,
-    breakpoints: Vec<usize>,
+    breakpoints: Vec<Breakpoint>,
+    break_hit: Option<BreakHit>,
+    // PC a pause breakpoint stopped at, skipped once when resuming
+    resume_pc: Option<u16>,
+    watchpoints: Vec<Watchpoint>,
+    watch_hit: Cell<Option<WatchHit>>,
+    symbols: Option<SymbolTable>,
+    coverage: Option<RefCell<Coverage>>,
+    profiler: Option<Profiler>,
+    cheats: Cheats,
+    // cycles into the current frame, GameShark codes apply when it wraps
+    frame_cycles: OpCycles,
+    call_stack: CallStack,
     test_mode: bool,
     panic_on_stuck: bool,
+    // VRAM and OAM are blocked from the CPU while the PPU uses them
+    access_restrictions: bool,
+}
+
+// mode bits of STAT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PpuMode {
+    HBlank = 0,
+    VBlank = 1,
+    OamScan = 2,
+    PixelTransfer = 3,
+}
+
+// WRAM address an echo RAM address mirrors
+#[inline]
+fn echo_mirror(address: u16) -> u16 {
+    address - (ECHO_RAM_ADDRESS_START - WRAM_ADDRESS_START)
 }
 
 impl Gameboy {
@@ -154,35 +289,390 @@ impl Gameboy {
     pub fn new() -> Gameboy {
         let mut mb = GameboyBuilder::new()
             .with_cart("assests/cpu_instrs.gb")
-            .expect("Failed to load cart")
-            .build();
+            .and_then(GameboyBuilder::build)
+            .expect("Failed to load cart");
         mb.cpu.reset();
         mb
     }
 
-    pub fn execute_op_code(&mut self, op_code: u8, value: u16) -> anyhow::Result<OpCycles> {
+    pub fn execute_op_code(&mut self, op_code: u8, value: u16) -> crate::Result<OpCycles> {
+        let illegal = Error::IllegalOpcode {
+            pc: self.cpu.pc,
+            op: op_code,
+        };
         if ILLEGAL_OPCODES.contains(&op_code) {
             self.cpu.is_stuck = true;
-            return Err(Error::msg(format!("Illegal opcode: {:#x}", op_code)));
+            return Err(illegal);
+        }
+
+        // the CB prefix runs the next byte from the second table
+        if op_code == 0xCB {
+            self.cpu.pc = self.cpu.pc.wrapping_add(1);
+            let cb_opcode = self.fetch(self.cpu.pc);
+            self.cpu.pc = self.cpu.pc.wrapping_add(1);
+            return self.execute_op_code_cb(cb_opcode);
         }
 
         match self.opcode_map.get(&op_code) {
             Some(op) => Ok(op(self, value)),
-            None => Err(Error::msg(format!("Unexpected opcode: {:#x}", op_code))),
+            None => Err(illegal),
         }
     }
 
-    pub fn execute_op_code_cb(&mut self, op_code: u8) -> anyhow::Result<OpCycles> {
+    pub fn execute_op_code_cb(&mut self, op_code: u8) -> crate::Result<OpCycles> {
         match self.opcode_map_cb.get(&op_code) {
             Some(op) => Ok(op(self, 0)),
-            None => Err(Error::msg(format!("Unexpected opcode: {:#x}", op_code))),
+            None => Err(Error::IllegalOpcode {
+                pc: self.cpu.pc,
+                op: op_code,
+            }),
+        }
+    }
+
+    pub fn symbols(&self) -> Option<&SymbolTable> {
+        self.symbols.as_ref()
+    }
+
+    pub fn set_symbols(&mut self, symbols: Option<SymbolTable>) {
+        self.symbols = symbols;
+    }
+
+    // `Name+offset` of the closest symbol to an address in the currently mapped bank
+    pub fn describe_address(&self, address: u16) -> Option<String> {
+        self.symbols
+            .as_ref()
+            .and_then(|symbols| symbols.describe(self.cart.bank(address), address))
+    }
+
+    pub fn coverage(&self) -> Option<Ref<'_, Coverage>> {
+        self.coverage.as_ref().map(|coverage| coverage.borrow())
+    }
+
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take().map(RefCell::into_inner)
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    pub fn profiler_mut(&mut self) -> Option<&mut Profiler> {
+        self.profiler.as_mut()
+    }
+
+    pub fn cheats(&self) -> &Cheats {
+        &self.cheats
+    }
+
+    pub fn cheats_mut(&mut self) -> &mut Cheats {
+        &mut self.cheats
+    }
+
+    // tilt in g for MBC7 carts, positive is right and down; ignored by others
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.cart.set_tilt(x, y);
+    }
+
+    // 128x112 grayscale image for the Pocket Camera's sensor; ignored by other carts
+    pub fn set_camera_image(&mut self, image: &[u8]) -> crate::Result<()> {
+        self.cart.set_camera_image(image)
+    }
+
+    fn apply_gameshark(&mut self) {
+        // CGB WRAM bank mapped at $D000, bank 0 selects 1
+        let wram_bank = match self.cgb_mode {
+            true => (self.memory[IO_SVBK as usize] & 0x07).max(1),
+            false => 1,
+        };
+        let writes = self.cheats.writes().collect::<Vec<_>>();
+        for (bank, address, value) in writes {
+            let in_bank = match bank {
+                Some(bank) => !(WRAM1_ADDRESS_START..=WRAM1_ADDRESS_END).contains(&address) || bank == wram_bank,
+                None => true,
+            };
+            if in_bank {
+                self.debug_write(address, value);
+            }
+        }
+    }
+
+    pub fn call_stack(&self) -> &CallStack {
+        &self.call_stack
+    }
+
+    // symbolized backtrace from the current PC
+    pub fn backtrace(&self) -> String {
+        self.call_stack
+            .backtrace(self.cart.bank(self.cpu.pc), self.cpu.pc, self.symbols.as_ref())
+    }
+
+    // CPU state, backtrace and the last executed instructions
+    pub fn crash_report(&self) -> String {
+        format!(
+            "{}\nBacktrace:\n{}Last instructions:\n{}",
+            self.cpu_state_snapshot(),
+            self.backtrace(),
+            self.call_stack.history_listing(self.symbols.as_ref())
+        )
+    }
+
+    // Follows calls and returns of the instruction that just ran at bank:pc
+    // for the call stack and the profiler, `sp` is the stack pointer before it ran.
+    fn track_instruction(&mut self, op_code: u8, bank: usize, pc: u16, sp: u16, cycles: OpCycles) {
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record(bank, pc, cycles);
+        }
+
+        match op_code {
+            // taken CALL cc/CALL/RST push the return address
+            0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC | 0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF
+                if self.cpu.sp == sp.wrapping_sub(2) =>
+            {
+                let return_addr = pc.wrapping_add(OPCODE_LENGTHS[op_code as usize] as u16);
+                let target_bank = self.cart.bank(self.cpu.pc);
+                self.call_stack.push(StackFrame {
+                    kind: match op_code & 0x07 {
+                        0x07 => FrameKind::Rst,
+                        _ => FrameKind::Call,
+                    },
+                    bank: target_bank,
+                    target: self.cpu.pc,
+                    caller_bank: bank,
+                    caller: pc,
+                    return_addr,
+                    sp: self.cpu.sp,
+                });
+                if let Some(profiler) = self.profiler.as_mut() {
+                    profiler.call(target_bank, self.cpu.pc, return_addr);
+                }
+            }
+            // taken RET cc/RET/RETI pop it
+            0xC0 | 0xC8 | 0xC9 | 0xD0 | 0xD8 | 0xD9 if self.cpu.sp == sp.wrapping_add(2) => {
+                if let Some(profiler) = self.profiler.as_mut() {
+                    profiler.ret(self.cpu.pc);
+                }
+            }
+            _ => {}
+        }
+
+        // returns, and return addresses discarded by POP or SP arithmetic
+        self.call_stack.unwind(self.cpu.sp);
+    }
+
+    #[inline]
+    fn mark_coverage(&self, address: u16, flag: u8) {
+        if let Some(coverage) = &self.coverage {
+            if address <= ROM1_ADDRESS_END {
+                coverage.borrow_mut().mark(self.cart.bank(address), address, flag);
+            }
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    pub fn remove_breakpoint(&mut self, index: usize) -> Option<Breakpoint> {
+        match index < self.breakpoints.len() {
+            true => Some(self.breakpoints.remove(index)),
+            false => None,
+        }
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    // returns the pause breakpoint hit since the last call, if any
+    pub fn take_break_hit(&mut self) -> Option<BreakHit> {
+        self.break_hit.take()
+    }
+
+    // returns false when an identical watchpoint is already set
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> bool {
+        if self.watchpoints.contains(&watchpoint) {
+            return false;
+        }
+        self.watchpoints.push(watchpoint);
+        true
+    }
+
+    pub fn remove_watchpoint(&mut self, watchpoint: &Watchpoint) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|w| w != watchpoint);
+        len != self.watchpoints.len()
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    // returns the first watchpoint hit since the last call, if any
+    pub fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        self.watch_hit.take()
+    }
+
+    #[inline]
+    fn check_watchpoints(&self, address: u16, access: WatchKind, value: u8, previous: Option<u8>) {
+        if self.watchpoints.is_empty() {
+            return;
+        }
+
+        for watchpoint in self.watchpoints.iter() {
+            if !watchpoint.kind.matches(access) || !watchpoint.contains(address) {
+                continue;
+            }
+
+            if let Some(bank) = watchpoint.bank {
+                if self.cart.bank(address) != bank {
+                    continue;
+                }
+            }
+
+            if !watchpoint.condition_met(address, value, previous) {
+                continue;
+            }
+
+            match watchpoint.action {
+                WatchAction::Log => log::debug!(
+                    "Watchpoint {}: {:?} {:02X}:{:04X}={:02X} {}",
+                    watchpoint,
+                    access,
+                    self.cart.bank(address),
+                    address,
+                    value,
+                    self.cpu_state_snapshot()
+                ),
+                WatchAction::Pause => {
+                    log::debug!(
+                        "Watchpoint {}: {:?} {:04X}={:02X} PC: {:04X}",
+                        watchpoint,
+                        access,
+                        address,
+                        value,
+                        self.cpu.pc
+                    );
+                    if self.watch_hit.get().is_none() {
+                        self.watch_hit.set(Some(WatchHit {
+                            kind: watchpoint.kind,
+                            address,
+                            value,
+                            pc: self.cpu.pc,
+                        }));
+                    }
+                }
+            }
+        }
+    }
+
+    fn lcd_enabled(&self) -> bool {
+        bits::is_bit_set(self.memory[IO_LCDC as usize], 7)
+    }
+
+    // scanline and cycles into it
+    fn scanline(&self) -> (OpCycles, OpCycles) {
+        (self.frame_cycles / CYCLES_PER_SCANLINE, self.frame_cycles % CYCLES_PER_SCANLINE)
+    }
+
+    // Until there is a PPU the mode follows the fixed timings of a frame: each
+    // visible line spends 80 cycles in OAM scan, 172 in pixel transfer and the
+    // rest in HBlank, then 10 lines of VBlank.
+    pub fn ppu_mode(&self) -> PpuMode {
+        let (line, dot) = self.scanline();
+        if !self.lcd_enabled() {
+            PpuMode::HBlank
+        } else if line >= VISIBLE_SCANLINES {
+            PpuMode::VBlank
+        } else if dot < CYCLES_OAM_SCAN {
+            PpuMode::OamScan
+        } else if dot < CYCLES_OAM_SCAN + CYCLES_PIXEL_TRANSFER {
+            PpuMode::PixelTransfer
+        } else {
+            PpuMode::HBlank
+        }
+    }
+
+    // moves the PPU to cycles into the frame, used by tests and debuggers
+    pub fn set_frame_cycles(&mut self, cycles: OpCycles) {
+        self.frame_cycles = cycles % CYCLES_PER_FRAME;
+        self.update_lcd_status();
+    }
+
+    // LY and the STAT mode bits show where the PPU is, LY stays 0 while the LCD is off
+    fn update_lcd_status(&mut self) {
+        let mode = self.ppu_mode();
+        self.memory[IO_LY as usize] = match self.lcd_enabled() {
+            true => self.scanline().0 as u8,
+            false => 0,
+        };
+        let status = &mut self.memory[IO_STAT as usize];
+        *status = (*status & !0x03) | mode as u8;
+    }
+
+    // VRAM is unreachable during pixel transfer, OAM during OAM scan and
+    // pixel transfer; reads return $FF and writes are ignored
+    fn ppu_blocks(&self, address: u16) -> bool {
+        if !self.access_restrictions || self.test_mode {
+            return false;
+        }
+        matches!(
+            (address, self.ppu_mode()),
+            (VRAM_ADDRESS_START..=VRAM_ADDRESS_END, PpuMode::PixelTransfer)
+                | (OAM_ADDRESS_START..=OAM_ADDRESS_END, PpuMode::OamScan | PpuMode::PixelTransfer)
+        )
+    }
+
+    // OAM row corruption when the 16-bit inc/dec unit puts address on the bus
+    // during OAM scan, DMG only. The scan reads a row every 4 of its 80 cycles,
+    // at instruction granularity here.
+    pub(crate) fn trigger_oam_bug(&mut self, address: u16, corruption: Corruption) {
+        if self.cgb_mode
+            || self.test_mode
+            || !(OAM_ADDRESS_START..=UNUSABLE_ADDRESS_END).contains(&address)
+            || self.ppu_mode() != PpuMode::OamScan
+        {
+            return;
         }
+
+        let (_, dot) = self.scanline();
+        let row = (dot * OAM_ROWS as OpCycles / CYCLES_OAM_SCAN) as usize;
+        let oam = &mut self.memory[OAM_ADDRESS_START as usize..=OAM_ADDRESS_END as usize];
+        oam_bug::corrupt(oam, row, corruption);
     }
 
     pub fn memory_write(&mut self, address: u16, value: u8) {
+        if self.ppu_blocks(address) {
+            return;
+        }
+        if !self.watchpoints.is_empty() {
+            let previous = self.debug_read(address);
+            self.check_watchpoints(address, WatchKind::Write, value, Some(previous));
+        }
+        self.debug_write(address, value);
+    }
+
+    // a data read by the CPU, debugger and frontend reads go through `debug_read`
+    pub fn memory_read(&self, address: u16) -> u8 {
+        self.mark_coverage(address, coverage::DATA);
+        self.fetch(address)
+    }
+
+    // reads instruction bytes, same as `memory_read` but not recorded as data
+    #[inline]
+    pub(crate) fn fetch(&self, address: u16) -> u8 {
+        let value = match self.ppu_blocks(address) {
+            true => 0xFF,
+            false => self.debug_read(address),
+        };
+        self.check_watchpoints(address, WatchKind::Read, value, None);
+        value
+    }
+
+    // same as `memory_write` but ignores watchpoints and PPU access restrictions, used by debuggers
+    pub fn debug_write(&mut self, address: u16, value: u8) {
         if self.test_mode {
             match address {
-                ROM_ADDRESS_START..=ROM1_ADDRESS_END => {
+                ROM_ADDRESS_START..=ROM1_ADDRESS_END | EXTERNAL_RAM_ADDRESS_START..=EXTERNAL_RAM_ADDRESS_END => {
                     self.cart.write(address, value);
                 }
                 _ => {
@@ -196,9 +686,19 @@ impl Gameboy {
             ROM_ADDRESS_START..=ROM1_ADDRESS_END => {
                 self.cart.write(address, value);
             }
-            EXTERNAL_RAM_ADDRESS_END..=EXTERNAL_RAM_ADDRESS_END => {
+            EXTERNAL_RAM_ADDRESS_START..=EXTERNAL_RAM_ADDRESS_END => {
                 self.cart.write(address, value);
             }
+            ECHO_RAM_ADDRESS_START..=ECHO_RAM_ADDRESS_END => {
+                self.memory[echo_mirror(address) as usize] = value;
+            }
+            UNUSABLE_ADDRESS_START..=UNUSABLE_ADDRESS_END => {}
+            // the mode and coincidence bits are read-only
+            IO_STAT => {
+                let status = self.memory[address as usize];
+                self.memory[address as usize] = (value & 0x78) | (status & 0x07);
+            }
+            IO_PCM12 | IO_PCM34 => {}
 
             IO_DIV => {
                 self.timer_tima_counter = 0;
@@ -226,19 +726,24 @@ impl Gameboy {
         }
     }
 
-    pub fn memory_read(&self, address: u16) -> u8 {
+    // same as `memory_read` but ignores watchpoints and PPU access restrictions, used by debuggers
+    pub fn debug_read(&self, address: u16) -> u8 {
         if self.test_mode {
             return match address {
-                ROM_ADDRESS_START..=ROM1_ADDRESS_END => self.cart.read(address),
-                EXTERNAL_RAM_ADDRESS_END..=EXTERNAL_RAM_ADDRESS_END => self.cart.read(address),
+                ROM_ADDRESS_START..=ROM1_ADDRESS_END => self.cheats.read_rom(address, self.cart.read(address)),
+                EXTERNAL_RAM_ADDRESS_START..=EXTERNAL_RAM_ADDRESS_END => self.cart.read(address),
                 _ => self.memory[address as usize],
             };
         }
 
         match address {
-            ROM_ADDRESS_START..=ROM1_ADDRESS_END => self.cart.read(address),
-            EXTERNAL_RAM_ADDRESS_END..=EXTERNAL_RAM_ADDRESS_END => self.cart.read(address),
-            IO_LY => 0x90,
+            ROM_ADDRESS_START..=ROM1_ADDRESS_END => self.cheats.read_rom(address, self.cart.read(address)),
+            EXTERNAL_RAM_ADDRESS_START..=EXTERNAL_RAM_ADDRESS_END => self.cart.read(address),
+            ECHO_RAM_ADDRESS_START..=ECHO_RAM_ADDRESS_END => self.memory[echo_mirror(address) as usize],
+            UNUSABLE_ADDRESS_START..=UNUSABLE_ADDRESS_END => 0x00,
+            IO_ADDRESS_START..=IO_ADDRESS_END | IO_IE => {
+                self.memory[address as usize] | utils::io_read_mask(address, self.cgb_mode)
+            }
             _ => self.memory[address as usize],
         }
     }
@@ -247,7 +752,7 @@ impl Gameboy {
         let mut result = Vec::new();
         for i in 0..number {
             let pc = self.cpu.pc.wrapping_sub(i);
-            result.push(self.memory_read(pc));
+            result.push(self.debug_read(pc));
         }
         format!("{:02X?}", result)
     }
@@ -256,13 +761,13 @@ impl Gameboy {
         let mut result = Vec::new();
         for i in 0..number {
             let pc = self.cpu.pc.wrapping_add(i);
-            result.push(self.memory_read(pc));
+            result.push(self.debug_read(pc));
         }
         format!("{:02X?}", result)
     }
 
     fn cpu_state_snapshot(&self) -> String {
-        format!(
+        let snapshot = format!(
             "A: {:02X} F: {:02X} B: {:02X} C: {:02X} D: {:02X} E: {:02X} H: {:02X} L: {:02X} SP: {:04X} PC: {:02X}:{:04X} {}|{}",
             self.cpu.a,
             self.cpu.f,
@@ -273,24 +778,95 @@ impl Gameboy {
             self.cpu.h,
             self.cpu.l,
             self.cpu.sp,
-            utils::rom_bank_from_address(self.cpu.pc.into(), self.cart.rom_banks()),
+            self.cart.bank(self.cpu.pc),
             self.cpu.pc,
             self.instruction_look_behind(4),
             self.instruction_look_ahead(4)
-        )
+        );
+
+        match self.describe_address(self.cpu.pc) {
+            Some(symbol) => format!("{} <{}>", snapshot, symbol),
+            None => snapshot,
+        }
     }
 
-    #[inline]
-    fn log_state(&self) {
-        if !self.breakpoints.is_empty() && self.breakpoints.contains(&(self.cpu.pc as usize)) {
-            log::debug!("{}", self.cpu_state_snapshot());
+    fn state_dump(&self) -> String {
+        let flag = |bit: u8, name: char| match bits::is_bit_set(self.cpu.f, bit) {
+            true => name,
+            false => '-',
+        };
+        let hex_row = |addr: u16| {
+            let row: Vec<u8> = (0..16).map(|i| self.debug_read(addr.wrapping_add(i))).collect();
+            format!("{:04X}: {:02X?}", addr, row)
+        };
+        let hl = (self.cpu.h as u16) << 8 | self.cpu.l as u16;
+
+        let dump = [
+            self.cpu_state_snapshot(),
+            format!(
+                "Flags: {}{}{}{} IME: {} IE: {:02X} IF: {:02X} Halted: {}",
+                flag(BIT_FLAGZ, 'Z'),
+                flag(BIT_FLAGN, 'N'),
+                flag(BIT_FLAGH, 'H'),
+                flag(BIT_FLAGC, 'C'),
+                self.interrupts_on,
+                self.debug_read(IO_IE),
+                self.debug_read(IO_IF),
+                self.cpu.halted
+            ),
+            format!("Stack: {}", hex_row(self.cpu.sp)),
+            format!("(HL):  {}", hex_row(hl & 0xFFF0)),
+        ];
+        dump.join("\n")
+    }
+
+    // Checks all breakpoints at the current PC before the instruction executes.
+    // Returns true if a pause breakpoint fired.
+    fn check_breakpoints(&mut self) -> bool {
+        if self.breakpoints.is_empty() {
+            return false;
+        }
+
+        if self.resume_pc.take() == Some(self.cpu.pc) {
+            return false;
+        }
+
+        let mut breakpoints = std::mem::take(&mut self.breakpoints);
+        let mut paused = false;
+
+        for (index, breakpoint) in breakpoints.iter_mut().enumerate() {
+            if !breakpoint.check(self) {
+                continue;
+            }
+
+            for action in breakpoint.actions.iter() {
+                match action {
+                    BreakAction::Log => log::debug!("{}", self.cpu_state_snapshot()),
+                    BreakAction::Dump => log::debug!("Breakpoint {}\n{}", breakpoint, self.state_dump()),
+                    BreakAction::Pause => {
+                        if !paused {
+                            log::debug!("Paused at breakpoint {}", breakpoint);
+                            self.break_hit = Some(BreakHit {
+                                index,
+                                bank: self.cart.bank(self.cpu.pc),
+                                pc: self.cpu.pc,
+                                hits: breakpoint.hits(),
+                            });
+                        }
+                        paused = true;
+                    }
+                }
+            }
         }
-        // let flatten_expr = |addr: u16, bank: usize| -> usize { (bank * ROM_BANK_SIZE) + addr as usize };
 
-        // utils::write_to_file(&self.cpu_state_snapshot());
+        self.breakpoints = breakpoints;
+        if paused {
+            self.resume_pc = Some(self.cpu.pc);
+        }
+        paused
     }
 
-    pub fn tick(&mut self) -> anyhow::Result<OpCycles> {
+    pub fn tick(&mut self) -> crate::Result<OpCycles> {
         let mut cycles: OpCycles = 4;
 
         if self.cpu.stopped || self.cpu.is_stuck {
@@ -298,50 +874,99 @@ impl Gameboy {
         }
 
         if !self.cpu.halted {
+            if self.check_breakpoints() {
+                return Ok(0);
+            }
+
             // Tick CPU
             let old_pc = self.cpu.pc;
             let old_sp = self.cpu.sp;
+            // banks may be switched by the instruction itself
+            let old_bank = self.cart.bank(old_pc);
+            let op_code = self.fetch(old_pc);
             cycles = {
-                let op_code = self.memory_read(self.cpu.pc);
-
-                self.log_state();
+                if self.coverage.is_some() {
+                    let length = match op_code {
+                        0xCB => 2,
+                        _ => OPCODE_LENGTHS[op_code as usize] as u16,
+                    };
+                    self.mark_coverage(self.cpu.pc, coverage::EXECUTED);
+                    for i in 1..length {
+                        self.mark_coverage(self.cpu.pc.wrapping_add(i), coverage::OPERAND);
+                    }
+                }
 
                 let value = match OPCODE_LENGTHS[op_code as usize] {
                     1 => 0,
-                    2 => self.memory_read(self.cpu.pc + 1) as u16,
+                    2 => self.fetch(self.cpu.pc.wrapping_add(1)) as u16,
                     3 => {
-                        let low = self.memory_read(self.cpu.pc + 1) as u16;
-                        let high = self.memory_read(self.cpu.pc + 2) as u16;
+                        let low = self.fetch(self.cpu.pc.wrapping_add(1)) as u16;
+                        let high = self.fetch(self.cpu.pc.wrapping_add(2)) as u16;
                         (high << 8) | low
                     }
-                    _ => {
-                        log::error!("Current State: {}", self.cpu_state_snapshot());
-                        panic!("Invalid opcode length: {:#x}", op_code);
-                    }
+                    // illegal opcodes have no length, execute_op_code rejects them
+                    _ => 0,
+                };
+
+                let bytes = match op_code {
+                    0xCB => [op_code, self.debug_read(old_pc.wrapping_add(1)), 0],
+                    _ => [op_code, value as u8, (value >> 8) as u8],
                 };
+                self.call_stack.record(old_bank, old_pc, bytes);
 
                 // std::thread::sleep(std::time::Duration::from_millis(100));
-                self.execute_op_code(op_code, value)?
+                match self.execute_op_code(op_code, value) {
+                    Ok(cycles) => cycles,
+                    Err(error) => {
+                        return Err(Error::Crash {
+                            error: Box::new(error),
+                            report: self.crash_report(),
+                        })
+                    }
+                }
             };
+            self.track_instruction(op_code, old_bank, old_pc, old_sp, cycles);
 
             if !self.cpu.is_stuck
                 && (old_pc == self.cpu.pc)
                 && (old_sp == self.cpu.sp)
                 && !self.cpu.is_stuck
             {
-                log::warn!("Stuck CPU: {:#x}", old_pc);
+                let report = self.crash_report();
+                log::warn!("Stuck CPU: {:#x}\n{}", old_pc, report);
                 self.cpu.is_stuck = true;
                 if self.panic_on_stuck {
-                    std::process::exit(0);
+                    return Err(Error::Crash {
+                        error: Box::new(Error::Stuck { pc: old_pc }),
+                        report,
+                    });
                 }
             }
+        } else if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record(self.cart.bank(self.cpu.pc), self.cpu.pc, cycles);
         }
         // Tick Cart (RTC)
         // Tick Timer
         self.handle_timer(cycles);
         // Tick PPU
         // Tick Interrupts
-        cycles += self.handle_interrupts();
+        let interrupt_cycles = self.handle_interrupts();
+        if interrupt_cycles > 0 {
+            if let Some(profiler) = self.profiler.as_mut() {
+                profiler.record(self.cart.bank(self.cpu.pc), self.cpu.pc, interrupt_cycles);
+            }
+        }
+        cycles += interrupt_cycles;
+
+        self.frame_cycles += cycles;
+        if self.frame_cycles >= CYCLES_PER_FRAME {
+            self.frame_cycles -= CYCLES_PER_FRAME;
+            // stands in for VBlank until there is a PPU
+            self.apply_gameshark();
+        }
+        if !self.test_mode {
+            self.update_lcd_status();
+        }
         Ok(cycles)
     }
 
@@ -356,8 +981,9 @@ impl Gameboy {
             return 0;
         }
 
-        let req = self.memory_read(IO_IF) | 0xE0;
-        let enabled = self.memory_read(IO_IE);
+        // polled by the interrupt controller, not the CPU
+        let req = self.debug_read(IO_IF) | 0xE0;
+        let enabled = self.debug_read(IO_IE);
 
         if req > 0 {
             for i in 0..5 {
@@ -374,7 +1000,7 @@ impl Gameboy {
     fn service_interrupt(&mut self, interrupt: u8) {
         if self.cpu.halted {
             self.cpu.halted = false;
-            self.cpu.pc += 1;
+            self.cpu.pc = self.cpu.pc.wrapping_add(1);
             return;
         }
 
@@ -386,10 +1012,23 @@ impl Gameboy {
             let sp = self.cpu.sp;
             let pc = self.cpu.pc;
 
-            self.memory_write(sp - 1, ((pc & 0xff00) >> 8) as u8);
-            self.memory_write(sp - 2, (pc & 0xff) as u8);
-            self.cpu.sp -= 2;
+            self.memory_write(sp.wrapping_sub(1), ((pc & 0xff00) >> 8) as u8);
+            self.memory_write(sp.wrapping_sub(2), (pc & 0xff) as u8);
+            self.cpu.sp = sp.wrapping_sub(2);
             self.cpu.pc = utils::interrupt_address(interrupt);
+
+            self.call_stack.push(StackFrame {
+                kind: FrameKind::Interrupt,
+                bank: 0,
+                target: self.cpu.pc,
+                caller_bank: self.cart.bank(pc),
+                caller: pc,
+                return_addr: pc,
+                sp: self.cpu.sp,
+            });
+            if let Some(profiler) = self.profiler.as_mut() {
+                profiler.call(0, self.cpu.pc, pc);
+            }
         }
     }
 }
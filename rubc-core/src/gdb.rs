@@ -0,0 +1,524 @@
+// GDB remote serial protocol stub.
+//
+// Registers are exposed as six little-endian 16-bit pairs in the order
+// AF, BC, DE, HL, SP, PC (register numbers 0-5 for `p`/`P`). `monitor bt` and
+// `monitor history` print the shadow call stack and the last instructions.
+// `monitor search start [word] [bcd]` begins a RAM search, narrowed down with
+// `monitor search =N|changed|unchanged|increased|decreased`.
+
+use crate::gameboy::Gameboy;
+use crate::search::{RamSearch, SearchFilter, ValueEncoding, ValueSize};
+use crate::watchpoints::{WatchHit, WatchKind, Watchpoint};
+use crate::Error;
+
+use std::collections::HashSet;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+const INTERRUPT_CHAR: u8 = 0x03;
+const PACKET_SIZE: usize = 0x4000;
+
+// number of instructions executed between polls for a client interrupt
+const POLL_INTERVAL: usize = 0x1000;
+
+// search candidates listed by `monitor search`
+const SEARCH_LISTED: usize = 16;
+
+const REG_AF: usize = 0;
+const REG_BC: usize = 1;
+const REG_DE: usize = 2;
+const REG_HL: usize = 3;
+const REG_SP: usize = 4;
+const REG_PC: usize = 5;
+const NUM_REGS: usize = 6;
+
+pub struct GdbStub {
+    listener: TcpListener,
+    breakpoints: HashSet<u16>,
+    watchpoints: Vec<Watchpoint>,
+    search: RamSearch,
+}
+
+enum Resume {
+    Step,
+    Continue,
+}
+
+enum Session {
+    Running,
+    Detached,
+}
+
+impl GdbStub {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> crate::Result<GdbStub> {
+        let listener = TcpListener::bind(addr)?;
+        log::info!("GDB stub listening on {}", listener.local_addr()?);
+        Ok(GdbStub {
+            listener,
+            breakpoints: HashSet::new(),
+            watchpoints: Vec::new(),
+            search: RamSearch::default(),
+        })
+    }
+
+    pub fn local_addr(&self) -> crate::Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    // Accepts a single client and serves it until it detaches, kills the
+    // target or disconnects.
+    pub fn serve(&mut self, gb: &mut Gameboy) -> crate::Result<()> {
+        let (stream, peer) = self.listener.accept()?;
+        log::info!("GDB client connected from {}", peer);
+        stream.set_nodelay(true)?;
+
+        let mut conn = Connection::new(stream);
+        while let Some(packet) = conn.read_packet()? {
+            log::trace!("gdb <- {}", packet);
+
+            match self.handle_packet(gb, &mut conn, &packet)? {
+                Session::Running => continue,
+                Session::Detached => break,
+            }
+        }
+
+        self.breakpoints.clear();
+        for watchpoint in self.watchpoints.drain(..) {
+            gb.remove_watchpoint(&watchpoint);
+        }
+        log::info!("GDB client disconnected");
+        Ok(())
+    }
+
+    fn handle_packet(
+        &mut self,
+        gb: &mut Gameboy,
+        conn: &mut Connection,
+        packet: &str,
+    ) -> crate::Result<Session> {
+        // invalid UTF-8 comes through as a multibyte U+FFFD
+        let (cmd, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+
+        let reply = match cmd {
+            "?" => stop_reply(SIGTRAP, None),
+            "g" => read_registers(gb),
+            "G" => match write_registers(gb, args) {
+                Ok(_) => "OK".to_string(),
+                Err(_) => "E01".to_string(),
+            },
+            "p" => match usize::from_str_radix(args, 16) {
+                Ok(reg) if reg < NUM_REGS => encode_u16(get_register(gb, reg)),
+                _ => "E01".to_string(),
+            },
+            "P" => match parse_register_write(args) {
+                Some((reg, value)) if reg < NUM_REGS => {
+                    set_register(gb, reg, value);
+                    "OK".to_string()
+                }
+                _ => "E01".to_string(),
+            },
+            "m" => match parse_memory_range(args) {
+                Some((addr, len)) => (0..len)
+                    .map(|i| format!("{:02x}", gb.debug_read(addr.wrapping_add(i as u16))))
+                    .collect(),
+                None => "E01".to_string(),
+            },
+            "M" => match write_memory(gb, args) {
+                Ok(_) => "OK".to_string(),
+                Err(_) => "E01".to_string(),
+            },
+            "Z" | "z" => self.handle_breakpoint(gb, cmd == "Z", args),
+            "s" => {
+                set_resume_address(gb, args);
+                self.resume(gb, conn, Resume::Step)?
+            }
+            "c" => {
+                set_resume_address(gb, args);
+                self.resume(gb, conn, Resume::Continue)?
+            }
+            "v" if args == "Cont?" => "vCont;c;s".to_string(),
+            "v" if args.starts_with("Cont;") => match args[5..].chars().next() {
+                Some('s') => self.resume(gb, conn, Resume::Step)?,
+                Some('c') => self.resume(gb, conn, Resume::Continue)?,
+                _ => String::new(),
+            },
+            "q" if args.starts_with("Rcmd,") => monitor(gb, &mut self.search, &args[5..]),
+            "q" => query(args),
+            "H" | "T" => "OK".to_string(),
+            "D" => {
+                conn.write_packet("OK")?;
+                return Ok(Session::Detached);
+            }
+            "k" => return Ok(Session::Detached),
+            _ => String::new(),
+        };
+
+        log::trace!("gdb -> {}", reply);
+        conn.write_packet(&reply)?;
+        Ok(Session::Running)
+    }
+
+    fn handle_breakpoint(&mut self, gb: &mut Gameboy, insert: bool, args: &str) -> String {
+        let parts: Vec<&str> = args.split(',').collect();
+        if parts.len() < 3 {
+            return "E01".to_string();
+        }
+
+        let addr = match u16::from_str_radix(parts[1], 16) {
+            Ok(addr) => addr,
+            Err(_) => return "E01".to_string(),
+        };
+        let len = usize::from_str_radix(parts[2], 16).unwrap_or(1).max(1);
+        let end = (addr as usize + len - 1).min(u16::MAX as usize) as u16;
+
+        let kind = match parts[0] {
+            "0" | "1" => {
+                if insert {
+                    self.breakpoints.insert(addr);
+                } else {
+                    self.breakpoints.remove(&addr);
+                }
+                return "OK".to_string();
+            }
+            "2" => WatchKind::Write,
+            "3" => WatchKind::Read,
+            "4" => WatchKind::Access,
+            _ => return String::new(),
+        };
+
+        // only watchpoints the stub added are removed, others are left to whoever set them
+        let watchpoint = Watchpoint::new(kind, addr, end);
+        if insert {
+            if gb.add_watchpoint(watchpoint.clone()) {
+                self.watchpoints.push(watchpoint);
+            }
+        } else if self.watchpoints.contains(&watchpoint) {
+            gb.remove_watchpoint(&watchpoint);
+            self.watchpoints.retain(|w| *w != watchpoint);
+        }
+        "OK".to_string()
+    }
+
+    fn resume(
+        &mut self,
+        gb: &mut Gameboy,
+        conn: &mut Connection,
+        mode: Resume,
+    ) -> crate::Result<String> {
+        gb.take_watch_hit();
+        gb.take_break_hit();
+        let mut executed = 0;
+
+        loop {
+            if let Err(e) = gb.tick() {
+                log::error!("GDB target stopped: {}", e);
+                return Ok(stop_reply(SIGILL, None));
+            }
+            executed += 1;
+
+            if let Some(hit) = gb.take_watch_hit() {
+                return Ok(stop_reply(SIGTRAP, Some(hit)));
+            }
+
+            if gb.take_break_hit().is_some() {
+                return Ok(stop_reply(SIGTRAP, None));
+            }
+
+            match mode {
+                Resume::Step => return Ok(stop_reply(SIGTRAP, None)),
+                Resume::Continue => {
+                    if self.breakpoints.contains(&gb.cpu.pc) {
+                        return Ok(stop_reply(SIGTRAP, None));
+                    }
+                }
+            }
+
+            if executed % POLL_INTERVAL == 0 && conn.poll_interrupt()? {
+                return Ok(stop_reply(SIGINT, None));
+            }
+        }
+    }
+}
+
+struct Connection {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            buffer: Vec::new(),
+        }
+    }
+
+    fn fill(&mut self) -> crate::Result<bool> {
+        let mut chunk = [0u8; 1024];
+        let n = self.stream.read(&mut chunk)?;
+        self.buffer.extend_from_slice(&chunk[..n]);
+        Ok(n > 0)
+    }
+
+    // returns `None` once the client has disconnected
+    fn read_packet(&mut self) -> crate::Result<Option<String>> {
+        loop {
+            // drop acks and stray interrupts while the target is stopped
+            while let Some(&b) = self.buffer.first() {
+                if b == b'$' {
+                    break;
+                }
+                self.buffer.remove(0);
+            }
+
+            if let Some(end) = self.buffer.iter().position(|&b| b == b'#') {
+                if self.buffer.len() >= end + 3 {
+                    let data = self.buffer[1..end].to_vec();
+                    let checksum = std::str::from_utf8(&self.buffer[end + 1..end + 3])
+                        .ok()
+                        .and_then(|s| u8::from_str_radix(s, 16).ok());
+                    self.buffer.drain(..end + 3);
+
+                    if checksum != Some(packet_checksum(&data)) {
+                        log::warn!("GDB packet checksum mismatch");
+                        self.stream.write_all(b"-")?;
+                        continue;
+                    }
+
+                    self.stream.write_all(b"+")?;
+                    return Ok(Some(String::from_utf8_lossy(&unescape(&data)).into_owned()));
+                }
+            }
+
+            if !self.fill()? {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn write_packet(&mut self, data: &str) -> crate::Result<()> {
+        let packet = format!("${}#{:02x}", data, packet_checksum(data.as_bytes()));
+        self.stream.write_all(packet.as_bytes())?;
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    fn poll_interrupt(&mut self) -> crate::Result<bool> {
+        self.stream.set_nonblocking(true)?;
+        let result = self.fill();
+        self.stream.set_nonblocking(false)?;
+
+        match result {
+            Ok(_) => {}
+            Err(Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e),
+        }
+
+        match self.buffer.iter().position(|&b| b == INTERRUPT_CHAR) {
+            Some(idx) => {
+                self.buffer.remove(idx);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+fn packet_checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |acc, b| acc.wrapping_add(*b))
+}
+
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len());
+    let mut iter = data.iter();
+    while let Some(&b) = iter.next() {
+        match b {
+            b'}' => {
+                if let Some(&next) = iter.next() {
+                    result.push(next ^ 0x20);
+                }
+            }
+            _ => result.push(b),
+        }
+    }
+    result
+}
+
+fn stop_reply(signal: u8, hit: Option<WatchHit>) -> String {
+    match hit {
+        Some(hit) => {
+            let kind = match hit.kind {
+                WatchKind::Write => "watch",
+                WatchKind::Read => "rwatch",
+                WatchKind::Access => "awatch",
+            };
+            format!("T{:02x}{}:{:04x};", signal, kind, hit.address)
+        }
+        None => format!("S{:02x}", signal),
+    }
+}
+
+fn query(args: &str) -> String {
+    match args.split(':').next().unwrap_or("") {
+        "Supported" => format!("PacketSize={:x};swbreak+;hwbreak+", PACKET_SIZE),
+        "Attached" => "1".to_string(),
+        "C" => "QC1".to_string(),
+        "fThreadInfo" => "m1".to_string(),
+        "sThreadInfo" => "l".to_string(),
+        _ => String::new(),
+    }
+}
+
+// `monitor` commands, the command and its output are hex encoded
+fn monitor(gb: &Gameboy, search: &mut RamSearch, command: &str) -> String {
+    let command: Option<Vec<u8>> = (0..command.len())
+        .step_by(2)
+        .map(|i| command.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+        .collect();
+    let output = match command.as_deref().map(String::from_utf8_lossy).as_deref().map(str::trim) {
+        Some("bt") | Some("backtrace") => gb.backtrace(),
+        Some("history") => gb.call_stack().history_listing(gb.symbols()),
+        Some(command) if command.starts_with("search") => search_command(gb, search, &command[6..]),
+        Some(_) => "Unknown command, try `bt`, `history` or `search`\n".to_string(),
+        None => return "E01".to_string(),
+    };
+    output.bytes().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn search_command(gb: &Gameboy, search: &mut RamSearch, args: &str) -> String {
+    let mut words = args.split_whitespace();
+    if words.clone().next() == Some("start") {
+        let words = words.skip(1).collect::<Vec<_>>();
+        let size = match words.contains(&"word") {
+            true => ValueSize::Word,
+            false => ValueSize::Byte,
+        };
+        let encoding = match words.contains(&"bcd") {
+            true => ValueEncoding::Bcd,
+            false => ValueEncoding::Binary,
+        };
+        *search = RamSearch::new(size, encoding);
+        return format!("{} candidates\n", search.start(gb));
+    }
+
+    let filter = match words.next().map(str::parse::<SearchFilter>) {
+        Some(Ok(filter)) => filter,
+        Some(Err(e)) => return format!("{}\n", e),
+        None => return "Usage: search start [word] [bcd] | =N | changed | unchanged | increased | decreased\n".to_string(),
+    };
+    let mut output = format!("{} candidates\n", search.filter(gb, filter));
+    for candidate in search.candidates().iter().take(SEARCH_LISTED) {
+        output += &format!("{:04X} = {}\n", candidate.address, candidate.value);
+    }
+    output
+}
+
+fn encode_u16(value: u16) -> String {
+    format!("{:02x}{:02x}", value & 0xFF, value >> 8)
+}
+
+fn decode_u16(hex: &str) -> Option<u16> {
+    if hex.len() != 4 {
+        return None;
+    }
+    let lo = u8::from_str_radix(&hex[0..2], 16).ok()?;
+    let hi = u8::from_str_radix(&hex[2..4], 16).ok()?;
+    Some((hi as u16) << 8 | lo as u16)
+}
+
+fn get_register(gb: &Gameboy, reg: usize) -> u16 {
+    let pair = |hi: u8, lo: u8| (hi as u16) << 8 | lo as u16;
+    match reg {
+        REG_AF => pair(gb.cpu.a, gb.cpu.f),
+        REG_BC => pair(gb.cpu.b, gb.cpu.c),
+        REG_DE => pair(gb.cpu.d, gb.cpu.e),
+        REG_HL => pair(gb.cpu.h, gb.cpu.l),
+        REG_SP => gb.cpu.sp,
+        REG_PC => gb.cpu.pc,
+        _ => 0,
+    }
+}
+
+fn set_register(gb: &mut Gameboy, reg: usize, value: u16) {
+    let hi = (value >> 8) as u8;
+    let lo = value as u8;
+    match reg {
+        REG_AF => {
+            gb.cpu.a = hi;
+            gb.cpu.f = lo & 0xF0;
+        }
+        REG_BC => {
+            gb.cpu.b = hi;
+            gb.cpu.c = lo;
+        }
+        REG_DE => {
+            gb.cpu.d = hi;
+            gb.cpu.e = lo;
+        }
+        REG_HL => {
+            gb.cpu.h = hi;
+            gb.cpu.l = lo;
+        }
+        REG_SP => gb.cpu.sp = value,
+        REG_PC => gb.cpu.pc = value,
+        _ => {}
+    }
+}
+
+fn read_registers(gb: &Gameboy) -> String {
+    (0..NUM_REGS).map(|reg| encode_u16(get_register(gb, reg))).collect()
+}
+
+fn write_registers(gb: &mut Gameboy, args: &str) -> crate::Result<()> {
+    if args.len() < NUM_REGS * 4 || !args.is_ascii() {
+        return Err(Error::Protocol("Register packet too short".to_string()));
+    }
+
+    for reg in 0..NUM_REGS {
+        let value = decode_u16(&args[reg * 4..reg * 4 + 4])
+            .ok_or_else(|| Error::Protocol("Invalid register value".to_string()))?;
+        set_register(gb, reg, value);
+    }
+    Ok(())
+}
+
+fn parse_register_write(args: &str) -> Option<(usize, u16)> {
+    let (reg, value) = args.split_once('=')?;
+    Some((usize::from_str_radix(reg, 16).ok()?, decode_u16(value)?))
+}
+
+fn parse_memory_range(args: &str) -> Option<(u16, usize)> {
+    let (addr, len) = args.split_once(',')?;
+    Some((
+        u16::from_str_radix(addr, 16).ok()?,
+        usize::from_str_radix(len, 16).ok()?.min(PACKET_SIZE / 2),
+    ))
+}
+
+fn write_memory(gb: &mut Gameboy, args: &str) -> crate::Result<()> {
+    let (range, data) = args
+        .split_once(':')
+        .ok_or_else(|| Error::Protocol("Missing memory data".to_string()))?;
+    let (addr, len) = parse_memory_range(range).ok_or_else(|| Error::Protocol("Invalid range".to_string()))?;
+
+    if data.len() != len * 2 || !data.is_ascii() {
+        return Err(Error::Protocol("Memory data length mismatch".to_string()));
+    }
+
+    for i in 0..len {
+        let value = u8::from_str_radix(&data[i * 2..i * 2 + 2], 16)
+            .map_err(|_| Error::Protocol("Invalid memory data".to_string()))?;
+        gb.debug_write(addr.wrapping_add(i as u16), value);
+    }
+    Ok(())
+}
+
+fn set_resume_address(gb: &mut Gameboy, args: &str) {
+    if let Ok(addr) = u16::from_str_radix(args, 16) {
+        gb.cpu.pc = addr;
+    }
+}
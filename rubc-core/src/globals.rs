@@ -7,6 +7,11 @@ pub type OpCodeMap = phf::Map<u8, OpCodeFunc>;
 
 pub const DMG_CLOCK_SPEED: u64 = 4194304;
 pub const GB_TIMER_FREQ: u64 = 16384;
+pub const CYCLES_PER_FRAME: OpCycles = 70224;
+pub const CYCLES_PER_SCANLINE: OpCycles = 456;
+pub const CYCLES_OAM_SCAN: OpCycles = 80;
+pub const CYCLES_PIXEL_TRANSFER: OpCycles = 172;
+pub const VISIBLE_SCANLINES: OpCycles = 144;
 
 pub const INTR_VBLANK_POS: u8 = 0;
 pub const INTR_LCD_STAT_POS: u8 = 1;
@@ -22,9 +27,20 @@ pub const INTR_HIGH_TO_LOW: u16 = 0x0060;
 
 pub const ROM_MAX_BANKS_MBC0: usize = 2;
 pub const ROM_MAX_BANKS_MBC1: usize = 128;
+pub const ROM_MAX_BANKS_MMM01: usize = 512;
+pub const ROM_MAX_BANKS_HUC1: usize = 64;
+pub const ROM_MAX_BANKS_HUC3: usize = 128;
+pub const ROM_MAX_BANKS_MBC7: usize = 128;
+pub const ROM_MAX_BANKS_CAMERA: usize = 64;
 pub const ROM_BANK_SIZE: usize = 0x4000;
+// the largest ROM a header can declare, 512 banks or 8 MiB
+pub const ROM_MAX_SIZE: usize = 512 * ROM_BANK_SIZE;
 
 pub const RAM_MAX_BANKS_MBC1: usize = 4;
+pub const RAM_MAX_BANKS_MMM01: usize = 16;
+pub const RAM_MAX_BANKS_HUC1: usize = 4;
+pub const RAM_MAX_BANKS_HUC3: usize = 4;
+pub const RAM_MAX_BANKS_CAMERA: usize = 16;
 pub const RAM_BANK_SIZE: usize = 0x2000;
 
 pub const CYCLE_RETURN_4: OpCycles = 4;
@@ -51,10 +67,12 @@ pub const WRAM_ADDRESS_START: u16 = 0xC000;
 pub const WRAM_ADDRESS_END: u16 = 0xCFFF;
 pub const WRAM1_ADDRESS_START: u16 = 0xD000;
 pub const WRAM1_ADDRESS_END: u16 = 0xDFFF;
-pub const ECHO_RAM_ADDRESS_START: u16 = 0xC000;
-pub const ECHO_RAM_ADDRESS_END: u16 = 0xDDFF;
+pub const ECHO_RAM_ADDRESS_START: u16 = 0xE000; // mirror of 0xC000-0xDDFF
+pub const ECHO_RAM_ADDRESS_END: u16 = 0xFDFF;
 pub const OAM_ADDRESS_START: u16 = 0xFE00;
 pub const OAM_ADDRESS_END: u16 = 0xFE9F;
+pub const UNUSABLE_ADDRESS_START: u16 = 0xFEA0;
+pub const UNUSABLE_ADDRESS_END: u16 = 0xFEFF;
 pub const IO_ADDRESS_START: u16 = 0xFF00;
 pub const IO_ADDRESS_END: u16 = 0xFF7F;
 pub const HRAM_ADDRESS_START: u16 = 0xFF80;
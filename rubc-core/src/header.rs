@@ -0,0 +1,368 @@
+// Cartridge header at $0100-$014F.
+//
+// The title area changed meaning over time: 16 bytes on DMG carts, 15 once
+// $0143 became the CGB flag, and 11 on later carts that store a 4 character
+// manufacturer code at $013F-$0142. The manufacturer code is only recognised
+// on CGB carts using the new licensee code, when it is 4 uppercase letters or
+// digits following either an 11 character title or the title's padding.
+
+use crate::validation::{self, LogoCheck};
+use crate::{globals::*, Error};
+
+use prettytable::{format, row, Table};
+use serde::{Serialize, Serializer};
+
+// licensee byte telling that the new licensee code at $0144 is used
+const USE_NEW_LICENSEE: u8 = 0x33;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CgbSupport {
+    // DMG only
+    None,
+    // runs on both, with CGB enhancements
+    Compatible,
+    // CGB only
+    Only,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mapper {
+    RomOnly,
+    Mbc1,
+    Mbc2,
+    Mmm01,
+    Mbc3,
+    Mbc5,
+    Mbc6,
+    Mbc7,
+    PocketCamera,
+    Tama5,
+    HuC3,
+    HuC1,
+    Unknown,
+}
+
+impl Mapper {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mapper::RomOnly => "ROM",
+            Mapper::Mbc1 => "MBC1",
+            Mapper::Mbc2 => "MBC2",
+            Mapper::Mmm01 => "MMM01",
+            Mapper::Mbc3 => "MBC3",
+            Mapper::Mbc5 => "MBC5",
+            Mapper::Mbc6 => "MBC6",
+            Mapper::Mbc7 => "MBC7",
+            Mapper::PocketCamera => "POCKET CAMERA",
+            Mapper::Tama5 => "TAMA5",
+            Mapper::HuC3 => "HuC3",
+            Mapper::HuC1 => "HuC1",
+            Mapper::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+impl Serialize for Mapper {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+// hardware on the cartridge besides the mapper
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Features {
+    pub ram: bool,
+    pub battery: bool,
+    pub timer: bool,
+    pub rumble: bool,
+    // MBC7 accelerometer
+    pub sensor: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CartridgeHeader {
+    pub title: String,
+    pub manufacturer_code: Option<String>,
+    pub cgb: CgbSupport,
+    pub sgb: bool,
+    pub cart_type: u8,
+    pub mapper: Mapper,
+    pub features: Features,
+    // sizes in bytes, `None` when the size byte is unknown
+    pub rom_size: Option<usize>,
+    pub ram_size: Option<usize>,
+    // new licensee code, or the old one as two hex digits
+    pub licensee_code: String,
+    pub licensee: &'static str,
+    pub destination: &'static str,
+    pub version: u8,
+    pub header_checksum: u8,
+    pub header_checksum_valid: bool,
+    pub global_checksum: u16,
+}
+
+// mapper and features of a cartridge type byte
+pub fn mapper(cart_type: u8) -> (Mapper, Features) {
+    let features = |ram, battery, timer, rumble| Features {
+        ram,
+        battery,
+        timer,
+        rumble,
+        sensor: false,
+    };
+
+    match cart_type {
+        0x00 => (Mapper::RomOnly, features(false, false, false, false)),
+        0x01 => (Mapper::Mbc1, features(false, false, false, false)),
+        0x02 => (Mapper::Mbc1, features(true, false, false, false)),
+        0x03 => (Mapper::Mbc1, features(true, true, false, false)),
+        // MBC2 has 512 half bytes of built-in RAM
+        0x05 => (Mapper::Mbc2, features(true, false, false, false)),
+        0x06 => (Mapper::Mbc2, features(true, true, false, false)),
+        0x08 => (Mapper::RomOnly, features(true, false, false, false)),
+        0x09 => (Mapper::RomOnly, features(true, true, false, false)),
+        0x0B => (Mapper::Mmm01, features(false, false, false, false)),
+        0x0C => (Mapper::Mmm01, features(true, false, false, false)),
+        0x0D => (Mapper::Mmm01, features(true, true, false, false)),
+        0x0F => (Mapper::Mbc3, features(false, true, true, false)),
+        0x10 => (Mapper::Mbc3, features(true, true, true, false)),
+        0x11 => (Mapper::Mbc3, features(false, false, false, false)),
+        0x12 => (Mapper::Mbc3, features(true, false, false, false)),
+        0x13 => (Mapper::Mbc3, features(true, true, false, false)),
+        0x19 => (Mapper::Mbc5, features(false, false, false, false)),
+        0x1A => (Mapper::Mbc5, features(true, false, false, false)),
+        0x1B => (Mapper::Mbc5, features(true, true, false, false)),
+        0x1C => (Mapper::Mbc5, features(false, false, false, true)),
+        0x1D => (Mapper::Mbc5, features(true, false, false, true)),
+        0x1E => (Mapper::Mbc5, features(true, true, false, true)),
+        0x20 => (Mapper::Mbc6, features(true, true, false, false)),
+        0x22 => (
+            Mapper::Mbc7,
+            Features {
+                sensor: true,
+                ..features(true, true, false, true)
+            },
+        ),
+        0xFC => (Mapper::PocketCamera, features(true, true, false, false)),
+        0xFD => (Mapper::Tama5, features(true, true, true, false)),
+        0xFE => (Mapper::HuC3, features(true, true, true, false)),
+        0xFF => (Mapper::HuC1, features(true, true, false, false)),
+        _ => (Mapper::Unknown, Features::default()),
+    }
+}
+
+// ROM size in bytes of a ROM size byte
+pub fn rom_size(value: u8) -> Option<usize> {
+    let banks = match value {
+        0x00..=0x08 => 2 << value,
+        0x52 => 72,
+        0x53 => 80,
+        0x54 => 96,
+        _ => return None,
+    };
+    Some(banks * ROM_BANK_SIZE)
+}
+
+// RAM size in bytes of a RAM size byte
+pub fn ram_size(value: u8) -> Option<usize> {
+    match value {
+        0x00 => Some(0),
+        0x01 => Some(0x800),
+        0x02 => Some(RAM_BANK_SIZE),
+        0x03 => Some(RAM_BANK_SIZE * 4),
+        0x04 => Some(RAM_BANK_SIZE * 16),
+        0x05 => Some(RAM_BANK_SIZE * 8),
+        _ => None,
+    }
+}
+
+// checksum of $0134-$014C as computed by the boot ROM
+pub fn header_checksum(rom: &[u8]) -> u8 {
+    rom[CART_TITLE_START as usize..=CART_MASK_ROM_VERSION_NUMBER as usize]
+        .iter()
+        .fold(0u8, |acc, x| acc.wrapping_sub(*x).wrapping_sub(1))
+}
+
+// the boot ROM would accept the header at the start of area
+fn boots(area: &[u8]) -> bool {
+    validation::check_logo(area) == LogoCheck::Valid && area[CART_HEADER_CHECKSUM as usize] == header_checksum(area)
+}
+
+// Part of the ROM holding the header. MMM01 carts boot from their last
+// 32 KiB, which has the cart's header, while $0100 belongs to the first game
+// and declares only its size. The trailing header is used only when it is a
+// bootable MMM01 header and $0100 is not a bootable header for the whole ROM,
+// so a normal ROM with a stray MMM01 type byte keeps its own header.
+pub fn header_area(rom: &[u8]) -> &[u8] {
+    let boot = rom.len().saturating_sub(2 * ROM_BANK_SIZE);
+    if boot == 0 {
+        return rom;
+    }
+    let trailing = &rom[boot..];
+    let own = boots(rom) && rom_size(rom[CART_ROM_SIZE as usize]) == Some(rom.len());
+    match !own && mapper(trailing[CART_TYPE as usize]).0 == Mapper::Mmm01 && boots(trailing) {
+        true => trailing,
+        false => rom,
+    }
+}
+
+fn ascii(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .take_while(|b| **b != 0)
+        .map(|b| match b {
+            0x20..=0x7E => *b as char,
+            _ => '?',
+        })
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+impl CartridgeHeader {
+    // Parses the header from a ROM image, or any slice covering $0000-$014F.
+    pub fn parse(rom: &[u8]) -> crate::Result<CartridgeHeader> {
+        if rom.len() <= CART_HEADER_END as usize {
+            return Err(Error::SizeMismatch {
+                expected: CART_HEADER_END as usize + 1,
+                actual: rom.len(),
+            });
+        }
+
+        let cgb_flag = rom[CART_CBG_FLAG as usize];
+        let cgb = match cgb_flag {
+            0x80 => CgbSupport::Compatible,
+            0xC0 => CgbSupport::Only,
+            _ => CgbSupport::None,
+        };
+        let old_licensee = rom[CART_OLD_LICENSEE_CODE as usize];
+
+        let title_area = match cgb {
+            CgbSupport::None => &rom[CART_TITLE_START as usize..=CART_CBG_FLAG as usize],
+            _ => &rom[CART_TITLE_START as usize..CART_CBG_FLAG as usize],
+        };
+        let code = &rom[CART_MANUFACTURER_CODE_START as usize..=CART_MANUFACTURER_CODE_END as usize];
+        let short_title = &title_area[..CART_MANUFACTURER_CODE_START as usize - CART_TITLE_START as usize];
+        let manufacturer_code = match cgb != CgbSupport::None
+            && old_licensee == USE_NEW_LICENSEE
+            && code.iter().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+            && (short_title.iter().all(|b| *b != 0) || short_title.last() == Some(&0))
+        {
+            true => Some(ascii(code)),
+            false => None,
+        };
+        let title = match manufacturer_code {
+            Some(_) => ascii(short_title),
+            None => ascii(title_area),
+        };
+
+        let (licensee_code, licensee) = match old_licensee {
+            USE_NEW_LICENSEE => {
+                let code = ascii(&rom[CART_NEW_LICENSEE_CODE_START as usize..=CART_NEW_LICENSEE_CODE_END as usize]);
+                let licensee = match u8::from_str_radix(&code, 16) {
+                    Ok(value) => new_licensee_code_map(value),
+                    Err(_) => "UNKNOWN",
+                };
+                (code, licensee)
+            }
+            code => (format!("{:02X}", code), old_licensee_code_map(code)),
+        };
+
+        let cart_type = rom[CART_TYPE as usize];
+        let (mapper, features) = mapper(cart_type);
+        let checksum = rom[CART_HEADER_CHECKSUM as usize];
+
+        Ok(CartridgeHeader {
+            title,
+            manufacturer_code,
+            cgb,
+            sgb: rom[CART_SGB_FLAG as usize] == 0x03,
+            cart_type,
+            mapper,
+            features,
+            rom_size: rom_size(rom[CART_ROM_SIZE as usize]),
+            ram_size: ram_size(rom[CART_SRAM_SIZE as usize]),
+            licensee_code,
+            licensee,
+            destination: destination_code_map(rom[CART_DESTINATION_CODE as usize]),
+            version: rom[CART_MASK_ROM_VERSION_NUMBER as usize],
+            header_checksum: checksum,
+            header_checksum_valid: header_checksum(rom) == checksum,
+            global_checksum: (rom[CART_GLOBAL_CHECKSUM_START as usize] as u16) << 8
+                | rom[CART_GLOBAL_CHECKSUM_END as usize] as u16,
+        })
+    }
+
+    pub fn rom_banks(&self) -> Option<usize> {
+        self.rom_size.map(|size| size / ROM_BANK_SIZE)
+    }
+
+    // 2KB of RAM still takes a (partial) bank
+    pub fn ram_banks(&self) -> Option<usize> {
+        self.ram_size.map(|size| size.div_ceil(RAM_BANK_SIZE))
+    }
+
+    pub fn to_json(&self) -> crate::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn to_table(&self) -> String {
+        let yes_no = |value: bool| match value {
+            true => "yes",
+            false => "no",
+        };
+        let size = |size: Option<usize>| match size {
+            Some(0) => "None".to_string(),
+            Some(size) if size >= 0x100000 => format!("{}MB", size as f64 / 0x100000 as f64),
+            Some(size) => format!("{}KB", size / 0x400),
+            None => "UNKNOWN".to_string(),
+        };
+        let features = [
+            ("RAM", self.features.ram),
+            ("BATTERY", self.features.battery),
+            ("TIMER", self.features.timer),
+            ("RUMBLE", self.features.rumble),
+            ("SENSOR", self.features.sensor),
+        ]
+        .iter()
+        .filter(|(_, present)| *present)
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join("+");
+
+        let mut table = Table::new();
+        table.set_titles(row!["Attribute", "Value"]);
+        table.add_row(row!["Title", self.title]);
+        table.add_row(row![
+            "Manufacturer Code",
+            self.manufacturer_code.as_deref().unwrap_or("-")
+        ]);
+        table.add_row(row![
+            "CGB Flag",
+            match self.cgb {
+                CgbSupport::None => "GB",
+                CgbSupport::Compatible => "CGB/GB",
+                CgbSupport::Only => "CGB only",
+            }
+        ]);
+        table.add_row(row!["SGB Flag", yes_no(self.sgb)]);
+        table.add_row(row![
+            "Cartridge Type",
+            format!("{:#04x} {}", self.cart_type, cart_type_map(self.cart_type))
+        ]);
+        table.add_row(row!["Mapper", self.mapper.name()]);
+        table.add_row(row!["Features", if features.is_empty() { "-" } else { &features }]);
+        table.add_row(row!["ROM Size", size(self.rom_size)]);
+        table.add_row(row!["RAM Size", size(self.ram_size)]);
+        table.add_row(row!["Licensee", format!("{} ({})", self.licensee, self.licensee_code)]);
+        table.add_row(row!["Destination", self.destination]);
+        table.add_row(row!["Version", self.version]);
+        table.add_row(row!["Header Checksum", format!("{:#04x}", self.header_checksum)]);
+        table.add_row(row!["Checksum Valid", yes_no(self.header_checksum_valid)]);
+        table.add_row(row!["Global Checksum", format!("{:#06x}", self.global_checksum)]);
+        table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
+
+        table.to_string()
+    }
+}
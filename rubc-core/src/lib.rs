@@ -1,17 +1,36 @@
 #[macro_use]
 pub mod bits;
 
+pub mod archive;
+pub mod breakpoints;
+pub mod callstack;
 pub mod cartridge;
+pub mod cheats;
+pub mod coverage;
+pub mod disasm;
+pub mod error;
+pub mod expr;
 pub mod gameboy;
+pub mod gdb;
 pub mod globals;
+pub mod header;
 pub mod logger;
 pub mod mbc;
+pub mod oam_bug;
 pub mod opcodes;
 pub mod opcodes_cb;
+pub mod patch;
+pub mod profiler;
+pub mod search;
+pub mod symbols;
 pub mod utils;
+pub mod validation;
+pub mod watchpoints;
 
-pub type Result<T> = anyhow::Result<T>;
-pub type Error = anyhow::Error;
+pub use error::RubcError;
+
+pub type Result<T> = std::result::Result<T, RubcError>;
+pub type Error = RubcError;
 
 pub fn format_binary(value: u8) -> String {
     format!("0b{:04b}_{:04b}", value >> 4, value & 0x0F)
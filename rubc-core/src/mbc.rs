@@ -1,5 +1,7 @@
 use crate::globals::*;
-use crate::utils;
+use crate::{utils, Error};
+
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub trait IntoMBC {
     // address gaurenteed to be in range 0x0000..=0x7FFF
@@ -8,14 +10,20 @@ pub trait IntoMBC {
     // address gaurenteed to be in range 0x0000..=0x7FFF
     fn write(&mut self, address: usize, value: u8);
 
-    // address gaurenteed to be in range 0xA000..=0xBFFF
+    // offset into the 0xA000..=0xBFFF window, gaurenteed to be in range 0x0000..=0x1FFF
     fn read_sram(&self, address: usize) -> u8;
 
-    // address gaurenteed to be in range 0xA000..=0xBFFF
+    // offset into the 0xA000..=0xBFFF window, gaurenteed to be in range 0x0000..=0x1FFF
     fn write_sram(&mut self, address: usize, value: u8);
 
     fn rom_banks(&self) -> usize;
     fn ram_banks(&self) -> usize;
+
+    // bank currently mapped at address, address gaurenteed to be in range 0x0000..=0x7FFF
+    fn rom_bank(&self, address: usize) -> usize;
+
+    // bank currently mapped at 0xA000..=0xBFFF
+    fn ram_bank(&self) -> usize;
 }
 
 pub struct DummyMBC {
@@ -56,6 +64,14 @@ impl IntoMBC for DummyMBC {
     fn ram_banks(&self) -> usize {
         1
     }
+
+    fn rom_bank(&self, address: usize) -> usize {
+        address / ROM_BANK_SIZE
+    }
+
+    fn ram_bank(&self) -> usize {
+        0
+    }
 }
 
 pub struct MBC0 {
@@ -93,6 +109,14 @@ impl IntoMBC for MBC0 {
     fn ram_banks(&self) -> usize {
         0
     }
+
+    fn rom_bank(&self, address: usize) -> usize {
+        address / ROM_BANK_SIZE
+    }
+
+    fn ram_bank(&self) -> usize {
+        0
+    }
 }
 
 pub struct MBC1 {
@@ -103,35 +127,62 @@ pub struct MBC1 {
     ram_enabled: bool,
     rom_bank_select: usize,
     ram_bank_select: usize,
-    rom_mask: u8,
     mode: u8,
+    // MBC1M wiring, the upper bank bits go to A18-A19 and only four bits of
+    // the bank register are connected
+    multicart: bool,
 }
 
 impl MBC1 {
+    // bank counts are clamped to what MBC1 can address
     pub fn new(rom_banks: usize, ram_banks: usize) -> MBC1 {
-        let rom_mask = match rom_banks {
-            128 => 0b00011111,
-            64 => 0b00011111,
-            32 => 0b00011111,
-            16 => 0b00001111,
-            8 => 0b00000111,
-            4 => 0b00000011,
-            2 => 0b00000001,
-            _ => panic!("Invalid number of ROM banks: {}", rom_banks),
-        };
+        let rom_banks = rom_banks.clamp(2, ROM_MAX_BANKS_MBC1);
+        let ram_banks = ram_banks.min(RAM_MAX_BANKS_MBC1);
 
         MBC1 {
             rom: vec![0; ROM_MAX_BANKS_MBC1 * ROM_BANK_SIZE].into_boxed_slice(),
             sram: vec![0; RAM_BANK_SIZE * RAM_MAX_BANKS_MBC1].into_boxed_slice(),
-            rom_banks: rom_banks,
-            ram_banks: ram_banks,
+            rom_banks,
+            ram_banks,
             ram_enabled: false,
             rom_bank_select: 1,
             ram_bank_select: 0,
             mode: 0,
-            rom_mask: rom_mask,
+            multicart: false,
         }
     }
+
+    // 1 MiB compilation carts, where each game is 16 banks
+    pub fn multicart(rom_banks: usize, ram_banks: usize) -> MBC1 {
+        MBC1 {
+            multicart: true,
+            ..MBC1::new(rom_banks, ram_banks)
+        }
+    }
+
+    pub fn is_multicart(&self) -> bool {
+        self.multicart
+    }
+
+    // bits of the bank number above the 0x2000 bank register
+    fn upper_bank(&self) -> usize {
+        match self.multicart {
+            true => self.ram_bank_select << 4,
+            false => self.ram_bank_select << 5,
+        }
+    }
+
+    fn lower_bank(&self) -> usize {
+        match self.multicart {
+            true => self.rom_bank_select & 0x0F,
+            false => self.rom_bank_select,
+        }
+    }
+
+    // bank numbers wrap around the ROM, whose size is a power of two
+    fn mask_bank(&self, bank: usize) -> usize {
+        bank & (self.rom_banks.next_power_of_two() - 1)
+    }
 }
 
 impl IntoMBC for MBC1 {
@@ -143,39 +194,45 @@ impl IntoMBC for MBC1 {
         self.ram_banks
     }
 
+    fn rom_bank(&self, address: usize) -> usize {
+        match address {
+            0x0000..=0x3FFF if self.mode == 1 => self.mask_bank(self.upper_bank()),
+            0x0000..=0x3FFF => 0,
+            _ => self.mask_bank(self.upper_bank() | self.lower_bank()),
+        }
+    }
+
+    fn ram_bank(&self) -> usize {
+        match self.mode {
+            1 => self.ram_bank_select % self.ram_banks.max(1),
+            _ => 0,
+        }
+    }
+
     fn read(&self, address: usize) -> u8 {
         match address {
             0x0000..=0x3FFF => {
                 log::trace!("reading from ROM bank 0: {:04X}", address);
-                let mut bank = 0;
-                if self.mode == 1 {
-                    bank = (self.ram_bank_select << 5) & self.rom_banks
-                }
-                self.rom[utils::rom_absolute_address(bank, address)]
+                self.rom[utils::rom_absolute_address(self.rom_bank(address), address)]
             }
             0x4000..=0x7FFF => {
                 log::trace!("reading from ROM bank 1: {:04X}", address);
-                let bank = (self.ram_bank_select << 5) % self.rom_banks | self.rom_bank_select;
-                self.rom[utils::rom_absolute_address(bank, address - 0x4000)]
+                self.rom[utils::rom_absolute_address(self.rom_bank(address), address - 0x4000)]
             }
-            _ => panic!("Invalid ROM address for MBC1 read: {:04X}", address),
+            _ => 0xFF,
         }
     }
 
     fn read_sram(&self, address: usize) -> u8 {
-        if !self.ram_enabled {
+        if !self.ram_enabled || self.ram_banks == 0 {
             return 0xFF;
         }
 
         log::trace!("Reading from SRAM: {:04X}", address);
-        match self.mode {
-            0 => self.sram[utils::ram_absolute_address(0 % self.ram_banks, address)],
-            1 => {
-                self.sram
-                    [utils::ram_absolute_address(self.ram_bank_select % self.ram_banks, address)]
-            }
-            _ => panic!("Memory bank controller mode not supported: {}", self.mode),
-        }
+        self.sram
+            .get(utils::ram_absolute_address(self.ram_bank(), address))
+            .copied()
+            .unwrap_or(0xFF)
     }
 
     fn write(&mut self, address: usize, value: u8) {
@@ -185,11 +242,11 @@ impl IntoMBC for MBC1 {
                 self.ram_enabled = (value & 0x0F) == 0x0A;
             }
             0x2000..=0x3FFF => {
-                if value == 0 {
-                    self.rom_bank_select = 1;
-                } else {
-                    self.rom_bank_select = (value & self.rom_mask) as usize;
-                }
+                // the zero check sees all five bits, even those the ROM doesn't decode
+                self.rom_bank_select = match value & 0x1F {
+                    0 => 1,
+                    bank => bank as usize,
+                };
             }
             0x4000..=0x5FFF => {
                 self.ram_bank_select = (value & 0x03) as usize;
@@ -197,32 +254,1074 @@ impl IntoMBC for MBC1 {
             0x6000..=0x7FFF => {
                 self.mode = value & 0x01;
             }
-            _ => panic!(
-                "Invalid ROM address for MBC1 write: {:04X}={:02X}",
-                address, value
-            ),
+            _ => {}
+        }
+    }
+
+    fn write_sram(&mut self, address: usize, value: u8) {
+        if !self.ram_enabled || self.ram_banks == 0 {
+            return;
+        }
+
+        log::trace!("Writing to SRAM: {:04X}={:02X}", address, value);
+        if let Some(byte) = self.sram.get_mut(utils::ram_absolute_address(self.ram_bank(), address)) {
+            *byte = value;
+        }
+    }
+}
+
+// MMM01 multigame carts start unmapped, with the last 32 KiB of ROM at
+// 0x0000-0x7FFF where the menu lives. The menu sets the outer bank bits and
+// which bank bits the game may still change, then maps the game, which locks
+// everything but the MBC1-like registers until power off.
+pub struct MMM01 {
+    pub rom: Box<[u8]>,
+    pub sram: Box<[u8]>,
+    rom_banks: usize,
+    ram_banks: usize,
+    ram_enabled: bool,
+    mapped: bool,
+    rom_bank_low: usize,
+    rom_bank_mid: usize,
+    rom_bank_high: usize,
+    ram_bank_low: usize,
+    ram_bank_high: usize,
+    // bits 1-4 of the low ROM bank and bits 0-1 of the low RAM bank that are
+    // kept from the menu once mapped
+    rom_bank_mask: usize,
+    ram_bank_mask: usize,
+    mode: u8,
+    mode_locked: bool,
+    // the 0x4000 register selects ROM bits 5-6 and the RAM bank comes from
+    // the locked mid ROM bits, as MBC1 games larger than 512 KiB expect
+    multiplex: bool,
+}
+
+impl MMM01 {
+    pub fn new(rom_banks: usize, ram_banks: usize) -> MMM01 {
+        let rom_banks = rom_banks.clamp(2, ROM_MAX_BANKS_MMM01);
+
+        MMM01 {
+            // banks wrap at a power of two
+            rom: vec![0; rom_banks.next_power_of_two() * ROM_BANK_SIZE].into_boxed_slice(),
+            sram: vec![0; RAM_BANK_SIZE * RAM_MAX_BANKS_MMM01].into_boxed_slice(),
+            rom_banks,
+            ram_banks: ram_banks.min(RAM_MAX_BANKS_MMM01),
+            ram_enabled: false,
+            mapped: false,
+            rom_bank_low: 0,
+            rom_bank_mid: 0,
+            rom_bank_high: 0,
+            ram_bank_low: 0,
+            ram_bank_high: 0,
+            rom_bank_mask: 0,
+            ram_bank_mask: 0,
+            mode: 0,
+            mode_locked: false,
+            multiplex: false,
+        }
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        self.mapped
+    }
+
+    // ROM bits 5-6 and the low RAM bank bits, swapped when multiplexed
+    fn mid_banks(&self) -> (usize, usize) {
+        match self.multiplex {
+            true => (self.ram_bank_low, self.rom_bank_mid),
+            false => (self.rom_bank_mid, self.ram_bank_low),
+        }
+    }
+
+    fn mask_bank(&self, bank: usize) -> usize {
+        bank & (self.rom_banks.next_power_of_two() - 1)
+    }
+}
+
+impl IntoMBC for MMM01 {
+    fn rom_banks(&self) -> usize {
+        self.rom_banks
+    }
+
+    fn ram_banks(&self) -> usize {
+        self.ram_banks
+    }
+
+    fn rom_bank(&self, address: usize) -> usize {
+        if !self.mapped {
+            return match address {
+                0x0000..=0x3FFF => self.mask_bank(!1),
+                _ => self.mask_bank(!0),
+            };
+        }
+
+        let (mid, _) = self.mid_banks();
+        let masked = (self.rom_bank_mask << 1) & 0x1E;
+        match address {
+            0x0000..=0x3FFF => {
+                let mid = match self.multiplex && self.mode == 0 {
+                    true => 0,
+                    false => mid,
+                };
+                self.mask_bank(self.rom_bank_high << 7 | mid << 5 | (self.rom_bank_low & masked))
+            }
+            _ => {
+                // the zero check only sees the bits the game can change
+                let low = match self.rom_bank_low & !masked {
+                    0 => self.rom_bank_low | 1,
+                    _ => self.rom_bank_low,
+                };
+                self.mask_bank(self.rom_bank_high << 7 | mid << 5 | low)
+            }
+        }
+    }
+
+    fn ram_bank(&self) -> usize {
+        let (_, low) = self.mid_banks();
+        let low = match self.multiplex && self.mode == 0 {
+            true => 0,
+            false => low,
+        };
+        (self.ram_bank_high << 2 | low) % self.ram_banks.max(1)
+    }
+
+    fn read(&self, address: usize) -> u8 {
+        let offset = address % ROM_BANK_SIZE;
+        self.rom[utils::rom_absolute_address(self.rom_bank(address), offset)]
+    }
+
+    fn read_sram(&self, address: usize) -> u8 {
+        if !self.ram_enabled || self.ram_banks == 0 {
+            return 0xFF;
+        }
+
+        self.sram
+            .get(utils::ram_absolute_address(self.ram_bank(), address))
+            .copied()
+            .unwrap_or(0xFF)
+    }
+
+    fn write(&mut self, address: usize, value: u8) {
+        log::trace!("Writing to ROM: {:04X}={:02X}", address, value);
+        let value = value as usize;
+        match address {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = (value & 0x0F) == 0x0A;
+                if !self.mapped {
+                    self.ram_bank_mask = (value >> 4) & 0x03;
+                    self.mapped = value & 0x40 != 0;
+                    if self.mapped {
+                        log::debug!("MMM01 mapped the game at bank {:03X}", self.rom_bank(0x0000));
+                    }
+                }
+            }
+            0x2000..=0x3FFF => {
+                if !self.mapped {
+                    self.rom_bank_mid = (value >> 5) & 0x03;
+                }
+                let masked = (self.rom_bank_mask << 1) & 0x1E;
+                self.rom_bank_low = (self.rom_bank_low & masked) | (value & 0x1F & !masked);
+            }
+            0x4000..=0x5FFF => {
+                self.ram_bank_low = (self.ram_bank_low & self.ram_bank_mask) | (value & 0x03 & !self.ram_bank_mask);
+                if !self.mapped {
+                    self.ram_bank_high = (value >> 2) & 0x03;
+                    self.rom_bank_high = (value >> 4) & 0x03;
+                    self.mode_locked = value & 0x40 != 0;
+                }
+            }
+            0x6000..=0x7FFF => {
+                if !self.mode_locked {
+                    self.mode = (value & 0x01) as u8;
+                }
+                if !self.mapped {
+                    self.rom_bank_mask = (value >> 2) & 0x0F;
+                    self.multiplex = value & 0x40 != 0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn write_sram(&mut self, address: usize, value: u8) {
+        if !self.ram_enabled || self.ram_banks == 0 {
+            return;
+        }
+
+        if let Some(byte) = self.sram.get_mut(utils::ram_absolute_address(self.ram_bank(), address)) {
+            *byte = value;
+        }
+    }
+}
+
+// IR receiver reading while no light is seen, bit 0 is set while it is
+const IR_DARK: u8 = 0xC0;
+
+// HuC1 is MBC1-like, with an infrared LED and receiver that replace RAM at
+// 0xA000..=0xBFFF while 0x0E is written to 0x0000..=0x1FFF. RAM needs no enable.
+pub struct HuC1 {
+    pub rom: Box<[u8]>,
+    pub sram: Box<[u8]>,
+    rom_banks: usize,
+    ram_banks: usize,
+    rom_bank_select: usize,
+    ram_bank_select: usize,
+    ir_mode: bool,
+    ir_led: bool,
+    ir_light: bool,
+}
+
+impl HuC1 {
+    pub fn new(rom_banks: usize, ram_banks: usize) -> HuC1 {
+        let rom_banks = rom_banks.clamp(2, ROM_MAX_BANKS_HUC1);
+
+        HuC1 {
+            rom: vec![0; rom_banks.next_power_of_two() * ROM_BANK_SIZE].into_boxed_slice(),
+            sram: vec![0; RAM_BANK_SIZE * RAM_MAX_BANKS_HUC1].into_boxed_slice(),
+            rom_banks,
+            ram_banks: ram_banks.min(RAM_MAX_BANKS_HUC1),
+            rom_bank_select: 1,
+            ram_bank_select: 0,
+            ir_mode: false,
+            ir_led: false,
+            ir_light: false,
+        }
+    }
+
+    pub fn ir_led(&self) -> bool {
+        self.ir_led
+    }
+
+    // light seen by the IR receiver, e.g. from a linked cart's LED
+    pub fn set_ir_light(&mut self, light: bool) {
+        self.ir_light = light;
+    }
+}
+
+impl IntoMBC for HuC1 {
+    fn rom_banks(&self) -> usize {
+        self.rom_banks
+    }
+
+    fn ram_banks(&self) -> usize {
+        self.ram_banks
+    }
+
+    fn rom_bank(&self, address: usize) -> usize {
+        match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank_select & (self.rom_banks.next_power_of_two() - 1),
+        }
+    }
+
+    fn ram_bank(&self) -> usize {
+        self.ram_bank_select % self.ram_banks.max(1)
+    }
+
+    fn read(&self, address: usize) -> u8 {
+        let offset = address % ROM_BANK_SIZE;
+        self.rom[utils::rom_absolute_address(self.rom_bank(address), offset)]
+    }
+
+    fn read_sram(&self, address: usize) -> u8 {
+        if self.ir_mode {
+            return IR_DARK | self.ir_light as u8;
+        }
+        if self.ram_banks == 0 {
+            return 0xFF;
+        }
+
+        self.sram
+            .get(utils::ram_absolute_address(self.ram_bank(), address))
+            .copied()
+            .unwrap_or(0xFF)
+    }
+
+    fn write(&mut self, address: usize, value: u8) {
+        log::trace!("Writing to ROM: {:04X}={:02X}", address, value);
+        match address {
+            0x0000..=0x1FFF => {
+                self.ir_mode = value == 0x0E;
+            }
+            0x2000..=0x3FFF => {
+                self.rom_bank_select = match value & 0x3F {
+                    0 => 1,
+                    bank => bank as usize,
+                };
+            }
+            0x4000..=0x5FFF => {
+                self.ram_bank_select = (value & 0x03) as usize;
+            }
+            _ => {}
+        }
+    }
+
+    fn write_sram(&mut self, address: usize, value: u8) {
+        if self.ir_mode {
+            self.ir_led = value & 0x01 != 0;
+            return;
+        }
+        if self.ram_banks == 0 {
+            return;
+        }
+
+        if let Some(byte) = self.sram.get_mut(utils::ram_absolute_address(self.ram_bank(), address)) {
+            *byte = value;
+        }
+    }
+}
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+// HuC3 registers holding the clock, as 3 nibbles of minutes and 4 of days
+const HUC3_TIME: usize = 0x00;
+const HUC3_ALARM: usize = 0x58;
+const HUC3_ALARM_ENABLE: usize = 0x5F;
+// tone played by the piezo speaker, and bit 0 starts it
+const HUC3_TONE: usize = 0x26;
+const HUC3_TONE_ENABLE: usize = 0x27;
+
+// size of the clock state saved after RAM, as laid out by SameBoy
+pub const HUC3_RTC_SAVE_SIZE: usize = 17;
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0)
+}
+
+// HuC3 maps one of several devices at 0xA000..=0xBFFF, picked by the mode
+// written to 0x0000..=0x1FFF: RAM (0x0/0xA), a command port (0xB), its
+// response (0xC), a ready flag (0xD) and the IR port (0xE).
+//
+// Commands are a nibble of opcode and one of data, working on 256 nibble
+// registers: 0x1 reads and 0x3 writes with an address increment, 0x2 writes,
+// 0x4/0x5 set the low/high address nibble. Opcode 0x6 executes: 0x0 latches
+// the clock into registers 0x00-0x06, 0x1 sets it from them and 0x2 asks
+// for a status that reads back as 1.
+pub struct HuC3 {
+    pub rom: Box<[u8]>,
+    pub sram: Box<[u8]>,
+    rom_banks: usize,
+    ram_banks: usize,
+    rom_bank_select: usize,
+    ram_bank_select: usize,
+    mode: u8,
+    registers: Box<[u8]>,
+    address: u8,
+    response: u8,
+    minutes: u32,
+    days: u32,
+    // unix time the clock was last brought up to date
+    last_second: u64,
+    ir_led: bool,
+    ir_light: bool,
+}
+
+impl HuC3 {
+    pub fn new(rom_banks: usize, ram_banks: usize) -> HuC3 {
+        let rom_banks = rom_banks.clamp(2, ROM_MAX_BANKS_HUC3);
+
+        HuC3 {
+            rom: vec![0; rom_banks.next_power_of_two() * ROM_BANK_SIZE].into_boxed_slice(),
+            sram: vec![0; RAM_BANK_SIZE * RAM_MAX_BANKS_HUC3].into_boxed_slice(),
+            rom_banks,
+            ram_banks: ram_banks.min(RAM_MAX_BANKS_HUC3),
+            rom_bank_select: 1,
+            ram_bank_select: 0,
+            mode: 0,
+            registers: vec![0; 0x100].into_boxed_slice(),
+            address: 0,
+            response: 0,
+            minutes: 0,
+            days: 0,
+            last_second: unix_time(),
+            ir_led: false,
+            ir_light: false,
+        }
+    }
+
+    pub fn ir_led(&self) -> bool {
+        self.ir_led
+    }
+
+    pub fn set_ir_light(&mut self, light: bool) {
+        self.ir_light = light;
+    }
+
+    // tone the speaker is playing
+    pub fn tone(&self) -> Option<u8> {
+        (self.registers[HUC3_TONE_ENABLE] & 0x01 != 0).then_some(self.registers[HUC3_TONE])
+    }
+
+    // minutes into the day and days
+    pub fn clock(&self) -> (u32, u32) {
+        let (_, minutes, days) = self.current_clock();
+        (minutes, days)
+    }
+
+    // last update, minutes and days with the time elapsed since counted in
+    fn current_clock(&self) -> (u64, u32, u32) {
+        let elapsed = unix_time().saturating_sub(self.last_second) / 60;
+        let minutes = self.minutes as u64 + elapsed;
+        (
+            self.last_second + elapsed * 60,
+            (minutes % MINUTES_PER_DAY as u64) as u32,
+            (self.days as u64 + minutes / MINUTES_PER_DAY as u64) as u32 & 0xFFFF,
+        )
+    }
+
+    fn update_clock(&mut self) {
+        (self.last_second, self.minutes, self.days) = self.current_clock();
+    }
+
+    fn nibbles(&self, start: usize, count: usize) -> u32 {
+        (0..count).fold(0, |value, i| value | ((self.registers[start + i] & 0x0F) as u32) << (i * 4))
+    }
+
+    fn set_nibbles(&mut self, start: usize, count: usize, value: u32) {
+        for i in 0..count {
+            self.registers[start + i] = ((value >> (i * 4)) & 0x0F) as u8;
+        }
+    }
+
+    fn command(&mut self, value: u8) {
+        let data = value & 0x0F;
+        let address = self.address as usize;
+        match value >> 4 {
+            0x1 => {
+                self.response = self.registers[address];
+                self.address = self.address.wrapping_add(1);
+            }
+            0x2 => self.registers[address] = data,
+            0x3 => {
+                self.registers[address] = data;
+                self.address = self.address.wrapping_add(1);
+            }
+            0x4 => self.address = (self.address & 0xF0) | data,
+            0x5 => self.address = (self.address & 0x0F) | data << 4,
+            0x6 => match data {
+                0x0 => {
+                    self.update_clock();
+                    self.set_nibbles(HUC3_TIME, 3, self.minutes);
+                    self.set_nibbles(HUC3_TIME + 3, 4, self.days);
+                }
+                0x1 => {
+                    self.minutes = self.nibbles(HUC3_TIME, 3) % MINUTES_PER_DAY;
+                    self.days = self.nibbles(HUC3_TIME + 3, 4);
+                    self.last_second = unix_time();
+                }
+                0x2 => self.response = 0x01,
+                _ => log::debug!("Unsupported HuC3 command: {:02X}", value),
+            },
+            _ => log::debug!("Unsupported HuC3 command: {:02X}", value),
+        }
+    }
+
+    // clock state saved after RAM: last update, minutes, days, alarm minutes,
+    // alarm days and alarm enable, little-endian
+    pub fn rtc_save_data(&self) -> Vec<u8> {
+        let (last_second, minutes, days) = self.current_clock();
+        let mut data = Vec::with_capacity(HUC3_RTC_SAVE_SIZE);
+        data.extend(last_second.to_le_bytes());
+        data.extend((minutes as u16).to_le_bytes());
+        data.extend((days as u16).to_le_bytes());
+        data.extend((self.nibbles(HUC3_ALARM, 3) as u16).to_le_bytes());
+        data.extend((self.nibbles(HUC3_ALARM + 3, 4) as u16).to_le_bytes());
+        data.push(self.registers[HUC3_ALARM_ENABLE] & 0x01);
+        data
+    }
+
+    // the clock keeps running from the saved time
+    pub fn load_rtc_save_data(&mut self, data: &[u8]) -> crate::Result<()> {
+        if data.len() < HUC3_RTC_SAVE_SIZE {
+            return Err(Error::SizeMismatch {
+                expected: HUC3_RTC_SAVE_SIZE,
+                actual: data.len(),
+            });
+        }
+        let word = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]) as u32;
+        self.last_second = u64::from_le_bytes(data[..8].try_into().unwrap());
+        self.minutes = word(8) % MINUTES_PER_DAY;
+        self.days = word(10);
+        self.set_nibbles(HUC3_ALARM, 3, word(12));
+        self.set_nibbles(HUC3_ALARM + 3, 4, word(14));
+        self.registers[HUC3_ALARM_ENABLE] = data[16] & 0x01;
+        self.update_clock();
+        Ok(())
+    }
+}
+
+impl IntoMBC for HuC3 {
+    fn rom_banks(&self) -> usize {
+        self.rom_banks
+    }
+
+    fn ram_banks(&self) -> usize {
+        self.ram_banks
+    }
+
+    fn rom_bank(&self, address: usize) -> usize {
+        match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank_select & (self.rom_banks.next_power_of_two() - 1),
+        }
+    }
+
+    fn ram_bank(&self) -> usize {
+        self.ram_bank_select % self.ram_banks.max(1)
+    }
+
+    fn read(&self, address: usize) -> u8 {
+        let offset = address % ROM_BANK_SIZE;
+        self.rom[utils::rom_absolute_address(self.rom_bank(address), offset)]
+    }
+
+    fn read_sram(&self, address: usize) -> u8 {
+        match self.mode {
+            0x0 | 0xA if self.ram_banks > 0 => self
+                .sram
+                .get(utils::ram_absolute_address(self.ram_bank(), address))
+                .copied()
+                .unwrap_or(0xFF),
+            0xC => self.response,
+            // commands complete immediately
+            0xD => 0x01,
+            0xE => IR_DARK | self.ir_light as u8,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, address: usize, value: u8) {
+        log::trace!("Writing to ROM: {:04X}={:02X}", address, value);
+        match address {
+            0x0000..=0x1FFF => {
+                self.mode = value & 0x0F;
+            }
+            0x2000..=0x3FFF => {
+                self.rom_bank_select = (value & 0x7F) as usize;
+            }
+            0x4000..=0x5FFF => {
+                self.ram_bank_select = (value & 0x0F) as usize;
+            }
+            _ => {}
         }
     }
 
     fn write_sram(&mut self, address: usize, value: u8) {
+        match self.mode {
+            0x0 | 0xA if self.ram_banks > 0 => {
+                if let Some(byte) = self.sram.get_mut(utils::ram_absolute_address(self.ram_bank(), address)) {
+                    *byte = value;
+                }
+            }
+            0xB => self.command(value),
+            0xE => self.ir_led = value & 0x01 != 0,
+            _ => {}
+        }
+    }
+}
+
+// accelerometer reading when level, and the change for 1g of tilt
+const ACCELEROMETER_CENTER: f32 = 0x81D0 as f32;
+const ACCELEROMETER_G: f32 = 0x70 as f32;
+// reading after an erase, until the next latch
+const ACCELEROMETER_ERASED: u16 = 0x8000;
+
+pub const MBC7_EEPROM_SIZE: usize = 0x100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EepromState {
+    // waiting for a start bit
+    Idle,
+    // opcode and address bits after the start bit
+    Command { bits: u16, count: u8 },
+    // shifting out a word MSB first, then the words after it
+    Read { address: u8, word: u16, count: u8 },
+    // data bits for WRITE, or for WRAL when address is None
+    Write { address: Option<u8>, bits: u16, count: u8 },
+}
+
+// 93LC56 serial EEPROM organised as 128 16-bit words. Bits are clocked in on
+// DI and out on DO on the rising edge of CLK while CS is high. A command is a
+// start bit, 2 opcode bits and 8 address bits: 10 READ, 01 WRITE, 11 ERASE,
+// and 00 with the top address bits picking EWEN (11), EWDS (00), ERAL (10)
+// or WRAL (01). Programming needs EWEN and completes immediately.
+struct Eeprom {
+    cs: bool,
+    clk: bool,
+    di: bool,
+    do_: bool,
+    write_enabled: bool,
+    state: EepromState,
+}
+
+impl Eeprom {
+    fn new() -> Eeprom {
+        Eeprom {
+            cs: false,
+            clk: false,
+            di: false,
+            do_: true,
+            write_enabled: false,
+            state: EepromState::Idle,
+        }
+    }
+
+    fn word(data: &[u8], address: u8) -> u16 {
+        let offset = (address as usize & 0x7F) * 2;
+        u16::from_le_bytes([data[offset], data[offset + 1]])
+    }
+
+    fn program(&mut self, data: &mut [u8], address: Option<u8>, word: u16) -> EepromState {
+        if self.write_enabled {
+            match address {
+                Some(address) => {
+                    let offset = (address as usize & 0x7F) * 2;
+                    data[offset..offset + 2].copy_from_slice(&word.to_le_bytes());
+                }
+                None => data
+                    .chunks_mut(2)
+                    .for_each(|chunk| chunk.copy_from_slice(&word.to_le_bytes())),
+            }
+        }
+        self.do_ = true;
+        EepromState::Idle
+    }
+
+    // pins as seen at 0xA080: CS bit 7, CLK bit 6, DI bit 1 and DO bit 0
+    fn pins(&self) -> u8 {
+        (self.cs as u8) << 7 | (self.clk as u8) << 6 | (self.di as u8) << 1 | self.do_ as u8
+    }
+
+    fn set_pins(&mut self, data: &mut [u8], value: u8) {
+        let (cs, clk) = (value & 0x80 != 0, value & 0x40 != 0);
+        self.di = value & 0x02 != 0;
+        if !cs {
+            self.state = EepromState::Idle;
+        } else if self.cs && clk && !self.clk {
+            self.clock(data);
+        }
+        self.cs = cs;
+        self.clk = clk;
+    }
+
+    fn clock(&mut self, data: &mut [u8]) {
+        let di = self.di as u16;
+        self.state = match self.state {
+            EepromState::Idle if di == 1 => EepromState::Command { bits: 0, count: 0 },
+            EepromState::Idle => EepromState::Idle,
+            EepromState::Command { bits, count } if count < 9 => EepromState::Command {
+                bits: bits << 1 | di,
+                count: count + 1,
+            },
+            EepromState::Command { bits, .. } => self.command(data, bits << 1 | di),
+            EepromState::Read { address, word, count } => {
+                self.do_ = word & 0x8000 != 0;
+                if count == 15 {
+                    let address = (address + 1) & 0x7F;
+                    EepromState::Read {
+                        address,
+                        word: Eeprom::word(data, address),
+                        count: 0,
+                    }
+                } else {
+                    EepromState::Read {
+                        address,
+                        word: word << 1,
+                        count: count + 1,
+                    }
+                }
+            }
+            EepromState::Write { address, bits, count } if count < 15 => EepromState::Write {
+                address,
+                bits: bits << 1 | di,
+                count: count + 1,
+            },
+            EepromState::Write { address, bits, .. } => self.program(data, address, bits << 1 | di),
+        };
+    }
+
+    fn command(&mut self, data: &mut [u8], bits: u16) -> EepromState {
+        let address = bits as u8;
+        match bits >> 8 {
+            0b10 => {
+                // a dummy 0 comes before the data
+                self.do_ = false;
+                let address = address & 0x7F;
+                EepromState::Read {
+                    address,
+                    word: Eeprom::word(data, address),
+                    count: 0,
+                }
+            }
+            0b01 => EepromState::Write {
+                address: Some(address),
+                bits: 0,
+                count: 0,
+            },
+            0b11 => self.program(data, Some(address), 0xFFFF),
+            _ => match address >> 6 {
+                0b11 => {
+                    self.write_enabled = true;
+                    EepromState::Idle
+                }
+                0b00 => {
+                    self.write_enabled = false;
+                    EepromState::Idle
+                }
+                0b10 => self.program(data, None, 0xFFFF),
+                _ => EepromState::Write {
+                    address: None,
+                    bits: 0,
+                    count: 0,
+                },
+            },
+        }
+    }
+}
+
+// MBC7 has no RAM, instead a 2-axis accelerometer and an EEPROM are mapped at
+// 0xA000..=0xAFFF once 0x0A is written to 0x0000..=0x1FFF and 0x40 to
+// 0x4000..=0x5FFF. Bits 4-7 of the address pick the register: writing 0x55
+// to 0x0 then 0xAA to 0x1 latches the tilt, 0x2-0x5 read it back as
+// little-endian X and Y, and 0x8 is the EEPROM's pins.
+pub struct MBC7 {
+    pub rom: Box<[u8]>,
+    // EEPROM words, little-endian
+    pub eeprom: Box<[u8]>,
+    rom_banks: usize,
+    rom_bank_select: usize,
+    ram_enabled: bool,
+    registers_enabled: bool,
+    tilt: (f32, f32),
+    latch: (u16, u16),
+    latch_ready: bool,
+    eeprom_pins: Eeprom,
+}
+
+impl MBC7 {
+    pub fn new(rom_banks: usize) -> MBC7 {
+        let rom_banks = rom_banks.clamp(2, ROM_MAX_BANKS_MBC7);
+
+        MBC7 {
+            rom: vec![0; rom_banks.next_power_of_two() * ROM_BANK_SIZE].into_boxed_slice(),
+            eeprom: vec![0xFF; MBC7_EEPROM_SIZE].into_boxed_slice(),
+            rom_banks,
+            rom_bank_select: 1,
+            ram_enabled: false,
+            registers_enabled: false,
+            tilt: (0.0, 0.0),
+            latch: (ACCELEROMETER_ERASED, ACCELEROMETER_ERASED),
+            latch_ready: false,
+            eeprom_pins: Eeprom::new(),
+        }
+    }
+
+    // tilt in g along each axis, positive is right and down
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.tilt = (x, y);
+    }
+
+    fn accelerometer(tilt: f32) -> u16 {
+        (ACCELEROMETER_CENTER + tilt * ACCELEROMETER_G).clamp(0.0, u16::MAX as f32) as u16
+    }
+}
+
+impl IntoMBC for MBC7 {
+    fn rom_banks(&self) -> usize {
+        self.rom_banks
+    }
+
+    fn ram_banks(&self) -> usize {
+        0
+    }
+
+    fn rom_bank(&self, address: usize) -> usize {
         match address {
-            0xA000..=0xBFFF => {
-                if !self.ram_enabled {
-                    return;
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank_select & (self.rom_banks.next_power_of_two() - 1),
+        }
+    }
+
+    fn ram_bank(&self) -> usize {
+        0
+    }
+
+    fn read(&self, address: usize) -> u8 {
+        let offset = address % ROM_BANK_SIZE;
+        self.rom[utils::rom_absolute_address(self.rom_bank(address), offset)]
+    }
+
+    fn read_sram(&self, address: usize) -> u8 {
+        if !self.ram_enabled || !self.registers_enabled || address > 0x0FFF {
+            return 0xFF;
+        }
+
+        match (address >> 4) & 0x0F {
+            0x2 => self.latch.0 as u8,
+            0x3 => (self.latch.0 >> 8) as u8,
+            0x4 => self.latch.1 as u8,
+            0x5 => (self.latch.1 >> 8) as u8,
+            0x6 => 0x00,
+            0x8 => self.eeprom_pins.pins(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, address: usize, value: u8) {
+        log::trace!("Writing to ROM: {:04X}={:02X}", address, value);
+        match address {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = value == 0x0A;
+            }
+            0x2000..=0x3FFF => {
+                self.rom_bank_select = (value & 0x7F) as usize;
+            }
+            0x4000..=0x5FFF => {
+                self.registers_enabled = value == 0x40;
+            }
+            _ => {}
+        }
+    }
+
+    fn write_sram(&mut self, address: usize, value: u8) {
+        if !self.ram_enabled || !self.registers_enabled || address > 0x0FFF {
+            return;
+        }
+
+        match (address >> 4) & 0x0F {
+            0x0 if value == 0x55 => {
+                self.latch = (ACCELEROMETER_ERASED, ACCELEROMETER_ERASED);
+                self.latch_ready = true;
+            }
+            0x1 if value == 0xAA && self.latch_ready => {
+                self.latch = (MBC7::accelerometer(self.tilt.0), MBC7::accelerometer(self.tilt.1));
+                self.latch_ready = false;
+            }
+            0x8 => self.eeprom_pins.set_pins(&mut self.eeprom, value),
+            _ => {}
+        }
+    }
+}
+
+// M64282FP sensor resolution, as used by the Pocket Camera
+pub const CAMERA_WIDTH: usize = 128;
+pub const CAMERA_HEIGHT: usize = 112;
+
+// captures are written as 16x14 tiles to RAM bank 0 from 0xA100
+const CAMERA_IMAGE: usize = 0x0100;
+const CAMERA_REGISTERS: usize = 0x36;
+// 4x4 matrix of 3 thresholds each, from 0xA006
+const CAMERA_DITHER_MATRIX: usize = 0x06;
+
+// relative gain for each G setting, unity at 4
+const CAMERA_GAIN: [f32; 32] = [
+    0.881, 0.915, 0.946, 0.974, 1.000, 1.024, 1.047, 1.068, 1.088, 1.124, 1.157, 1.187, 1.214, 1.240, 1.274,
+    1.316, 1.355, 1.392, 1.427, 1.460, 1.492, 1.523, 1.552, 1.580, 1.607, 1.633, 1.658, 1.682, 1.705, 1.727,
+    1.748, 1.769,
+];
+// edge enhancement ratio for each E setting
+const CAMERA_EDGE_RATIO: [f32; 8] = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0];
+// exposure time giving the image as is at unity gain
+const CAMERA_UNITY_EXPOSURE: f32 = 0x1000 as f32;
+
+// The Pocket Camera maps up to 64 ROM banks, with bank 0 selectable at
+// 0x4000, and 16 RAM banks that can always be read but need 0x0A written to
+// 0x0000..=0x1FFF to be written. Setting bit 4 of the RAM bank maps the
+// sensor's registers instead, mirrored every 0x80 bytes:
+//
+// 0x00 bit 0 starts a capture and reads back set while busy, the only
+//      readable register, the others read 0x00
+// 0x01 bit 7 exclusive edge mode, 6-5 edge direction (VH), 4-0 gain
+// 0x02-0x03 exposure time, big-endian
+// 0x04 bits 6-4 edge enhancement ratio, 3 invert, 2-0 output voltage
+// 0x05 zero point and output reference voltage
+// 0x06-0x35 dithering thresholds
+//
+// The sensor sees a caller-provided 128x112 grayscale image, 0 being black.
+// Captures complete immediately and ignore the analog voltage settings.
+pub struct PocketCamera {
+    pub rom: Box<[u8]>,
+    pub sram: Box<[u8]>,
+    rom_banks: usize,
+    ram_banks: usize,
+    rom_bank_select: usize,
+    ram_bank_select: usize,
+    ram_enabled: bool,
+    registers_mapped: bool,
+    registers: [u8; CAMERA_REGISTERS],
+    image: Box<[u8]>,
+}
+
+impl PocketCamera {
+    pub fn new(rom_banks: usize, ram_banks: usize) -> PocketCamera {
+        let rom_banks = rom_banks.clamp(2, ROM_MAX_BANKS_CAMERA);
+
+        PocketCamera {
+            rom: vec![0; rom_banks.next_power_of_two() * ROM_BANK_SIZE].into_boxed_slice(),
+            sram: vec![0; RAM_BANK_SIZE * RAM_MAX_BANKS_CAMERA].into_boxed_slice(),
+            rom_banks,
+            ram_banks: ram_banks.min(RAM_MAX_BANKS_CAMERA),
+            rom_bank_select: 1,
+            ram_bank_select: 0,
+            ram_enabled: false,
+            registers_mapped: false,
+            registers: [0; CAMERA_REGISTERS],
+            image: vec![0; CAMERA_WIDTH * CAMERA_HEIGHT].into_boxed_slice(),
+        }
+    }
+
+    // CAMERA_WIDTH x CAMERA_HEIGHT grayscale pixels, row by row
+    pub fn set_image(&mut self, image: &[u8]) -> crate::Result<()> {
+        if image.len() != self.image.len() {
+            return Err(Error::SizeMismatch {
+                expected: self.image.len(),
+                actual: image.len(),
+            });
+        }
+        self.image.copy_from_slice(image);
+        Ok(())
+    }
+
+    // sensor output after gain and exposure, pixels past the edges repeat
+    fn exposed(&self, x: isize, y: isize) -> f32 {
+        let x = x.clamp(0, CAMERA_WIDTH as isize - 1) as usize;
+        let y = y.clamp(0, CAMERA_HEIGHT as isize - 1) as usize;
+        let exposure = u16::from_be_bytes([self.registers[2], self.registers[3]]) as f32;
+        self.image[y * CAMERA_WIDTH + x] as f32 * CAMERA_GAIN[(self.registers[1] & 0x1F) as usize] * exposure
+            / CAMERA_UNITY_EXPOSURE
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> u8 {
+        let (x, y) = (x as isize, y as isize);
+        let mut value = self.exposed(x, y);
+
+        // subtracts the neighbours along the enabled directions
+        let ratio = CAMERA_EDGE_RATIO[(self.registers[4] >> 4 & 0x07) as usize];
+        if self.registers[1] & 0x20 != 0 {
+            value += (2.0 * value - self.exposed(x - 1, y) - self.exposed(x + 1, y)) * ratio;
+        }
+        if self.registers[1] & 0x40 != 0 {
+            value += (2.0 * value - self.exposed(x, y - 1) - self.exposed(x, y + 1)) * ratio;
+        }
+
+        let value = value.clamp(0.0, 255.0) as u8;
+        let value = match self.registers[4] & 0x08 {
+            0 => value,
+            _ => !value,
+        };
+
+        let matrix = CAMERA_DITHER_MATRIX + ((y as usize & 3) * 4 + (x as usize & 3)) * 3;
+        match &self.registers[matrix..matrix + 3] {
+            [low, ..] if value < *low => 3,
+            [_, mid, _] if value < *mid => 2,
+            [_, _, high] if value < *high => 1,
+            _ => 0,
+        }
+    }
+
+    // writes the dithered image as 2bpp tiles
+    fn capture(&mut self) {
+        for y in 0..CAMERA_HEIGHT {
+            for x in 0..CAMERA_WIDTH {
+                let color = self.pixel(x, y);
+                let tile = (y / 8) * (CAMERA_WIDTH / 8) + x / 8;
+                let offset = CAMERA_IMAGE + tile * 16 + (y % 8) * 2;
+                let bit = 0x80 >> (x % 8);
+                for (plane, byte) in self.sram[offset..offset + 2].iter_mut().enumerate() {
+                    match color >> plane & 1 {
+                        0 => *byte &= !bit,
+                        _ => *byte |= bit,
+                    }
                 }
+            }
+        }
+    }
+}
+
+impl IntoMBC for PocketCamera {
+    fn rom_banks(&self) -> usize {
+        self.rom_banks
+    }
+
+    fn ram_banks(&self) -> usize {
+        self.ram_banks
+    }
+
+    fn rom_bank(&self, address: usize) -> usize {
+        match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank_select & (self.rom_banks.next_power_of_two() - 1),
+        }
+    }
 
-                let mut bank = 0;
-                if self.mode == 1 {
-                    bank = self.ram_bank_select;
+    fn ram_bank(&self) -> usize {
+        self.ram_bank_select % self.ram_banks.max(1)
+    }
+
+    fn read(&self, address: usize) -> u8 {
+        let offset = address % ROM_BANK_SIZE;
+        self.rom[utils::rom_absolute_address(self.rom_bank(address), offset)]
+    }
+
+    fn read_sram(&self, address: usize) -> u8 {
+        if self.registers_mapped {
+            return match address & 0x7F {
+                0x00 => self.registers[0],
+                _ => 0x00,
+            };
+        }
+        if self.ram_banks == 0 {
+            return 0xFF;
+        }
+
+        self.sram
+            .get(utils::ram_absolute_address(self.ram_bank(), address))
+            .copied()
+            .unwrap_or(0xFF)
+    }
+
+    fn write(&mut self, address: usize, value: u8) {
+        log::trace!("Writing to ROM: {:04X}={:02X}", address, value);
+        match address {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = value & 0x0F == 0x0A;
+            }
+            0x2000..=0x3FFF => {
+                self.rom_bank_select = (value & 0x3F) as usize;
+            }
+            0x4000..=0x5FFF => {
+                self.registers_mapped = value & 0x10 != 0;
+                self.ram_bank_select = (value & 0x0F) as usize;
+            }
+            _ => {}
+        }
+    }
+
+    fn write_sram(&mut self, address: usize, value: u8) {
+        if self.registers_mapped {
+            match address & 0x7F {
+                0x00 => {
+                    self.registers[0] = value & 0x07;
+                    if value & 0x01 != 0 {
+                        self.capture();
+                        self.registers[0] &= !0x01;
+                    }
                 }
-                log::trace!("Writing to SRAM: {:04X}={:02X}", address, value);
-                self.sram[utils::ram_absolute_address(bank, address - 0xA000) % self.ram_banks] =
-                    value;
-            }
-            _ => panic!(
-                "Invalid SRAM address for MBC1 write: {:04X}={:02X}",
-                address, value
-            ),
+                register if register < CAMERA_REGISTERS => self.registers[register] = value,
+                _ => {}
+            }
+            return;
+        }
+        if !self.ram_enabled || self.ram_banks == 0 {
+            return;
+        }
+
+        if let Some(byte) = self.sram.get_mut(utils::ram_absolute_address(self.ram_bank(), address)) {
+            *byte = value;
         }
     }
 }
@@ -0,0 +1,71 @@
+// DMG OAM corruption bug.
+//
+// While the PPU scans OAM it reads one 8-byte row per M-cycle. When the CPU's
+// 16-bit increment/decrement unit puts an address in $FE00-$FEFF on the bus at
+// the same time (INC rr, DEC rr, PUSH, POP), the row being scanned gets mixed
+// with the one before it. OAM is handled as 20 rows of four little-endian
+// words; row 0 is never corrupted. CGB models are not affected.
+
+pub const OAM_ROWS: usize = 20;
+pub const OAM_ROW_SIZE: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corruption {
+    Write,
+    Read,
+    // a read and an increment in the same M-cycle, as done by POP
+    ReadIncrease,
+}
+
+fn word(oam: &[u8], row: usize, index: usize) -> u16 {
+    let offset = row * OAM_ROW_SIZE + index * 2;
+    u16::from_le_bytes([oam[offset], oam[offset + 1]])
+}
+
+fn set_word(oam: &mut [u8], row: usize, index: usize, value: u16) {
+    let offset = row * OAM_ROW_SIZE + index * 2;
+    oam[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+// copies the last three words of the preceding row into row
+fn copy_tail(oam: &mut [u8], row: usize) {
+    let start = row * OAM_ROW_SIZE;
+    oam.copy_within(start - 6..start, start + 2);
+}
+
+fn copy_row(oam: &mut [u8], from: usize, to: usize) {
+    oam.copy_within(from * OAM_ROW_SIZE..(from + 1) * OAM_ROW_SIZE, to * OAM_ROW_SIZE);
+}
+
+// corrupts the 160 bytes of OAM while row is being scanned
+pub fn corrupt(oam: &mut [u8], row: usize, corruption: Corruption) {
+    if row == 0 || row >= OAM_ROWS {
+        return;
+    }
+
+    match corruption {
+        Corruption::Write => {
+            let (a, b, c) = (word(oam, row, 0), word(oam, row - 1, 0), word(oam, row - 1, 2));
+            set_word(oam, row, 0, ((a ^ c) & (b ^ c)) ^ c);
+            copy_tail(oam, row);
+        }
+        Corruption::Read => {
+            let (a, b, c) = (word(oam, row, 0), word(oam, row - 1, 0), word(oam, row - 1, 2));
+            set_word(oam, row, 0, b | (a & c));
+            copy_tail(oam, row);
+        }
+        Corruption::ReadIncrease => {
+            // the first four rows and the last one only get the read corruption
+            if (4..OAM_ROWS - 1).contains(&row) {
+                let a = word(oam, row - 2, 0);
+                let b = word(oam, row - 1, 0);
+                let c = word(oam, row, 0);
+                let d = word(oam, row - 1, 2);
+                set_word(oam, row - 1, 0, (b & (a | c | d)) | (a & c & d));
+                copy_row(oam, row - 1, row);
+                copy_row(oam, row - 1, row - 2);
+            }
+            corrupt(oam, row, Corruption::Read);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{bits::*, gameboy::Gameboy, globals::*};
+use crate::{bits::*, gameboy::Gameboy, globals::*, oam_bug::Corruption};
 
 pub fn init_opcodes() -> OpCodeMap {
     phf::phf_map! {
@@ -28,6 +28,7 @@ pub fn init_opcodes() -> OpCodeMap {
         // INC BC
         0x03u8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
             let value = (gb.cpu.b as u16) << 8 | gb.cpu.c as u16;
+            gb.trigger_oam_bug(value, Corruption::Write);
             let result = value.wrapping_add(1);
             gb.cpu.b = (result >> 8) as u8;
             gb.cpu.c = result as u8;
@@ -116,6 +117,7 @@ pub fn init_opcodes() -> OpCodeMap {
         // DEC BC
         0x0Bu8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
             let value = (gb.cpu.b as u16) << 8 | gb.cpu.c as u16;
+            gb.trigger_oam_bug(value, Corruption::Write);
             let result = value.wrapping_sub(1);
             gb.cpu.b = (result >> 8) as u8;
             gb.cpu.c = result as u8;
@@ -223,6 +225,7 @@ pub fn init_opcodes() -> OpCodeMap {
         // INC DE
         0x13u8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
             let value = (gb.cpu.d as u16) << 8 | gb.cpu.e as u16;
+            gb.trigger_oam_bug(value, Corruption::Write);
             let result = value.wrapping_add(1);
             gb.cpu.d = (result >> 8) as u8;
             gb.cpu.e = result as u8;
@@ -310,6 +313,7 @@ pub fn init_opcodes() -> OpCodeMap {
         // DEC DE
         0x1Bu8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
             let value = (gb.cpu.d as u16) << 8 | gb.cpu.e as u16;
+            gb.trigger_oam_bug(value, Corruption::Write);
             let result = value.wrapping_sub(1);
             gb.cpu.d = (result >> 8) as u8;
             gb.cpu.e = result as u8;
@@ -379,6 +383,7 @@ pub fn init_opcodes() -> OpCodeMap {
         // LD (HL+), A
         0x22u8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
             let addr = ((gb.cpu.h as u16) << 8) | gb.cpu.l as u16;
+            gb.trigger_oam_bug(addr, Corruption::Write);
             gb.memory_write(addr, gb.cpu.a);
             let result = addr.wrapping_add(1);
             gb.cpu.h = (result >> 8) as u8;
@@ -390,6 +395,7 @@ pub fn init_opcodes() -> OpCodeMap {
         // INC HL
         0x23u8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
             let value = (gb.cpu.h as u16) << 8 | gb.cpu.l as u16;
+            gb.trigger_oam_bug(value, Corruption::Write);
             let result = value.wrapping_add(1);
             gb.cpu.h = (result >> 8) as u8;
             gb.cpu.l = result as u8;
@@ -494,6 +500,7 @@ pub fn init_opcodes() -> OpCodeMap {
         // LD A, (HL+)
         0x2Au8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
             let addr = ((gb.cpu.h as u16) << 8) | gb.cpu.l as u16;
+            gb.trigger_oam_bug(addr, Corruption::ReadIncrease);
             gb.cpu.a = gb.memory_read(addr);
             let result = addr.wrapping_add(1);
             gb.cpu.h = (result >> 8) as u8;
@@ -505,6 +512,7 @@ pub fn init_opcodes() -> OpCodeMap {
         // DEC HL
         0x2Bu8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
             let value = (gb.cpu.h as u16) << 8 | gb.cpu.l as u16;
+            gb.trigger_oam_bug(value, Corruption::Write);
             let result = value.wrapping_sub(1);
             gb.cpu.h = (result >> 8) as u8;
             gb.cpu.l = result as u8;
@@ -561,6 +569,7 @@ pub fn init_opcodes() -> OpCodeMap {
         // LD (HL-), A
         0x32u8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
             let addr = ((gb.cpu.h as u16) << 8) | gb.cpu.l as u16;
+            gb.trigger_oam_bug(addr, Corruption::Write);
             gb.memory_write(addr, gb.cpu.a);
             let result = addr.wrapping_sub(1);
             gb.cpu.h = (result >> 8) as u8;
@@ -571,6 +580,7 @@ pub fn init_opcodes() -> OpCodeMap {
 
         // INC SP
         0x33u8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
+            gb.trigger_oam_bug(gb.cpu.sp, Corruption::Write);
             gb.cpu.sp = gb.cpu.sp.wrapping_add(1);
             gb.cpu.pc = gb.cpu.pc.wrapping_add(1);
             CYCLE_RETURN_8
@@ -673,6 +683,7 @@ pub fn init_opcodes() -> OpCodeMap {
         // LD A, (HL-)
         0x3Au8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
             let addr = ((gb.cpu.h as u16) << 8) | gb.cpu.l as u16;
+            gb.trigger_oam_bug(addr, Corruption::ReadIncrease);
             gb.cpu.a = gb.memory_read(addr);
             let result = addr.wrapping_sub(1);
             gb.cpu.h = (result >> 8) as u8;
@@ -683,6 +694,7 @@ pub fn init_opcodes() -> OpCodeMap {
 
         // DEC SP
         0x3Bu8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
+            gb.trigger_oam_bug(gb.cpu.sp, Corruption::Write);
             gb.cpu.sp = gb.cpu.sp.wrapping_sub(1);
             gb.cpu.pc = gb.cpu.pc.wrapping_add(1);
             CYCLE_RETURN_8
@@ -1592,8 +1604,10 @@ pub fn init_opcodes() -> OpCodeMap {
 
         // POP BC
         0xC1u8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
+            gb.trigger_oam_bug(gb.cpu.sp, Corruption::ReadIncrease);
             let lo = gb.memory_read(gb.cpu.sp);
             gb.cpu.sp = gb.cpu.sp.wrapping_add(1);
+            gb.trigger_oam_bug(gb.cpu.sp, Corruption::Read);
             let hi = gb.memory_read(gb.cpu.sp);
             gb.cpu.sp = gb.cpu.sp.wrapping_add(1);
             gb.cpu.b = hi;
@@ -1643,6 +1657,9 @@ pub fn init_opcodes() -> OpCodeMap {
         0xC5u8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
             let sp1 = gb.cpu.sp.wrapping_sub(1);
             let sp2 = gb.cpu.sp.wrapping_sub(2);
+            gb.trigger_oam_bug(gb.cpu.sp, Corruption::Write);
+            gb.trigger_oam_bug(sp1, Corruption::Write);
+            gb.trigger_oam_bug(sp2, Corruption::Write);
             gb.memory_write(sp1, gb.cpu.b);
             gb.memory_write(sp2, gb.cpu.c);
             gb.cpu.sp = sp2;
@@ -1707,14 +1724,7 @@ pub fn init_opcodes() -> OpCodeMap {
             }
         },
 
-        // PREFIX CB
-        0xCBu8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
-            // let opcode = gb.memory_read(gb.cpu.pc);
-            gb.cpu.pc = gb.cpu.pc.wrapping_add(1);
-            let cb_opcode = gb.memory_read(gb.cpu.pc);
-            gb.cpu.pc = gb.cpu.pc.wrapping_add(1);
-            gb.execute_op_code_cb(cb_opcode).expect("Failed to execute CB opcode")
-        },
+        // PREFIX CB is dispatched by Gameboy::execute_op_code
 
         // CALL Z, u16
         0xCCu8 => |gb: &mut Gameboy, value: u16| -> OpCycles {
@@ -1790,8 +1800,10 @@ pub fn init_opcodes() -> OpCodeMap {
 
         // POP DE
         0xD1u8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
+            gb.trigger_oam_bug(gb.cpu.sp, Corruption::ReadIncrease);
             let lo = gb.memory_read(gb.cpu.sp);
             gb.cpu.sp = gb.cpu.sp.wrapping_add(1);
+            gb.trigger_oam_bug(gb.cpu.sp, Corruption::Read);
             let hi = gb.memory_read(gb.cpu.sp);
             gb.cpu.sp = gb.cpu.sp.wrapping_add(1);
             gb.cpu.d = hi;
@@ -1835,6 +1847,9 @@ pub fn init_opcodes() -> OpCodeMap {
         0xD5u8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
             let sp1 = gb.cpu.sp.wrapping_sub(1);
             let sp2 = gb.cpu.sp.wrapping_sub(2);
+            gb.trigger_oam_bug(gb.cpu.sp, Corruption::Write);
+            gb.trigger_oam_bug(sp1, Corruption::Write);
+            gb.trigger_oam_bug(sp2, Corruption::Write);
             gb.memory_write(sp1, gb.cpu.d);
             gb.memory_write(sp2, gb.cpu.e);
             gb.cpu.sp = sp2;
@@ -1951,8 +1966,10 @@ pub fn init_opcodes() -> OpCodeMap {
 
         // POP HL
         0xE1u8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
+            gb.trigger_oam_bug(gb.cpu.sp, Corruption::ReadIncrease);
             let lo = gb.memory_read(gb.cpu.sp);
             gb.cpu.sp = gb.cpu.sp.wrapping_add(1);
+            gb.trigger_oam_bug(gb.cpu.sp, Corruption::Read);
             let hi = gb.memory_read(gb.cpu.sp);
             gb.cpu.sp = gb.cpu.sp.wrapping_add(1);
             gb.cpu.h = hi;
@@ -1973,6 +1990,9 @@ pub fn init_opcodes() -> OpCodeMap {
         0xE5u8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
             let sp1 = gb.cpu.sp.wrapping_sub(1);
             let sp2 = gb.cpu.sp.wrapping_sub(2);
+            gb.trigger_oam_bug(gb.cpu.sp, Corruption::Write);
+            gb.trigger_oam_bug(sp1, Corruption::Write);
+            gb.trigger_oam_bug(sp2, Corruption::Write);
             gb.memory_write(sp1, gb.cpu.h);
             gb.memory_write(sp2, gb.cpu.l);
             gb.cpu.sp = sp2;
@@ -2070,8 +2090,10 @@ pub fn init_opcodes() -> OpCodeMap {
 
         // POP AF
         0xF1u8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
+            gb.trigger_oam_bug(gb.cpu.sp, Corruption::ReadIncrease);
             gb.cpu.f = gb.memory_read(gb.cpu.sp) & 0xF0 & 0xF0;
             gb.cpu.sp = gb.cpu.sp.wrapping_add(1);
+            gb.trigger_oam_bug(gb.cpu.sp, Corruption::Read);
             gb.cpu.a = gb.memory_read(gb.cpu.sp) ;
             gb.cpu.sp = gb.cpu.sp.wrapping_add(1);
             gb.cpu.pc = gb.cpu.pc.wrapping_add(1);
@@ -2098,6 +2120,9 @@ pub fn init_opcodes() -> OpCodeMap {
         0xF5u8 => |gb: &mut Gameboy, _value: u16| -> OpCycles {
             let sp1 = gb.cpu.sp.wrapping_sub(1);
             let sp2 = gb.cpu.sp.wrapping_sub(2);
+            gb.trigger_oam_bug(gb.cpu.sp, Corruption::Write);
+            gb.trigger_oam_bug(sp1, Corruption::Write);
+            gb.trigger_oam_bug(sp2, Corruption::Write);
             gb.memory_write(sp1, gb.cpu.a);
             gb.memory_write(sp2, gb.cpu.f);
             gb.cpu.sp = sp2;
@@ -0,0 +1,287 @@
+// ROM patches.
+//
+// IPS, UPS and BPS patches are recognised by their magic bytes and applied to
+// the ROM image before the mapper is built, so translations and hacks run
+// without touching the original file. IPS supports RLE records and the
+// truncation extension. UPS and BPS carry CRC-32s of the source, target and
+// patch, which are all verified.
+
+use crate::globals::ROM_MAX_SIZE;
+use crate::{utils, Error};
+
+use std::path::{Path, PathBuf};
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: usize = 0x454F46;
+const UPS_MAGIC: &[u8] = b"UPS1";
+const BPS_MAGIC: &[u8] = b"BPS1";
+// source, target and patch CRC-32
+const FOOTER_LEN: usize = 12;
+// enough for any 64-bit number
+const NUMBER_MAX_LEN: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchFormat {
+    Ips,
+    Ups,
+    Bps,
+}
+
+impl PatchFormat {
+    pub fn detect(patch: &[u8]) -> Option<PatchFormat> {
+        match patch {
+            p if p.starts_with(IPS_MAGIC) => Some(PatchFormat::Ips),
+            p if p.starts_with(UPS_MAGIC) => Some(PatchFormat::Ups),
+            p if p.starts_with(BPS_MAGIC) => Some(PatchFormat::Bps),
+            _ => None,
+        }
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::Patch(message.to_string())
+}
+
+// Looks for `<rom>.ips`, `<rom>.ups` or `<rom>.bps` next to the ROM file.
+pub fn find_for_rom<P: AsRef<Path>>(rom_path: P) -> Option<PathBuf> {
+    ["ips", "ups", "bps"]
+        .iter()
+        .map(|ext| rom_path.as_ref().with_extension(ext))
+        .find(|path| path.is_file())
+}
+
+pub fn apply(patch: &[u8], rom: &[u8]) -> crate::Result<Vec<u8>> {
+    match PatchFormat::detect(patch) {
+        Some(PatchFormat::Ips) => apply_ips(patch, rom),
+        Some(PatchFormat::Ups) => apply_ups(patch, rom),
+        Some(PatchFormat::Bps) => apply_bps(patch, rom),
+        None => Err(invalid("Unknown patch format")),
+    }
+}
+
+struct PatchReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl PatchReader<'_> {
+    fn bytes(&mut self, count: usize) -> crate::Result<&[u8]> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + count)
+            .ok_or_else(|| invalid("Unexpected end of patch"))?;
+        self.offset += count;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> crate::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn big_endian(&mut self, count: usize) -> crate::Result<usize> {
+        Ok(self.bytes(count)?.iter().fold(0, |value, byte| value << 8 | *byte as usize))
+    }
+
+    // UPS/BPS variable length number, with each continuation adding an offset
+    // so every value has a single encoding
+    fn number(&mut self) -> crate::Result<usize> {
+        let overflow = || invalid("Number overflow in patch");
+        let (mut value, mut shift) = (0usize, 1usize);
+        for _ in 0..NUMBER_MAX_LEN {
+            let byte = self.byte()?;
+            value = (byte as usize & 0x7F)
+                .checked_mul(shift)
+                .and_then(|v| v.checked_add(value))
+                .ok_or_else(overflow)?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_mul(1 << 7).ok_or_else(overflow)?;
+            value = value.checked_add(shift).ok_or_else(overflow)?;
+        }
+        Err(overflow())
+    }
+
+    // a target size for UPS/BPS, bounded so a bad patch cannot exhaust memory
+    fn target_size(&mut self) -> crate::Result<usize> {
+        match self.number()? {
+            size if size > ROM_MAX_SIZE => Err(Error::Patch(format!(
+                "Target size {} exceeds the largest ROM size {}",
+                size, ROM_MAX_SIZE
+            ))),
+            size => Ok(size),
+        }
+    }
+}
+
+fn read_crc(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+fn check_crc(name: &str, data: &[u8], expected: u32) -> crate::Result<()> {
+    let actual = utils::crc32(data);
+    match actual == expected {
+        true => Ok(()),
+        false => Err(Error::Patch(format!(
+            "{} CRC32 mismatch: expected {:08X}, found {:08X}",
+            name, expected, actual
+        ))),
+    }
+}
+
+fn footer(patch: &[u8]) -> crate::Result<(u32, u32)> {
+    if patch.len() < UPS_MAGIC.len() + FOOTER_LEN {
+        return Err(invalid("Unexpected end of patch"));
+    }
+    let end = patch.len() - 4;
+    check_crc("Patch", &patch[..end], read_crc(patch, end))?;
+    Ok((read_crc(patch, end - 8), read_crc(patch, end - 4)))
+}
+
+pub fn apply_ips(patch: &[u8], rom: &[u8]) -> crate::Result<Vec<u8>> {
+    let mut reader = PatchReader {
+        data: patch,
+        offset: IPS_MAGIC.len(),
+    };
+    let mut out = rom.to_vec();
+
+    loop {
+        let offset = reader.big_endian(3)?;
+        if offset == IPS_EOF {
+            break;
+        }
+        let (len, value) = match reader.big_endian(2)? {
+            0 => (reader.big_endian(2)?, Some(reader.byte()?)),
+            len => (len, None),
+        };
+        if out.len() < offset + len {
+            out.resize(offset + len, 0);
+        }
+        match value {
+            Some(value) => out[offset..offset + len].fill(value),
+            None => out[offset..offset + len].copy_from_slice(reader.bytes(len)?),
+        }
+    }
+
+    // truncation extension: the new ROM size follows EOF
+    if reader.offset + 3 == patch.len() {
+        out.truncate(reader.big_endian(3)?);
+    }
+    Ok(out)
+}
+
+pub fn apply_ups(patch: &[u8], rom: &[u8]) -> crate::Result<Vec<u8>> {
+    let (source_crc, target_crc) = footer(patch)?;
+    let mut reader = PatchReader {
+        data: &patch[..patch.len() - FOOTER_LEN],
+        offset: UPS_MAGIC.len(),
+    };
+    let source_size = reader.number()?;
+    let target_size = reader.target_size()?;
+    if rom.len() != source_size {
+        return Err(Error::SizeMismatch {
+            expected: source_size,
+            actual: rom.len(),
+        });
+    }
+    check_crc("Source", rom, source_crc)?;
+
+    let mut out = rom.to_vec();
+    out.resize(target_size, 0);
+    let mut position = 0;
+    while reader.offset < reader.data.len() {
+        position += reader.number()?;
+        loop {
+            let xor = reader.byte()?;
+            if xor == 0 {
+                position += 1;
+                break;
+            }
+            let byte = out.get_mut(position).ok_or_else(|| invalid("Write past end of target"))?;
+            *byte ^= xor;
+            position += 1;
+        }
+    }
+
+    check_crc("Target", &out, target_crc)?;
+    Ok(out)
+}
+
+pub fn apply_bps(patch: &[u8], rom: &[u8]) -> crate::Result<Vec<u8>> {
+    let (source_crc, target_crc) = footer(patch)?;
+    let mut reader = PatchReader {
+        data: &patch[..patch.len() - FOOTER_LEN],
+        offset: BPS_MAGIC.len(),
+    };
+    let source_size = reader.number()?;
+    let target_size = reader.target_size()?;
+    let metadata = reader.number()?;
+    reader.bytes(metadata)?;
+    if rom.len() != source_size {
+        return Err(Error::SizeMismatch {
+            expected: source_size,
+            actual: rom.len(),
+        });
+    }
+    check_crc("Source", rom, source_crc)?;
+
+    let mut out = Vec::with_capacity(target_size);
+    let (mut source_offset, mut target_offset) = (0usize, 0usize);
+    let relative = |offset: usize, data: usize| -> crate::Result<usize> {
+        let delta = data >> 1;
+        match data & 1 {
+            1 => offset.checked_sub(delta),
+            _ => offset.checked_add(delta),
+        }
+        .ok_or_else(|| invalid("Copy offset out of range"))
+    };
+
+    while reader.offset < reader.data.len() {
+        let action = reader.number()?;
+        let len = (action >> 2) + 1;
+        if out.len() + len > target_size {
+            return Err(invalid("Write past end of target"));
+        }
+        match action & 3 {
+            // source read
+            0 => {
+                let start = out.len();
+                let bytes = rom
+                    .get(start..start + len)
+                    .ok_or_else(|| invalid("Source read past end of ROM"))?;
+                out.extend_from_slice(bytes);
+            }
+            // target read
+            1 => out.extend_from_slice(reader.bytes(len)?),
+            // source copy
+            2 => {
+                source_offset = relative(source_offset, reader.number()?)?;
+                let bytes = rom
+                    .get(source_offset..source_offset + len)
+                    .ok_or_else(|| invalid("Source copy past end of ROM"))?;
+                out.extend_from_slice(bytes);
+                source_offset += len;
+            }
+            // target copy, which may overlap the bytes it produces
+            _ => {
+                target_offset = relative(target_offset, reader.number()?)?;
+                for _ in 0..len {
+                    let byte = *out
+                        .get(target_offset)
+                        .ok_or_else(|| invalid("Target copy past end of output"))?;
+                    out.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if out.len() != target_size {
+        return Err(Error::SizeMismatch {
+            expected: target_size,
+            actual: out.len(),
+        });
+    }
+    check_crc("Target", &out, target_crc)?;
+    Ok(out)
+}
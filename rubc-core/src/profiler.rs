@@ -0,0 +1,265 @@
+// Cycle profiler.
+//
+// Cycles are accumulated per bank-qualified PC, per ROM bank and per
+// subroutine. Subroutines are tracked from CALL/RST, RET/RETI and interrupt
+// dispatch: exclusive cycles are spent in the subroutine itself, inclusive
+// cycles also count everything it called. Time spent in every distinct call
+// stack is kept for flamegraph tools (`collapsed_stacks`).
+
+use crate::{globals::*, symbols::SymbolTable};
+
+use prettytable::{format, row, Table};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+// how many rows each report table shows
+const REPORT_ROWS: usize = 20;
+
+// bank:addr of a subroutine entry point
+pub type Location = (usize, u16);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionStats {
+    pub calls: u64,
+    pub inclusive: u64,
+    pub exclusive: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Frame {
+    function: Location,
+    // where the matching RET returns to, `None` for the root frame
+    return_addr: Option<u16>,
+    // total cycles when the frame was entered
+    entered: u64,
+    // interned call stack ending in this frame
+    stack: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Profiler {
+    total: u64,
+    per_pc: HashMap<Location, u64>,
+    per_bank: BTreeMap<usize, u64>,
+    functions: HashMap<Location, FunctionStats>,
+    frames: Vec<Frame>,
+    stacks: Vec<Vec<Location>>,
+    stack_ids: HashMap<Vec<Location>, usize>,
+    stack_cycles: Vec<u64>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        let mut profiler = Profiler {
+            total: 0,
+            per_pc: HashMap::new(),
+            per_bank: BTreeMap::new(),
+            functions: HashMap::new(),
+            frames: Vec::new(),
+            stacks: Vec::new(),
+            stack_ids: HashMap::new(),
+            stack_cycles: Vec::new(),
+        };
+        profiler.reset();
+        profiler
+    }
+
+    // drops everything recorded, execution is attributed to the entry point again
+    pub fn reset(&mut self) {
+        let root = (0, CART_HEADER_START);
+        self.total = 0;
+        self.per_pc.clear();
+        self.per_bank.clear();
+        self.functions.clear();
+        self.stacks.clear();
+        self.stack_ids.clear();
+        self.stack_cycles.clear();
+
+        let stack = self.intern(vec![root]);
+        self.functions.entry(root).or_default().calls = 1;
+        self.frames = vec![Frame {
+            function: root,
+            return_addr: None,
+            entered: 0,
+            stack,
+        }];
+    }
+
+    fn intern(&mut self, stack: Vec<Location>) -> usize {
+        if let Some(id) = self.stack_ids.get(&stack) {
+            return *id;
+        }
+        let id = self.stacks.len();
+        self.stacks.push(stack.clone());
+        self.stack_ids.insert(stack, id);
+        self.stack_cycles.push(0);
+        id
+    }
+
+    pub fn total_cycles(&self) -> u64 {
+        self.total
+    }
+
+    // cycles spent executing the instruction at bank:pc, ROM banks only count ROM code
+    #[inline]
+    pub fn record(&mut self, bank: usize, pc: u16, cycles: u64) {
+        self.total += cycles;
+        *self.per_pc.entry((bank, pc)).or_insert(0) += cycles;
+        if pc <= ROM1_ADDRESS_END {
+            *self.per_bank.entry(bank).or_insert(0) += cycles;
+        }
+
+        let frame = self.frames.last().expect("profiler always has a root frame");
+        self.stack_cycles[frame.stack] += cycles;
+        self.functions.entry(frame.function).or_default().exclusive += cycles;
+    }
+
+    // a CALL, RST or interrupt entered bank:target
+    pub fn call(&mut self, bank: usize, target: u16, return_addr: u16) {
+        let function = (bank, target);
+        let parent = self.frames.last().expect("profiler always has a root frame").stack;
+        let mut stack = self.stacks[parent].clone();
+        stack.push(function);
+        let stack = self.intern(stack);
+
+        self.functions.entry(function).or_default().calls += 1;
+        self.frames.push(Frame {
+            function,
+            return_addr: Some(return_addr),
+            entered: self.total,
+            stack,
+        });
+    }
+
+    // A RET/RETI continued at `pc`. Frames are unwound up to the call that
+    // returns there, a RET that matches no call is treated as a jump.
+    pub fn ret(&mut self, pc: u16) {
+        let depth = match self.frames.iter().rposition(|f| f.return_addr == Some(pc)) {
+            Some(depth) => depth,
+            None => return,
+        };
+
+        while self.frames.len() > depth {
+            let frame = self.frames.pop().expect("checked above");
+            // recursive calls are only counted once, by their outermost frame
+            if !self.frames.iter().any(|f| f.function == frame.function) {
+                self.functions.entry(frame.function).or_default().inclusive += self.total - frame.entered;
+            }
+        }
+    }
+
+    pub fn cycles_at(&self, bank: usize, pc: u16) -> u64 {
+        self.per_pc.get(&(bank, pc)).copied().unwrap_or(0)
+    }
+
+    pub fn bank_cycles(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        self.per_bank.iter().map(|(bank, cycles)| (*bank, *cycles))
+    }
+
+    // Per-subroutine statistics, including the time spent so far in
+    // subroutines that have not returned yet.
+    pub fn functions(&self) -> HashMap<Location, FunctionStats> {
+        let mut functions = self.functions.clone();
+        for (depth, frame) in self.frames.iter().enumerate() {
+            if !self.frames[..depth].iter().any(|f| f.function == frame.function) {
+                functions.entry(frame.function).or_default().inclusive += self.total - frame.entered;
+            }
+        }
+        functions
+    }
+
+    // call stacks in the collapsed format of flamegraph.pl and inferno,
+    // `root;caller;callee cycles` one per line
+    pub fn collapsed_stacks(&self, symbols: Option<&SymbolTable>) -> String {
+        let mut lines: Vec<String> = self
+            .stacks
+            .iter()
+            .zip(self.stack_cycles.iter())
+            .filter(|(_, cycles)| **cycles > 0)
+            .map(|(stack, cycles)| {
+                let names: Vec<String> = stack.iter().map(|l| name(symbols, *l)).collect();
+                format!("{} {}", names.join(";"), cycles)
+            })
+            .collect();
+        lines.sort();
+
+        let mut out = lines.join("\n");
+        out.push('\n');
+        out
+    }
+
+    pub fn report(&self, symbols: Option<&SymbolTable>) -> String {
+        let percent = |cycles: u64| match self.total {
+            0 => 0.0,
+            total => cycles as f64 * 100.0 / total as f64,
+        };
+        let mut out = String::new();
+        let _ = writeln!(out, "Total cycles: {}\n", self.total);
+
+        let mut functions: Vec<(Location, FunctionStats)> = self.functions().into_iter().collect();
+        for (title, inclusive) in [("Subroutines by inclusive cycles", true), ("Subroutines by exclusive cycles", false)] {
+            functions.sort_by_key(|(location, stats)| {
+                let cycles = if inclusive { stats.inclusive } else { stats.exclusive };
+                (std::cmp::Reverse(cycles), *location)
+            });
+
+            let mut table = Table::new();
+            table.set_titles(row!["SUBROUTINE", "CALLS", "INCLUSIVE", "%", "EXCLUSIVE", "%"]);
+            for (location, stats) in functions.iter().take(REPORT_ROWS) {
+                table.add_row(row![
+                    name(symbols, *location),
+                    stats.calls,
+                    stats.inclusive,
+                    format!("{:.2}", percent(stats.inclusive)),
+                    stats.exclusive,
+                    format!("{:.2}", percent(stats.exclusive))
+                ]);
+            }
+            table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
+            let _ = writeln!(out, "{}\n{}", title, table);
+        }
+
+        let mut pcs: Vec<(&Location, &u64)> = self.per_pc.iter().collect();
+        pcs.sort_by_key(|(location, cycles)| (std::cmp::Reverse(**cycles), **location));
+        let mut table = Table::new();
+        table.set_titles(row!["PC", "SYMBOL", "CYCLES", "%"]);
+        for ((bank, pc), cycles) in pcs.into_iter().take(REPORT_ROWS) {
+            let symbol = symbols.and_then(|s| s.describe(*bank, *pc)).unwrap_or_default();
+            table.add_row(row![
+                format!("{:02X}:{:04X}", bank, pc),
+                symbol,
+                cycles,
+                format!("{:.2}", percent(*cycles))
+            ]);
+        }
+        table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
+        let _ = writeln!(out, "Hottest instructions\n{}", table);
+
+        let mut table = Table::new();
+        table.set_titles(row!["BANK", "CYCLES", "%"]);
+        for (bank, cycles) in self.per_bank.iter() {
+            table.add_row(row![format!("{:02X}", bank), cycles, format!("{:.2}", percent(*cycles))]);
+        }
+        let ram = self.total - self.per_bank.values().sum::<u64>();
+        if ram > 0 {
+            table.add_row(row!["RAM", ram, format!("{:.2}", percent(ram))]);
+        }
+        table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
+        let _ = writeln!(out, "ROM banks\n{}", table);
+
+        out
+    }
+}
+
+// symbol name of a subroutine, or `BB:AAAA`
+fn name(symbols: Option<&SymbolTable>, (bank, addr): Location) -> String {
+    symbols
+        .and_then(|s| s.describe(bank, addr))
+        .unwrap_or_else(|| format!("{:02X}:{:04X}", bank, addr))
+}
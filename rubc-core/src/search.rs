@@ -0,0 +1,194 @@
+// RAM search, used to locate game variables such as lives or coordinates.
+//
+// A search starts with every address of the searched regions as a candidate
+// and a snapshot of their values. Each filter compares the current values to
+// the previous snapshot, drops the candidates that don't match and takes a new
+// snapshot. Values are read as 8 or 16-bit little-endian numbers, either
+// binary or BCD; candidates holding invalid BCD never match.
+
+use crate::gameboy::Gameboy;
+use crate::{globals::*, Error};
+
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchRegion {
+    Wram,
+    Hram,
+    // currently mapped cartridge RAM bank
+    Sram,
+}
+
+impl SearchRegion {
+    pub fn range(&self) -> RangeInclusive<u16> {
+        match self {
+            SearchRegion::Wram => WRAM_ADDRESS_START..=WRAM1_ADDRESS_END,
+            SearchRegion::Hram => HRAM_ADDRESS_START..=HRAM_ADDRESS_END,
+            SearchRegion::Sram => EXTERNAL_RAM_ADDRESS_START..=EXTERNAL_RAM_ADDRESS_END,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValueSize {
+    #[default]
+    Byte,
+    Word,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValueEncoding {
+    #[default]
+    Binary,
+    // two decimal digits per byte, as used for scores
+    Bcd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchFilter {
+    Equal(u16),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl SearchFilter {
+    pub fn matches(&self, previous: u16, value: u16) -> bool {
+        match self {
+            SearchFilter::Equal(expected) => value == *expected,
+            SearchFilter::Changed => value != previous,
+            SearchFilter::Unchanged => value == previous,
+            SearchFilter::Increased => value > previous,
+            SearchFilter::Decreased => value < previous,
+        }
+    }
+}
+
+// `=N`, `changed`, `unchanged`, `increased` or `decreased`, N decimal or $hex
+impl FromStr for SearchFilter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(value) = s.strip_prefix('=') {
+            let value = value.trim();
+            let parsed = match value.strip_prefix('$').or_else(|| value.strip_prefix("0x")) {
+                Some(hex) => u16::from_str_radix(hex, 16),
+                None => value.parse::<u16>(),
+            };
+            return parsed
+                .map(SearchFilter::Equal)
+                .map_err(|_| Error::Parse(format!("Invalid search value: {:?}", value)));
+        }
+
+        match s.to_lowercase().as_str() {
+            "changed" | "!=" => Ok(SearchFilter::Changed),
+            "unchanged" | "==" => Ok(SearchFilter::Unchanged),
+            "increased" | ">" => Ok(SearchFilter::Increased),
+            "decreased" | "<" => Ok(SearchFilter::Decreased),
+            _ => Err(Error::Parse(format!("Invalid search filter: {:?}", s))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Candidate {
+    pub address: u16,
+    // value at the last snapshot
+    pub value: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct RamSearch {
+    regions: Vec<SearchRegion>,
+    size: ValueSize,
+    encoding: ValueEncoding,
+    candidates: Vec<Candidate>,
+}
+
+fn decode_bcd(byte: u8) -> Option<u16> {
+    let (high, low) = (byte >> 4, byte & 0x0F);
+    (high < 10 && low < 10).then_some(high as u16 * 10 + low as u16)
+}
+
+impl Default for RamSearch {
+    fn default() -> Self {
+        Self::new(ValueSize::default(), ValueEncoding::default())
+    }
+}
+
+impl RamSearch {
+    pub fn new(size: ValueSize, encoding: ValueEncoding) -> Self {
+        RamSearch {
+            regions: vec![SearchRegion::Wram, SearchRegion::Hram, SearchRegion::Sram],
+            size,
+            encoding,
+            candidates: Vec::new(),
+        }
+    }
+
+    pub fn with_regions(mut self, regions: &[SearchRegion]) -> Self {
+        self.regions = regions.to_vec();
+        self
+    }
+
+    // value at address as searched, None when it is not valid BCD
+    pub fn read(&self, gb: &Gameboy, address: u16) -> Option<u16> {
+        let byte = |address: u16| match self.encoding {
+            ValueEncoding::Binary => Some(gb.debug_read(address) as u16),
+            ValueEncoding::Bcd => decode_bcd(gb.debug_read(address)),
+        };
+        let radix = match self.encoding {
+            ValueEncoding::Binary => 0x100,
+            ValueEncoding::Bcd => 100,
+        };
+        match self.size {
+            ValueSize::Byte => byte(address),
+            ValueSize::Word => Some(byte(address)? + byte(address.wrapping_add(1))? * radix),
+        }
+    }
+
+    // makes every address of the regions a candidate
+    pub fn start(&mut self, gb: &Gameboy) -> usize {
+        let last_byte = match self.size {
+            ValueSize::Byte => 0,
+            ValueSize::Word => 1,
+        };
+        self.candidates = self
+            .regions
+            .iter()
+            .flat_map(|region| *region.range().start()..=region.range().end() - last_byte)
+            .filter_map(|address| self.read(gb, address).map(|value| Candidate { address, value }))
+            .collect();
+        self.candidates.len()
+    }
+
+    // keeps the candidates matching the filter and returns how many are left
+    pub fn filter(&mut self, gb: &Gameboy, filter: SearchFilter) -> usize {
+        let candidates = std::mem::take(&mut self.candidates);
+        self.candidates = candidates
+            .into_iter()
+            .filter_map(|candidate| {
+                let value = self.read(gb, candidate.address)?;
+                filter
+                    .matches(candidate.value, value)
+                    .then_some(Candidate { value, ..candidate })
+            })
+            .collect();
+        self.candidates.len()
+    }
+
+    pub fn candidates(&self) -> &[Candidate] {
+        &self.candidates
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+}
@@ -0,0 +1,161 @@
+// Symbol tables loaded from RGBDS/NO$GMB `.sym` files (`BB:AAAA Name`) and
+// NoICE `.noi` files (`DEF Name 0xBBAAAA`) as produced by rgblink and GBDK.
+
+use crate::{globals::*, Error};
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+#[derive(Debug, Default, Clone)]
+pub struct SymbolTable {
+    by_address: BTreeMap<(usize, u16), Vec<String>>,
+    by_name: HashMap<String, (usize, u16)>,
+}
+
+// symbol lookups only care about the bank in banked regions
+#[inline]
+fn normalize_bank(bank: usize, address: u16) -> usize {
+    match address {
+        ROM1_ADDRESS_START..=ROM1_ADDRESS_END
+        | EXTERNAL_RAM_ADDRESS_START..=EXTERNAL_RAM_ADDRESS_END => bank,
+        _ => 0,
+    }
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> crate::Result<SymbolTable> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        let table = match path.extension().and_then(|e| e.to_str()) {
+            Some("noi") => SymbolTable::parse_noi(&contents)?,
+            _ => SymbolTable::parse_sym(&contents)?,
+        };
+        log::debug!("Loaded {} symbols from {}", table.len(), path.display());
+        Ok(table)
+    }
+
+    // Looks for `<rom>.sym` or `<rom>.noi` next to the ROM file.
+    pub fn load_for_rom<P: AsRef<Path>>(rom_path: P) -> Option<SymbolTable> {
+        ["sym", "noi"].iter().find_map(|ext| {
+            let path = rom_path.as_ref().with_extension(ext);
+            if !path.is_file() {
+                return None;
+            }
+            SymbolTable::load(&path)
+                .map_err(|e| log::warn!("Unable to load symbols from {}: {}", path.display(), e))
+                .ok()
+        })
+    }
+
+    pub fn parse_sym(contents: &str) -> crate::Result<SymbolTable> {
+        let mut table = SymbolTable::new();
+
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let invalid = || Error::Parse(format!("Invalid symbol on line {}: {:?}", line_number + 1, line));
+            let (location, name) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let (bank, address) = location.split_once(':').ok_or_else(invalid)?;
+            let bank = usize::from_str_radix(bank, 16).map_err(|_| invalid())?;
+            let address = u16::from_str_radix(address, 16).map_err(|_| invalid())?;
+
+            table.insert(bank, address, name.trim());
+        }
+
+        Ok(table)
+    }
+
+    pub fn parse_noi(contents: &str) -> crate::Result<SymbolTable> {
+        let mut table = SymbolTable::new();
+
+        for (line_number, line) in contents.lines().enumerate() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 3 || !fields[0].eq_ignore_ascii_case("DEF") {
+                continue;
+            }
+
+            let value = fields[2];
+            let value = value
+                .strip_prefix("0x")
+                .or_else(|| value.strip_prefix("0X"))
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .unwrap_or_else(|| value.parse::<u32>())
+                .map_err(|_| Error::Parse(format!("Invalid symbol on line {}: {:?}", line_number + 1, line)))?;
+
+            // GBDK stores the bank in the bits above the 16-bit address
+            table.insert((value >> 16) as usize, value as u16, fields[1]);
+        }
+
+        Ok(table)
+    }
+
+    pub fn insert(&mut self, bank: usize, address: u16, name: &str) {
+        let bank = normalize_bank(bank, address);
+        self.by_address
+            .entry((bank, address))
+            .or_default()
+            .push(name.to_string());
+        self.by_name.insert(name.to_string(), (bank, address));
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_name.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    pub fn resolve(&self, name: &str) -> Option<(usize, u16)> {
+        self.by_name.get(name).copied()
+    }
+
+    // first symbol defined exactly at bank:address
+    pub fn name_at(&self, bank: usize, address: u16) -> Option<&str> {
+        self.by_address
+            .get(&(normalize_bank(bank, address), address))
+            .and_then(|names| names.first())
+            .map(|name| name.as_str())
+    }
+
+    // closest symbol at or before bank:address within the same region, with the offset to it
+    pub fn nearest(&self, bank: usize, address: u16) -> Option<(&str, u16)> {
+        let bank = normalize_bank(bank, address);
+        let region_start = match address {
+            ROM_ADDRESS_START..=ROM_ADDRESS_END => ROM_ADDRESS_START,
+            ROM1_ADDRESS_START..=ROM1_ADDRESS_END => ROM1_ADDRESS_START,
+            VRAM_ADDRESS_START..=VRAM_ADDRESS_END => VRAM_ADDRESS_START,
+            EXTERNAL_RAM_ADDRESS_START..=EXTERNAL_RAM_ADDRESS_END => EXTERNAL_RAM_ADDRESS_START,
+            WRAM_ADDRESS_START..=WRAM_ADDRESS_END => WRAM_ADDRESS_START,
+            WRAM1_ADDRESS_START..=WRAM1_ADDRESS_END => WRAM1_ADDRESS_START,
+            HRAM_ADDRESS_START..=HRAM_ADDRESS_END => HRAM_ADDRESS_START,
+            _ => address,
+        };
+
+        self.by_address
+            .range((bank, region_start)..=(bank, address))
+            .next_back()
+            .and_then(|((_, sym_addr), names)| Some((names.first()?.as_str(), address - sym_addr)))
+    }
+
+    // `Name` or `Name+offset` for the closest symbol, if any
+    pub fn describe(&self, bank: usize, address: u16) -> Option<String> {
+        self.nearest(bank, address).map(|(name, offset)| match offset {
+            0 => name.to_string(),
+            _ => format!("{}+{}", name, offset),
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, u16, &str)> {
+        self.by_address
+            .iter()
+            .flat_map(|((bank, address), names)| names.iter().map(move |n| (*bank, *address, n.as_str())))
+    }
+}
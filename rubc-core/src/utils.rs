@@ -1,191 +1,80 @@
-use crate::{cartridge::Cartridge, globals::*, opcodes::op_code_names};
-use prettytable::{format, row, Table};
-
-pub fn disassemble(cart: &Cartridge) -> String {
-    let rom = cart.rom();
-
-    let rel_addr = |bank: usize, idx: usize| bank * ROM_BANK_SIZE + idx;
-
-    let mut table = Table::new();
-    let header = row![
-        "ADDRESS",
-        "OPCODE",
-        "VALUE",
-        "DESCRIPTION",
-        "NOTES",
-        "OPLEN"
-    ];
-    table.set_titles(header);
-
-    let mut opcode: u8;
-    let mut notes: &str = "";
-
-    for bank in 0..(rom.len() / ROM_BANK_SIZE) {
-        let mut i = 0;
-
-        while i < ROM_BANK_SIZE {
-            let mut cb_mode = false;
-            let address = rel_addr(bank, i);
-            let oplen = OPCODE_LENGTHS[rom[address] as usize] as usize;
-            if rom[address] == 0x00 {
-                i += 1;
-                continue;
-            }
-
-            opcode = rom[address];
-            if 0x104 <= address && address <= 0x014F {
-                i += 1;
-
-                table.add_row(row![
-                    format!("{:02X}:{:04X}", 0, address),
-                    format!("${:02X}", opcode),
-                    "",
-                    "CART HEADER",
-                    "",
-                    ""
-                ]);
-                continue;
-            }
-
-            if opcode == 0xCB {
-                i += 1;
-                opcode = rom[rel_addr(bank, i)];
-                notes = "CB Prefix";
-                cb_mode = true;
-            }
-
-            match oplen {
-                2 => {
-                    let orig_addr = address;
-                    notes = "8bit";
-                    opcode = rom[address];
-                    i += 1;
-                    let value = rom[rel_addr(bank, i)];
-                    let opcode_name = op_code_names(opcode, cb_mode);
-
-                    table.add_row(row![
-                        format!(
-                            "{:02X}:{:04X}",
-                            bank,
-                            if bank == 0 {
-                                orig_addr
-                            } else {
-                                orig_addr - (bank * ROM_BANK_SIZE)
-                            }
-                        ),
-                        format!("${:02X}", opcode),
-                        format!("${:04X}", value),
-                        opcode_name,
-                        notes,
-                        oplen
-                    ]);
-                    i += 1;
-                }
-                3 => {
-                    let orig_addr = address;
-                    notes = "16bit";
-                    opcode = rom[address];
-                    i += 1;
-                    let h = rom[rel_addr(bank, i)] as u16;
-                    i += 1;
-                    let l = rom[rel_addr(bank, i)] as u16;
-                    let value = (l << 8) | h;
-                    let opcode_name = op_code_names(opcode, cb_mode);
-                    table.add_row(row![
-                        format!(
-                            "{:02X}:{:04X}",
-                            bank,
-                            if bank == 0 {
-                                orig_addr
-                            } else {
-                                orig_addr - (bank * ROM_BANK_SIZE)
-                            }
-                        ),
-                        format!("${:02X}", opcode),
-                        format!("${:04X}", value),
-                        opcode_name,
-                        notes,
-                        oplen
-                    ]);
-                    i += 1;
-                }
-                _ => {
-                    let orig_addr = address;
-                    notes = "";
-
-                    let opcode_name = op_code_names(opcode, cb_mode);
-                    table.add_row(row![
-                        format!(
-                            "{:02X}:{:04X}",
-                            bank,
-                            if bank == 0 {
-                                orig_addr
-                            } else {
-                                orig_addr - (bank * ROM_BANK_SIZE)
-                            }
-                        ),
-                        format!("${:02X}", opcode),
-                        "",
-                        opcode_name,
-                        notes,
-                        oplen
-                    ]);
-                    i += 1;
-                }
-            }
-        }
-    }
-    table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
-
-    table.to_string()
+use crate::{cartridge::Cartridge, coverage::Coverage, disasm::Disassembler, globals::*, symbols::SymbolTable, Error};
+
+pub fn disassemble(cart: &Cartridge, symbols: Option<&SymbolTable>, coverage: Option<&Coverage>) -> String {
+    Disassembler::new(cart.rom())
+        .with_symbols(symbols)
+        .with_coverage(coverage)
+        .disassemble()
+        .to_table()
 }
 
 pub fn get_metadata(cart: &Cartridge) -> String {
-    let rom = cart.rom();
-
-    let header_checksum = rom[CART_HEADER_CHECKSUM as usize];
-    let checksum =
-        calculate_checksum(&rom[CART_TITLE_START as usize..CART_MASK_ROM_VERSION_NUMBER as usize]);
-
-    let mut table = Table::new();
-    table.set_titles(row!["Attribute", "Value"]);
-    table.add_row(row![
-        "TITLE",
-        &rom[CART_TITLE_START as usize..CART_TITLE_END as usize]
-            .iter()
-            .map(|x| *x as char)
-            .collect::<String>()
-    ]);
-    table.add_row(row!["CGB Flag", cbg_flag_map(rom[CART_TYPE as usize])]);
-    table.add_row(row!["SGB Flag", rom[CART_SGB_FLAG as usize]]);
-    table.add_row(row![
-        "Cartridge Type",
-        cart_type_map(rom[CART_TYPE as usize])
-    ]);
-    table.add_row(row!["ROM Size", rom_size_map(rom[CART_ROM_SIZE as usize])]);
-    table.add_row(row!["RAM Size", ram_size_map(rom[CART_SRAM_SIZE as usize])]);
-    table.add_row(row!["Header Checksum", format!("{:#x}", header_checksum)]);
-    table.add_row(row!["Checksum Valid", header_checksum == checksum]);
-    table.add_row(row![
-        "Global Checksum",
-        format!("{:#x}", {
-            let hi_byte = rom[CART_GLOBAL_CHECKSUM_START as usize];
-            let lo_byte = rom[CART_GLOBAL_CHECKSUM_END as usize + 1];
-            (hi_byte as u16) << 8 | (lo_byte as u16)
-        })
-    ]);
-
-    table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
-
-    table.to_string()
+    match cart.header() {
+        Ok(header) => header.to_table(),
+        Err(e) => format!("Invalid cartridge header: {}", e),
+    }
 }
 
 pub fn calculate_checksum(mem: &[u8]) -> u8 {
     mem.iter().fold(0, |acc: u8, x: &u8| {
         // asdf
-        let y = x + 1;
+        let y = x.wrapping_add(1);
         acc.wrapping_sub(y)
-    }) - 1
+    })
+    .wrapping_sub(1)
+}
+
+// CRC-32 (IEEE) as used by zip, gzip and the UPS/BPS patch formats
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| match crc & 1 {
+            1 => (crc >> 1) ^ 0xEDB8_8320,
+            _ => crc >> 1,
+        })
+    })
+}
+
+// parses a hex number with an optional `0x` or `$` prefix
+pub fn parse_hex(s: &str) -> crate::Result<usize> {
+    let s = s.trim();
+    let digits = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix('$'))
+        .unwrap_or(s);
+    usize::from_str_radix(digits, 16).map_err(|_| Error::Parse(format!("Invalid hex value: {:?}", s)))
+}
+
+// parses `addr` or `bank:addr`
+pub fn parse_bank_address(s: &str) -> crate::Result<(Option<usize>, u16)> {
+    let (bank, addr) = match s.split_once(':') {
+        Some((bank, addr)) => (Some(parse_hex(bank)?), addr),
+        None => (None, s),
+    };
+
+    let addr = parse_hex(addr)?;
+    if addr > u16::MAX as usize {
+        return Err(Error::Parse(format!("Address out of range: {:?}", s)));
+    }
+    Ok((bank, addr as u16))
+}
+
+// parses `addr`, `bank:addr`, `addr-addr` or `bank:addr-bank:addr` into an inclusive range
+pub fn parse_bank_range(s: &str) -> crate::Result<(Option<usize>, u16, u16)> {
+    let ((bank1, start), (bank2, end)) = match s.split_once('-') {
+        Some((start, end)) => (parse_bank_address(start)?, parse_bank_address(end)?),
+        None => {
+            let location = parse_bank_address(s)?;
+            (location, location)
+        }
+    };
+
+    if bank2.is_some() && bank1 != bank2 {
+        return Err(Error::Parse(format!("Range spans banks: {:?}", s)));
+    }
+    if start > end {
+        return Err(Error::Parse(format!("Invalid range: {:?}", s)));
+    }
+    Ok((bank1, start, end))
 }
 
 #[inline]
@@ -202,6 +91,45 @@ pub const fn interrupt_address(val: u8) -> u16 {
     }
 }
 
+// Bits of an IO register that read as 1: unused bits, write-only registers
+// and unmapped addresses, which read as open bus. CGB-only registers are
+// unmapped on the DMG.
+pub const fn io_read_mask(address: u16, cgb: bool) -> u8 {
+    match address {
+        IO_P1_JOYP => 0xC0,
+        IO_SB => 0x00,
+        IO_SC if cgb => 0x7C,
+        IO_SC => 0x7E,
+        IO_DIV | IO_TIMA | IO_TMA => 0x00,
+        IO_TAC => 0xF8,
+        IO_IF => 0xE0,
+        IO_NR10 => 0x80,
+        // sound lengths and frequency low bytes are write-only
+        IO_NR11 | IO_NR21 => 0x3F,
+        IO_NR12 | IO_NR22 | IO_NR42 | IO_NR43 | IO_NR50 | IO_NR51 => 0x00,
+        IO_NR13 | IO_NR23 | IO_NR31 | IO_NR33 | IO_NR41 => 0xFF,
+        IO_NR14 | IO_NR24 | IO_NR34 | IO_NR44 => 0xBF,
+        IO_NR30 => 0x7F,
+        IO_NR32 => 0x9F,
+        IO_NR52 => 0x70,
+        IO_WAVE_RAM1..=IO_WAVE_RAMF | 0xFF3F => 0x00,
+        IO_LCDC | IO_SCY | IO_SCX | IO_LY | IO_LYC | IO_DMA => 0x00,
+        IO_STAT => 0x80,
+        IO_BGP | IO_OBP0 | IO_OBP1 | IO_WY | IO_WX => 0x00,
+        IO_KEY1 if cgb => 0x7E,
+        IO_VBK if cgb => 0xFE,
+        IO_HDMA5 if cgb => 0x00,
+        IO_RP if cgb => 0x3C,
+        IO_BCPS | IO_OCPS if cgb => 0x40,
+        IO_BCPD | IO_OCPD if cgb => 0x00,
+        IO_OPRI if cgb => 0xFE,
+        IO_SVBK if cgb => 0xF8,
+        IO_PCM12 | IO_PCM34 if cgb => 0x00,
+        IO_IE => 0x00,
+        _ => 0xFF,
+    }
+}
+
 use std::fs::File;
 use std::io::Write;
 
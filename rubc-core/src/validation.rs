@@ -0,0 +1,188 @@
+// Cartridge validation.
+//
+// The DMG boot ROM locks up unless the Nintendo logo at $0104-$0133 and the
+// header checksum match, while the CGB boot ROM only compares the top half of
+// the logo. The 16-bit global checksum is never verified by hardware, so
+// homebrew and patched ROMs often get it wrong. `ValidationPolicy` decides
+// whether a failed check rejects the ROM, is only logged or is skipped; by
+// default only the checks the boot ROM makes are fatal.
+
+use crate::header::header_checksum;
+use crate::{globals::*, Error};
+
+use prettytable::{format, row, Table};
+use serde::Serialize;
+
+pub const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D, 0x00, 0x08,
+    0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99, 0xBB, 0xBB, 0x67, 0x63,
+    0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+// bytes of the logo compared by the CGB boot ROM
+const LOGO_TOP_HALF: usize = 0x18;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValidationPolicy {
+    // reject ROMs failing any check
+    Strict,
+    // reject ROMs the DMG boot ROM would lock up on, log a bad global checksum
+    #[default]
+    Boot,
+    // log failed checks and load the ROM anyway
+    Warn,
+    // skip validation
+    Ignore,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogoCheck {
+    Valid,
+    // only the part checked by the CGB boot ROM matches
+    TopHalfOnly,
+    Invalid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValidationReport {
+    pub logo: LogoCheck,
+    pub header_checksum: u8,
+    pub header_checksum_computed: u8,
+    pub global_checksum: u16,
+    pub global_checksum_computed: u16,
+}
+
+// sum of every ROM byte except the two checksum bytes themselves
+pub fn global_checksum(rom: &[u8]) -> u16 {
+    rom.iter()
+        .enumerate()
+        .filter(|(address, _)| {
+            !(CART_GLOBAL_CHECKSUM_START as usize..=CART_GLOBAL_CHECKSUM_END as usize).contains(address)
+        })
+        .fold(0u16, |sum, (_, byte)| sum.wrapping_add(*byte as u16))
+}
+
+pub fn check_logo(rom: &[u8]) -> LogoCheck {
+    let logo = &rom[CART_NINTENDO_LOGO_START as usize..=CART_NINTENDO_LOGO_END as usize];
+    if logo == NINTENDO_LOGO {
+        LogoCheck::Valid
+    } else if logo[..LOGO_TOP_HALF] == NINTENDO_LOGO[..LOGO_TOP_HALF] {
+        LogoCheck::TopHalfOnly
+    } else {
+        LogoCheck::Invalid
+    }
+}
+
+impl ValidationReport {
+    pub fn new(rom: &[u8]) -> crate::Result<ValidationReport> {
+        if rom.len() <= CART_HEADER_END as usize {
+            return Err(Error::SizeMismatch {
+                expected: CART_HEADER_END as usize + 1,
+                actual: rom.len(),
+            });
+        }
+
+        Ok(ValidationReport {
+            logo: check_logo(rom),
+            header_checksum: rom[CART_HEADER_CHECKSUM as usize],
+            header_checksum_computed: header_checksum(rom),
+            global_checksum: (rom[CART_GLOBAL_CHECKSUM_START as usize] as u16) << 8
+                | rom[CART_GLOBAL_CHECKSUM_END as usize] as u16,
+            global_checksum_computed: global_checksum(rom),
+        })
+    }
+
+    pub fn header_checksum_valid(&self) -> bool {
+        self.header_checksum == self.header_checksum_computed
+    }
+
+    pub fn global_checksum_valid(&self) -> bool {
+        self.global_checksum == self.global_checksum_computed
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.logo == LogoCheck::Valid && self.header_checksum_valid() && self.global_checksum_valid()
+    }
+
+    // failed checks, in the order the boot ROM would hit them
+    pub fn errors(&self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        if self.logo != LogoCheck::Valid {
+            errors.push(Error::InvalidLogo {
+                top_half_valid: self.logo == LogoCheck::TopHalfOnly,
+            });
+        }
+        if !self.header_checksum_valid() {
+            errors.push(Error::ChecksumMismatch {
+                expected: self.header_checksum,
+                actual: self.header_checksum_computed,
+            });
+        }
+        if !self.global_checksum_valid() {
+            errors.push(Error::GlobalChecksumMismatch {
+                expected: self.global_checksum,
+                actual: self.global_checksum_computed,
+            });
+        }
+        errors
+    }
+
+    pub fn apply(&self, policy: ValidationPolicy) -> crate::Result<()> {
+        match policy {
+            ValidationPolicy::Ignore => Ok(()),
+            ValidationPolicy::Warn => {
+                self.errors().iter().for_each(|error| log::warn!("{}", error));
+                Ok(())
+            }
+            ValidationPolicy::Boot => {
+                let (logged, fatal): (Vec<_>, Vec<_>) = self
+                    .errors()
+                    .into_iter()
+                    .partition(|error| matches!(error, Error::GlobalChecksumMismatch { .. }));
+                logged.iter().for_each(|error| log::warn!("{}", error));
+                match fatal.into_iter().next() {
+                    Some(error) => Err(error),
+                    None => Ok(()),
+                }
+            }
+            ValidationPolicy::Strict => match self.errors().into_iter().next() {
+                Some(error) => Err(error),
+                None => Ok(()),
+            },
+        }
+    }
+
+    pub fn to_table(&self) -> String {
+        let check = |valid: bool| match valid {
+            true => "ok",
+            false => "MISMATCH",
+        };
+        let mut table = Table::new();
+        table.set_titles(row!["Check", "Stored", "Computed", "Result"]);
+        table.add_row(row![
+            "Nintendo Logo",
+            "-",
+            "-",
+            match self.logo {
+                LogoCheck::Valid => "ok",
+                LogoCheck::TopHalfOnly => "TOP HALF ONLY",
+                LogoCheck::Invalid => "MISMATCH",
+            }
+        ]);
+        table.add_row(row![
+            "Header Checksum",
+            format!("{:#04x}", self.header_checksum),
+            format!("{:#04x}", self.header_checksum_computed),
+            check(self.header_checksum_valid())
+        ]);
+        table.add_row(row![
+            "Global Checksum",
+            format!("{:#06x}", self.global_checksum),
+            format!("{:#06x}", self.global_checksum_computed),
+            check(self.global_checksum_valid())
+        ]);
+        table.set_format(*format::consts::FORMAT_BORDERS_ONLY);
+        table.to_string()
+    }
+}
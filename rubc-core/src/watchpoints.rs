@@ -0,0 +1,228 @@
+use crate::{globals::*, utils, Error};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,
+}
+
+impl WatchKind {
+    // does a watchpoint of this kind fire for the given memory access
+    #[inline]
+    pub fn matches(&self, access: WatchKind) -> bool {
+        *self == WatchKind::Access || *self == access
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchCondition {
+    Equal(u8),
+    NotEqual(u8),
+    // value differs from the previous one, i.e. the byte being overwritten on
+    // writes or the last value read from the address through this watchpoint on reads
+    Changed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WatchAction {
+    #[default]
+    Pause,
+    Log,
+}
+
+#[derive(Debug, Clone)]
+pub struct Watchpoint {
+    pub kind: WatchKind,
+    pub start: u16,
+    pub end: u16,
+    // only fire while this ROM/SRAM bank is mapped
+    pub bank: Option<usize>,
+    pub condition: Option<WatchCondition>,
+    pub action: WatchAction,
+    // last value read through this watchpoint at each address
+    last_read: RefCell<HashMap<u16, u8>>,
+}
+
+impl PartialEq for Watchpoint {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+            && self.start == other.start
+            && self.end == other.end
+            && self.bank == other.bank
+            && self.condition == other.condition
+            && self.action == other.action
+    }
+}
+
+impl Eq for Watchpoint {}
+
+impl Watchpoint {
+    pub fn new(kind: WatchKind, start: u16, end: u16) -> Self {
+        Self {
+            kind,
+            start,
+            end,
+            bank: None,
+            condition: None,
+            action: WatchAction::default(),
+            last_read: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_bank(mut self, bank: usize) -> Self {
+        self.bank = Some(bank);
+        self
+    }
+
+    pub fn with_condition(mut self, condition: WatchCondition) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+
+    pub fn with_action(mut self, action: WatchAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    #[inline]
+    pub fn contains(&self, address: u16) -> bool {
+        (self.start..=self.end).contains(&address)
+    }
+
+    // `previous` is the byte about to be overwritten, `None` for reads
+    pub fn condition_met(&self, address: u16, value: u8, previous: Option<u8>) -> bool {
+        let previous = match previous {
+            Some(previous) => Some(previous),
+            None => self.last_read.borrow_mut().insert(address, value),
+        };
+
+        match self.condition {
+            None => true,
+            Some(WatchCondition::Equal(expected)) => value == expected,
+            Some(WatchCondition::NotEqual(expected)) => value != expected,
+            Some(WatchCondition::Changed) => previous.is_some_and(|previous| previous != value),
+        }
+    }
+}
+
+fn is_banked(address: u16) -> bool {
+    matches!(
+        address,
+        ROM_ADDRESS_START..=ROM1_ADDRESS_END | EXTERNAL_RAM_ADDRESS_START..=EXTERNAL_RAM_ADDRESS_END
+    )
+}
+
+// Parses the `--watchpoints` syntax: `KIND/RANGE[/CONDITION][/ACTION]`
+//
+//   KIND       r, w or a (read, write, access)
+//   RANGE      addr, bank:addr, addr-addr or bank:addr-bank:addr
+//   CONDITION  ==XX, !=XX or changed
+//   ACTION     pause (default) or log
+//
+// i.e. `w/C000`, `a/01:A000-01:A0FF/==FF/log`, `w/C000-C00F/changed`
+impl FromStr for Watchpoint {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split('/');
+
+        let kind = match fields.next().map(|k| k.trim().to_lowercase()).as_deref() {
+            Some("r") | Some("read") => WatchKind::Read,
+            Some("w") | Some("write") => WatchKind::Write,
+            Some("a") | Some("rw") | Some("access") => WatchKind::Access,
+            _ => return Err(Error::Parse(format!("Invalid watchpoint kind: {:?}", s))),
+        };
+
+        let range = fields
+            .next()
+            .ok_or_else(|| Error::Parse(format!("Missing watchpoint address: {:?}", s)))?;
+
+        let (bank, start, end) = utils::parse_bank_range(range)?;
+
+        let mut watchpoint = Watchpoint::new(kind, start, end);
+        if let Some(bank) = bank {
+            if !is_banked(start) || !is_banked(end) {
+                return Err(Error::Parse(format!(
+                    "Bank qualifier only valid for ROM and SRAM addresses: {:?}",
+                    s
+                )));
+            }
+            watchpoint = watchpoint.with_bank(bank);
+        }
+
+        for field in fields {
+            let field = field.trim();
+            let condition = if let Some(value) = field.strip_prefix("==") {
+                WatchCondition::Equal(utils::parse_hex(value)? as u8)
+            } else if let Some(value) = field.strip_prefix("!=") {
+                WatchCondition::NotEqual(utils::parse_hex(value)? as u8)
+            } else {
+                match field.to_lowercase().as_str() {
+                    "changed" => WatchCondition::Changed,
+                    "pause" => {
+                        watchpoint = watchpoint.with_action(WatchAction::Pause);
+                        continue;
+                    }
+                    "log" => {
+                        watchpoint = watchpoint.with_action(WatchAction::Log);
+                        continue;
+                    }
+                    _ => {
+                        return Err(Error::Parse(format!(
+                            "Invalid watchpoint field {:?} in {:?}",
+                            field, s
+                        )))
+                    }
+                }
+            };
+            watchpoint = watchpoint.with_condition(condition);
+        }
+
+        Ok(watchpoint)
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            WatchKind::Read => "r",
+            WatchKind::Write => "w",
+            WatchKind::Access => "a",
+        };
+        let bank = match self.bank {
+            Some(bank) => format!("{:02X}:", bank),
+            None => String::new(),
+        };
+
+        write!(f, "{}/{}{:04X}", kind, bank, self.start)?;
+        if self.end != self.start {
+            write!(f, "-{}{:04X}", bank, self.end)?;
+        }
+
+        match self.condition {
+            Some(WatchCondition::Equal(value)) => write!(f, "/=={:02X}", value)?,
+            Some(WatchCondition::NotEqual(value)) => write!(f, "/!={:02X}", value)?,
+            Some(WatchCondition::Changed) => write!(f, "/changed")?,
+            None => {}
+        }
+
+        match self.action {
+            WatchAction::Pause => write!(f, "/pause"),
+            WatchAction::Log => write!(f, "/log"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    pub kind: WatchKind,
+    pub address: u16,
+    pub value: u8,
+    pub pc: u16,
+}
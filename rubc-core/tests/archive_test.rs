@@ -0,0 +1,187 @@
+#[cfg(test)]
+mod tests {
+    use flate2::write::{DeflateEncoder, GzEncoder};
+    use flate2::Compression;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use rubc_core::archive::*;
+    use rubc_core::cartridge::Cartridge;
+    use rubc_core::utils::crc32;
+    use rubc_core::validation::ValidationPolicy;
+    use rubc_core::RubcError;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for len in [1, 7, 64, 1000, 65537] {
+            let data = (0..len).map(|_| rng.gen::<u8>()).collect::<Vec<u8>>();
+            let mut expected = flate2::Crc::new();
+            expected.update(&data);
+            assert_eq!(crc32(&data), expected.sum());
+        }
+    }
+
+    // random bytes mixed with runs and repeated slices so the encoder emits
+    // literals, matches and, depending on the level, all three block types
+    fn sample(rng: &mut StdRng, len: usize) -> Vec<u8> {
+        let mut data = Vec::with_capacity(len);
+        while data.len() < len {
+            match rng.gen_range(0..3) {
+                0 => data.extend((0..rng.gen_range(1..300)).map(|_| rng.gen::<u8>())),
+                1 => data.extend(std::iter::repeat_n(rng.gen::<u8>(), rng.gen_range(1..600))),
+                _ if !data.is_empty() => {
+                    let start = rng.gen_range(0..data.len());
+                    let end = (start + rng.gen_range(3..400)).min(data.len());
+                    data.extend_from_within(start..end);
+                }
+                _ => (),
+            }
+        }
+        data.truncate(len);
+        data
+    }
+
+    fn deflate(data: &[u8], level: u32) -> Vec<u8> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::new(level));
+        std::io::Write::write_all(&mut encoder, data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_inflate_against_flate2() {
+        let mut rng = StdRng::seed_from_u64(0x0138);
+        for round in 0..64 {
+            let len = match round % 4 {
+                0 => rng.gen_range(0..16),
+                1 => rng.gen_range(0..1024),
+                2 => rng.gen_range(0..70_000),
+                _ => rng.gen_range(0..200_000),
+            };
+            let data = sample(&mut rng, len);
+            for level in [0, 1, 6, 9] {
+                let compressed = deflate(&data, level);
+                let (inflated, consumed) = inflate(&compressed).unwrap();
+                assert!(inflated == data, "round {} level {} len {}", round, level, len);
+                assert_eq!(consumed, compressed.len());
+            }
+
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            std::io::Write::write_all(&mut encoder, &data).unwrap();
+            assert!(gunzip(&encoder.finish().unwrap()).unwrap() == data);
+        }
+    }
+
+    #[test]
+    fn test_inflate_corrupt_input() {
+        // damaged streams must fail or decode like flate2 does, never panic
+        let mut rng = StdRng::seed_from_u64(0x0139);
+        for _ in 0..2000 {
+            let len = rng.gen_range(0..4096);
+            let data = sample(&mut rng, len);
+            let mut compressed = deflate(&data, rng.gen_range(0..10));
+            match rng.gen_range(0..3) {
+                0 => compressed.truncate(rng.gen_range(0..=compressed.len())),
+                1 => {
+                    for _ in 0..rng.gen_range(1..4) {
+                        let index = rng.gen_range(0..compressed.len());
+                        compressed[index] ^= 1 << rng.gen_range(0..8);
+                    }
+                }
+                _ => compressed = (0..rng.gen_range(0..256)).map(|_| rng.gen::<u8>()).collect(),
+            }
+
+            let mut expected = Vec::new();
+            let reference = std::io::Read::read_to_end(
+                &mut flate2::read::DeflateDecoder::new(compressed.as_slice()),
+                &mut expected,
+            );
+            if let (Ok((inflated, _)), Ok(_)) = (inflate(&compressed), reference) {
+                assert!(inflated == expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_extract() {
+        let rom = std::fs::read("../assets/default_rom/default_rom.gb").unwrap();
+        let gz = std::fs::read("../assets/default_rom/default_rom.gb.gz").unwrap();
+        assert_eq!(extract_rom(&gz).unwrap(), rom);
+
+        // the zip holds a README before the ROM
+        let zip = std::fs::read("../assets/default_rom/default_rom.zip").unwrap();
+        assert_eq!(extract_rom(&zip).unwrap(), rom);
+
+        // plain ROMs pass through
+        assert_eq!(extract_rom(&rom).unwrap(), rom);
+
+        let rom = std::fs::read("../assets/cpu_instrs/cpu_instrs.gb").unwrap();
+        let gz = std::fs::read("../assets/cpu_instrs/cpu_instrs.gb.gz").unwrap();
+        assert_eq!(gunzip(&gz).unwrap(), rom);
+
+        let mut corrupt = gz.clone();
+        let len = corrupt.len();
+        corrupt[len - 8] ^= 0xFF;
+        assert!(matches!(gunzip(&corrupt), Err(RubcError::Archive(_))));
+        assert!(matches!(gunzip(&gz[..len / 2]), Err(RubcError::Archive(_))));
+    }
+
+    #[test]
+    fn test_inflate_blocks() {
+        // a stored block followed by a fixed Huffman block
+        let data = [
+            0x00, 0x03, 0x00, 0xFC, 0xFF, b'a', b'b', b'c', 0x4B, 0x4C, 0x4A, 0x06, 0x00,
+        ];
+        assert_eq!(inflate(&data).unwrap().0, b"abcabc");
+        assert!(inflate(&[0x07]).is_err());
+    }
+
+    #[test]
+    fn test_truncated_gzip_header() {
+        for len in 2..10 {
+            let gz = [0x1F, 0x8B, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03];
+            assert!(matches!(gunzip(&gz[..len]), Err(RubcError::Archive(_))));
+            assert!(matches!(Cartridge::from_bytes(&gz[..len]), Err(RubcError::Archive(_))));
+        }
+    }
+
+    // a fixed Huffman block of one literal repeated with length 258 at distance 1
+    fn repeated_literal(repeats: usize) -> Vec<u8> {
+        let mut bits = vec![true, true, false];
+        let mut code = |value: u32, len: u32| bits.extend((0..len).rev().map(|i| value >> i & 1 != 0));
+        code(0x30, 8);
+        for _ in 0..repeats {
+            code(0xC5, 8);
+            code(0x00, 5);
+        }
+        code(0x00, 7);
+        bits.chunks(8)
+            .map(|byte| byte.iter().rev().fold(0, |acc, bit| acc << 1 | *bit as u8))
+            .collect()
+    }
+
+    #[test]
+    fn test_inflate_size_limit() {
+        // output is capped at the largest ROM size, 8 MiB
+        let repeats = ((8 << 20) - 1) / 258;
+        assert_eq!(inflate(&repeated_literal(repeats)).unwrap().0.len(), 1 + repeats * 258);
+        assert!(matches!(
+            inflate(&repeated_literal(repeats + 1)),
+            Err(RubcError::Archive(_))
+        ));
+    }
+
+    #[test]
+    fn test_cartridge_from_archive() {
+        let cart = Cartridge::new("../assets/cpu_instrs/cpu_instrs.gb.gz").unwrap();
+        assert_eq!(cart.rom_banks(), 4);
+
+        let zip = std::fs::read("../assets/default_rom/default_rom.zip").unwrap();
+        let cart = Cartridge::from_bytes_with_policy(&zip, ValidationPolicy::Warn).unwrap();
+        assert_eq!(cart.header().unwrap().title, "NO-ROM");
+
+        assert!(Cartridge::from_bytes(&[0u8; 0x10]).is_err());
+    }
+}
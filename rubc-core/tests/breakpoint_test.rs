@@ -0,0 +1,127 @@
+#[cfg(test)]
+mod tests {
+    use rubc_core::breakpoints::*;
+    use rubc_core::expr::Expr;
+    use rubc_core::mbc::DummyMBC;
+    use rubc_core::{cartridge, gameboy};
+
+    fn setup(program: &[u8], breakpoints: Vec<Breakpoint>) -> gameboy::Gameboy {
+        let mut gb = gameboy::GameboyBuilder::new()
+            .enable_test_mode()
+            .with_breakpoints(breakpoints)
+            .build()
+            .unwrap();
+        gb.cart = cartridge::Cartridge::DummyMBC(DummyMBC::new());
+        for (i, byte) in program.iter().enumerate() {
+            gb.memory_write(0x0100 + i as u16, *byte);
+        }
+        gb
+    }
+
+    fn eval(gb: &gameboy::Gameboy, s: &str) -> i64 {
+        s.parse::<Expr>().unwrap().eval(gb)
+    }
+
+    #[test]
+    fn test_expressions() {
+        let mut gb = setup(&[], vec![]);
+        gb.cpu.a = 0x12;
+        gb.cpu.h = 0xC0;
+        gb.cpu.l = 0x10;
+        gb.memory_write(0xC010, 0xFF);
+
+        assert_eq!(eval(&gb, "A"), 0x12);
+        assert_eq!(eval(&gb, "HL"), 0xC010);
+        assert_eq!(eval(&gb, "[HL]"), 0xFF);
+        assert_eq!(eval(&gb, "[$C000 + $10] == $FF"), 1);
+        assert_eq!(eval(&gb, "PC==$0100 && A>=$10"), 1);
+        assert_eq!(eval(&gb, "PC==0x0101 || A<16"), 0);
+        assert_eq!(eval(&gb, "!(A == 18)"), 0);
+        assert_eq!(eval(&gb, "A & %1111"), 2);
+        assert_eq!(eval(&gb, "1 + 2 == 3 && 4 - 1 == 3"), 1);
+        assert_eq!(eval(&gb, "ZF && CF && bank == 0"), 1);
+
+        assert!("A ==".parse::<Expr>().is_err());
+        assert!("[A".parse::<Expr>().is_err());
+        assert!("foo == 1".parse::<Expr>().is_err());
+        assert!("A # 1".parse::<Expr>().is_err());
+
+        let resolve = |name: &str| match name {
+            "Main.loop" => Some(0x01AB),
+            _ => None,
+        };
+        let e = Expr::parse_with("PC == Main.loop", &resolve).unwrap();
+        assert_eq!(e, "PC == $01AB".parse().unwrap());
+    }
+
+    #[test]
+    fn test_parse() {
+        let b: Breakpoint = "0150".parse().unwrap();
+        assert_eq!(b, Breakpoint::at(0x0150));
+
+        let b: Breakpoint = "01:4000-01:4010 if A>=$10 after 3 do log+pause".parse().unwrap();
+        assert_eq!(
+            b,
+            Breakpoint::range(0x4000, 0x4010)
+                .with_bank(1)
+                .with_condition("A >= $10".parse().unwrap())
+                .with_after(3)
+                .with_actions(vec![BreakAction::Log, BreakAction::Pause])
+        );
+        assert_eq!(b.to_string(), "01:4000-01:4010 if (A >= $10) after 3 do log+pause");
+
+        let b: Breakpoint = "PC==$0150 && A>=$10 && [$C000]==$FF && bank==3".parse().unwrap();
+        assert_eq!(b.range, None);
+        assert!(b.condition.is_some());
+
+        let b: Breakpoint = "if A == 1 do dump".parse().unwrap();
+        assert_eq!(b.actions, vec![BreakAction::Dump]);
+
+        assert!("".parse::<Breakpoint>().is_err());
+        assert!("0150 do nothing".parse::<Breakpoint>().is_err());
+        assert!("0150 after x".parse::<Breakpoint>().is_err());
+        assert!("A==1 if B==2".parse::<Breakpoint>().is_err());
+    }
+
+    #[test]
+    fn test_pause_and_resume() {
+        // INC A; INC A; INC A; JP $0100
+        let program = [0x3C, 0x3C, 0x3C, 0xC3, 0x00, 0x01];
+        let b: Breakpoint = "0102 do pause".parse().unwrap();
+        let mut gb = setup(&program, vec![b]);
+
+        gb.tick().unwrap();
+        gb.tick().unwrap();
+        assert_eq!(gb.take_break_hit(), None);
+
+        assert_eq!(gb.tick().unwrap(), 0);
+        let hit = gb.take_break_hit().unwrap();
+        assert_eq!((hit.index, hit.pc, hit.hits), (0, 0x0102, 1));
+        assert_eq!(gb.cpu.a, 0x03);
+
+        gb.tick().unwrap();
+        assert_eq!(gb.take_break_hit(), None);
+        assert_eq!(gb.cpu.pc, 0x0103);
+        assert_eq!(gb.cpu.a, 0x04);
+    }
+
+    #[test]
+    fn test_conditions_and_hit_counts() {
+        // INC A; JP $0100
+        let program = [0x3C, 0xC3, 0x00, 0x01];
+        let b: Breakpoint = "0100 if A >= $08 after 2 do pause".parse().unwrap();
+        let mut gb = setup(&program, vec![b]);
+        gb.cpu.a = 0;
+
+        let mut ticks = 0;
+        while gb.take_break_hit().is_none() {
+            gb.tick().unwrap();
+            ticks += 1;
+            assert!(ticks < 100);
+        }
+
+        // A reached 8 on the first pass, the breakpoint fires on the second
+        assert_eq!(gb.cpu.a, 0x09);
+        assert_eq!(gb.breakpoints()[0].hits(), 2);
+    }
+}
@@ -0,0 +1,133 @@
+#[cfg(test)]
+mod tests {
+    use rubc_core::cartridge::Cartridge;
+    use rubc_core::gameboy::{Gameboy, GameboyBuilder, PpuMode};
+    use rubc_core::globals::*;
+    use rubc_core::mbc::DummyMBC;
+
+    #[test]
+    fn test_echo_ram() {
+        let mut gb = GameboyBuilder::new().build().unwrap();
+        gb.memory_write(0xC123, 0x42);
+        assert_eq!(gb.memory_read(0xE123), 0x42);
+
+        gb.memory_write(0xFDFF, 0x24);
+        assert_eq!(gb.memory_read(0xDDFF), 0x24);
+        // $DE00-$DFFF has no mirror
+        assert_eq!(gb.memory_read(0xFE00), 0x00);
+    }
+
+    #[test]
+    fn test_unusable_region() {
+        let mut gb = GameboyBuilder::new().build().unwrap();
+        gb.memory_write(0xFEA0, 0x42);
+        gb.memory_write(0xFEFF, 0x42);
+        assert_eq!(gb.memory_read(0xFEA0), 0x00);
+        assert_eq!(gb.memory_read(0xFEFF), 0x00);
+    }
+
+    #[test]
+    fn test_io_read_masks() {
+        let mut gb = GameboyBuilder::new().build().unwrap();
+        for (address, written, read) in [
+            (IO_TAC, 0x05, 0xFD),
+            (IO_IF, 0x01, 0xE1),
+            (IO_P1_JOYP, 0x30, 0xF0),
+            (IO_NR10, 0x00, 0x80),
+            // write-only
+            (IO_NR13, 0x12, 0xFF),
+            (IO_NR11, 0x81, 0xBF),
+            (IO_WAVE_RAM1, 0x5A, 0x5A),
+            (IO_BGP, 0xE4, 0xE4),
+            // unmapped
+            (0xFF03, 0x00, 0xFF),
+            (0xFF7F, 0x00, 0xFF),
+            // CGB only
+            (IO_SVBK, 0x02, 0xFF),
+            (IO_IE, 0x1F, 0x1F),
+        ] {
+            gb.memory_write(address, written);
+            assert_eq!(gb.memory_read(address), read, "{:#06x}", address);
+        }
+
+        // the low bits of STAT are read-only
+        gb.memory_write(IO_STAT, 0xFF);
+        assert_eq!(gb.memory_read(IO_STAT), 0xF8);
+
+        let mut gb = GameboyBuilder::new().enable_cgb_mode().build().unwrap();
+        gb.memory_write(IO_SVBK, 0x02);
+        assert_eq!(gb.memory_read(IO_SVBK), 0xFA);
+        gb.memory_write(IO_SC, 0x00);
+        assert_eq!(gb.memory_read(IO_SC), 0x7C);
+    }
+
+    #[test]
+    fn test_ppu_access_restrictions() {
+        let mut gb = GameboyBuilder::new().build().unwrap();
+        gb.memory_write(0x8000, 0x11);
+        gb.memory_write(0xFE00, 0x22);
+        gb.memory_write(IO_LCDC, 0x80);
+
+        gb.set_frame_cycles(CYCLES_PER_SCANLINE + 40);
+        assert_eq!(gb.ppu_mode(), PpuMode::OamScan);
+        assert_eq!(gb.memory_read(0x8000), 0x11);
+        assert_eq!(gb.memory_read(0xFE00), 0xFF);
+        gb.memory_write(0xFE00, 0x33);
+        assert_eq!(gb.debug_read(0xFE00), 0x22);
+
+        gb.set_frame_cycles(CYCLES_PER_SCANLINE + 100);
+        assert_eq!(gb.memory_read(0x8000), 0xFF);
+        assert_eq!(gb.memory_read(0xFE9F), 0xFF);
+        gb.memory_write(0x8000, 0x33);
+        assert_eq!(gb.debug_read(0x8000), 0x11);
+
+        gb.set_frame_cycles(CYCLES_PER_SCANLINE + 300);
+        assert_eq!(gb.memory_read(0x8000), 0x11);
+        assert_eq!(gb.memory_read(0xFE00), 0x22);
+        gb.set_frame_cycles(150 * CYCLES_PER_SCANLINE + 40);
+        assert_eq!(gb.memory_read(0xFE00), 0x22);
+
+        // everything is accessible while the LCD is off
+        gb.set_frame_cycles(100);
+        gb.memory_write(IO_LCDC, 0x00);
+        assert_eq!(gb.memory_read(0x8000), 0x11);
+
+        let mut gb = GameboyBuilder::new().disable_access_restrictions().build().unwrap();
+        gb.memory_write(IO_LCDC, 0x80);
+        gb.set_frame_cycles(100);
+        gb.memory_write(0x8000, 0x44);
+        assert_eq!(gb.memory_read(0x8000), 0x44);
+    }
+
+    #[test]
+    fn test_lcd_status() {
+        let mut gb = GameboyBuilder::new().build().unwrap();
+        gb.cart = Cartridge::DummyMBC(DummyMBC::new());
+        gb.memory_write(IO_LCDC, 0x80);
+        let status = |gb: &Gameboy| (gb.memory_read(IO_LY), gb.memory_read(IO_STAT) & 0x03);
+
+        // NOPs through the end of a line
+        gb.set_frame_cycles(2 * CYCLES_PER_SCANLINE - 4);
+        assert_eq!(status(&gb), (1, PpuMode::HBlank as u8));
+        gb.tick().unwrap();
+        assert_eq!(status(&gb), (2, PpuMode::OamScan as u8));
+        for _ in 0..20 {
+            gb.tick().unwrap();
+        }
+        assert_eq!(status(&gb), (2, PpuMode::PixelTransfer as u8));
+
+        // VBlank, then the frame wraps
+        gb.set_frame_cycles(CYCLES_PER_FRAME - 4);
+        assert_eq!(status(&gb), (153, PpuMode::VBlank as u8));
+        gb.tick().unwrap();
+        assert_eq!(status(&gb), (0, PpuMode::OamScan as u8));
+
+        // the mode bits stay read-only
+        gb.memory_write(IO_STAT, 0x03);
+        assert_eq!(gb.memory_read(IO_STAT) & 0x03, PpuMode::OamScan as u8);
+
+        gb.memory_write(IO_LCDC, 0x00);
+        gb.set_frame_cycles(CYCLES_PER_FRAME - 4);
+        assert_eq!(status(&gb), (0, PpuMode::HBlank as u8));
+    }
+}
@@ -0,0 +1,135 @@
+#[cfg(test)]
+mod tests {
+    use rubc_core::callstack::*;
+    use rubc_core::mbc::DummyMBC;
+    use rubc_core::symbols::SymbolTable;
+    use rubc_core::{cartridge, gameboy, RubcError};
+
+    fn setup(code: &[(u16, &[u8])]) -> gameboy::Gameboy {
+        let mut symbols = SymbolTable::new();
+        symbols.insert(0, 0x0110, "Outer");
+        symbols.insert(0, 0x0130, "Crash");
+
+        let mut gb = gameboy::GameboyBuilder::new()
+            .enable_test_mode()
+            .with_symbols(symbols)
+            .build()
+            .unwrap();
+        gb.cart = cartridge::Cartridge::DummyMBC(DummyMBC::new());
+        for (address, bytes) in code {
+            for (i, byte) in bytes.iter().enumerate() {
+                gb.memory_write(address + i as u16, *byte);
+            }
+        }
+        gb
+    }
+
+    #[test]
+    fn test_backtrace_on_illegal_opcode() {
+        let mut gb = setup(&[
+            // call Outer
+            (0x0100, &[0xCD, 0x10, 0x01]),
+            // Outer: call $0120
+            (0x0110, &[0xCD, 0x20, 0x01]),
+            // pop hl discards the return address; call Crash
+            (0x0120, &[0xE1, 0xCD, 0x30, 0x01]),
+            // Crash: an illegal opcode
+            (0x0130, &[0xD3]),
+        ]);
+
+        for _ in 0..4 {
+            gb.tick().unwrap();
+        }
+        let frames = gb.call_stack().frames();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].kind, FrameKind::Call);
+        assert_eq!((frames[0].caller, frames[0].target, frames[0].return_addr), (0x0100, 0x0110, 0x0103));
+        assert_eq!((frames[1].caller, frames[1].target, frames[1].sp), (0x0121, 0x0130, 0xFFFA));
+        assert_eq!(gb.backtrace(), "#0  00:0130 <Crash>\n#1  00:0121 <Outer+17>\n#2  00:0100\n");
+
+        let error = gb.tick().unwrap_err();
+        assert!(matches!(error.cause(), RubcError::IllegalOpcode { pc: 0x0130, op: 0xD3 }));
+        let error = error.to_string();
+        assert!(error.starts_with("Illegal opcode 0xd3 at 0x0130\n"));
+        assert!(error.contains("Backtrace:\n#0  00:0130 <Crash>\n#1  00:0121 <Outer+17>\n"));
+        assert!(error.contains("00:0120  E1        pop hl\n"));
+        assert!(error.contains("00:0121  CD 30 01  call $0130\n"));
+        assert!(error.ends_with("00:0130  D3        db $D3      ; Crash\n"));
+    }
+
+    #[test]
+    fn test_backtrace_when_stuck() {
+        // call Outer; Outer: jr Outer
+        let mut gb = gameboy::GameboyBuilder::new().enable_test_mode().panic_on_stuck().build().unwrap();
+        gb.cart = cartridge::Cartridge::DummyMBC(DummyMBC::new());
+        for (i, byte) in [0xCD, 0x10, 0x01].iter().enumerate() {
+            gb.memory_write(0x0100 + i as u16, *byte);
+        }
+        gb.memory_write(0x0110, 0x18);
+        gb.memory_write(0x0111, 0xFE);
+
+        gb.tick().unwrap();
+        let error = gb.tick().unwrap_err();
+        assert!(matches!(error.cause(), RubcError::Stuck { pc: 0x0110 }));
+        let error = error.to_string();
+        assert!(error.starts_with("Stuck CPU at 0x0110\n"));
+        assert!(error.contains("Backtrace:\n#0  00:0110\n#1  00:0100\n"));
+        assert!(gb.cpu.is_stuck);
+    }
+
+    #[test]
+    fn test_pc_and_sp_wrap() {
+        // ld a, $42 with its operand wrapped around to $0000
+        let mut gb = setup(&[(0xFFFF, &[0x3E]), (0x0000, &[0x42])]);
+        gb.cpu.pc = 0xFFFF;
+        gb.tick().unwrap();
+        assert_eq!((gb.cpu.a, gb.cpu.pc), (0x42, 0x0001));
+
+        // an interrupt pushes the return address across the top of memory
+        let mut gb = setup(&[(0x0100, &[0x00])]);
+        gb.cpu.sp = 0x0001;
+        gb.memory_write(0xFFFF, 0x01);
+        gb.memory_write(0xFF0F, 0x01);
+        gb.interrupts_on = true;
+        gb.tick().unwrap();
+        assert_eq!((gb.cpu.pc, gb.cpu.sp), (0x0040, 0xFFFF));
+        assert_eq!(gb.debug_read(0xFFFF), 0x01);
+    }
+
+    #[test]
+    fn test_unwinding() {
+        let frame = |kind, target, sp| StackFrame {
+            kind,
+            bank: 0,
+            target,
+            caller_bank: 0,
+            caller: 0x0150,
+            return_addr: 0x0153,
+            sp,
+        };
+
+        let mut call_stack = CallStack::new();
+        call_stack.push(frame(FrameKind::Call, 0x0200, 0xDFFC));
+        call_stack.push(frame(FrameKind::Rst, 0x0038, 0xDFFA));
+        call_stack.push(frame(FrameKind::Interrupt, 0x0040, 0xDFF8));
+
+        // pushes inside the handler keep every frame
+        call_stack.unwind(0xDFF6);
+        assert_eq!(call_stack.depth(), 3);
+        // RETI
+        call_stack.unwind(0xDFFA);
+        assert_eq!(call_stack.depth(), 2);
+        // `add sp, 4` throws away both remaining return addresses
+        call_stack.unwind(0xDFFE);
+        assert_eq!(call_stack.depth(), 0);
+
+        for pc in 0..(HISTORY_LEN as u16 + 8) {
+            call_stack.record(0, pc, [0x00, 0x00, 0x00]);
+        }
+        assert_eq!(call_stack.history().count(), HISTORY_LEN);
+        assert_eq!(call_stack.history().next().unwrap().pc, 8);
+
+        call_stack.reset();
+        assert_eq!(call_stack.history().count(), 0);
+    }
+}
@@ -0,0 +1,139 @@
+#[cfg(test)]
+mod tests {
+    use rubc_core::cartridge::Cartridge;
+    use rubc_core::gameboy::{Gameboy, GameboyBuilder};
+    use rubc_core::globals::*;
+    use rubc_core::mbc::{CAMERA_HEIGHT, CAMERA_WIDTH};
+    use rubc_core::validation::ValidationPolicy;
+    use rubc_core::RubcError;
+
+    // POCKET CAMERA with 64 ROM banks tagged with their number and 16 RAM banks
+    fn setup() -> Gameboy {
+        let mut rom = vec![0u8; 64 * ROM_BANK_SIZE];
+        for bank in 0..64 {
+            rom[bank * ROM_BANK_SIZE + 0x1000] = bank as u8;
+        }
+        rom[CART_TYPE as usize] = 0xFC;
+        rom[CART_ROM_SIZE as usize] = 0x05;
+        rom[CART_SRAM_SIZE as usize] = 0x04;
+        let cart = Cartridge::from_bytes_with_policy(&rom, ValidationPolicy::Ignore).unwrap();
+        GameboyBuilder::new().set_cart(cart).build().unwrap()
+    }
+
+    // unity gain and exposure with the same thresholds across the matrix
+    fn set_registers(gb: &mut Gameboy, exposure: u16, thresholds: [u8; 3]) {
+        gb.memory_write(0x4000, 0x10);
+        gb.memory_write(0xA001, 0x04);
+        gb.memory_write(0xA002, (exposure >> 8) as u8);
+        gb.memory_write(0xA003, exposure as u8);
+        for (i, threshold) in thresholds.iter().cycle().take(48).enumerate() {
+            gb.memory_write(0xA006 + i as u16, *threshold);
+        }
+    }
+
+    fn capture(gb: &mut Gameboy) {
+        gb.memory_write(0x4000, 0x10);
+        gb.memory_write(0xA000, 0x01);
+        assert_eq!(gb.memory_read(0xA000), 0x00);
+        gb.memory_write(0x4000, 0x00);
+    }
+
+    // both bitplanes of a row of a tile in the captured image
+    fn tile_row(gb: &mut Gameboy, tile: u16, row: u16) -> (u8, u8) {
+        let address = 0xA100 + tile * 16 + row * 2;
+        (gb.memory_read(address), gb.memory_read(address + 1))
+    }
+
+    #[test]
+    fn test_banking() {
+        let mut gb = setup();
+        assert_eq!(gb.memory_read(0x5000), 1);
+        gb.memory_write(0x2000, 0x3F);
+        assert_eq!(gb.memory_read(0x5000), 0x3F);
+        // bank 0 can be mapped at 0x4000
+        gb.memory_write(0x2000, 0x00);
+        assert_eq!(gb.memory_read(0x5000), 0);
+
+        // RAM can be read but not written without the enable
+        gb.memory_write(0x4000, 0x0F);
+        gb.memory_write(0xA000, 0x42);
+        assert_eq!(gb.memory_read(0xA000), 0x00);
+        gb.memory_write(0x0000, 0x0A);
+        gb.memory_write(0xA000, 0x42);
+        assert_eq!(gb.memory_read(0xA000), 0x42);
+        assert_eq!(gb.cart.bank(0xA000), 15);
+
+        // the registers replace RAM, only 0xA000 reads back
+        gb.memory_write(0x4000, 0x10);
+        assert_eq!(gb.memory_read(0xA000), 0x00);
+        gb.memory_write(0xA000, 0x06);
+        gb.memory_write(0xA001, 0xE4);
+        assert_eq!(gb.memory_read(0xA000), 0x06);
+        assert_eq!(gb.memory_read(0xA080), 0x06);
+        assert_eq!(gb.memory_read(0xA001), 0x00);
+        gb.memory_write(0x4000, 0x0F);
+        assert_eq!(gb.memory_read(0xA000), 0x42);
+    }
+
+    #[test]
+    fn test_capture() {
+        let mut gb = setup();
+        gb.set_camera_image(&[0x80; CAMERA_WIDTH * CAMERA_HEIGHT]).unwrap();
+        set_registers(&mut gb, 0x1000, [0x40, 0x80, 0xC0]);
+        capture(&mut gb);
+        assert_eq!(tile_row(&mut gb, 0, 0), (0xFF, 0x00));
+        assert_eq!(tile_row(&mut gb, 16 * 14 - 1, 7), (0xFF, 0x00));
+
+        // inverted 0x80 falls below the middle threshold
+        gb.memory_write(0x4000, 0x10);
+        gb.memory_write(0xA004, 0x08);
+        capture(&mut gb);
+        assert_eq!(tile_row(&mut gb, 0, 0), (0x00, 0xFF));
+
+        // a horizontal gradient at half exposure
+        let image: Vec<u8> = (0..CAMERA_WIDTH * CAMERA_HEIGHT)
+            .map(|i| (i % CAMERA_WIDTH * 2) as u8)
+            .collect();
+        gb.set_camera_image(&image).unwrap();
+        set_registers(&mut gb, 0x0800, [0x20, 0x40, 0x60]);
+        gb.memory_write(0xA004, 0x00);
+        capture(&mut gb);
+        assert_eq!(tile_row(&mut gb, 0, 0), (0xFF, 0xFF));
+        assert_eq!(tile_row(&mut gb, 4, 3), (0x00, 0xFF));
+        assert_eq!(tile_row(&mut gb, 8 + 16, 0), (0xFF, 0x00));
+        assert_eq!(tile_row(&mut gb, 15, 7), (0x00, 0x00));
+
+        // a vertical edge between tiles 7 and 8
+        let image: Vec<u8> = (0..CAMERA_WIDTH * CAMERA_HEIGHT)
+            .map(|i| if i % CAMERA_WIDTH < 64 { 0x50 } else { 0xB0 })
+            .collect();
+        gb.set_camera_image(&image).unwrap();
+        set_registers(&mut gb, 0x1000, [0x40, 0x80, 0xC0]);
+        capture(&mut gb);
+        assert_eq!(tile_row(&mut gb, 7, 0), (0x00, 0xFF));
+        assert_eq!(tile_row(&mut gb, 8, 0), (0xFF, 0x00));
+
+        // vertical enhancement leaves it be, horizontal enhancement sharpens it
+        gb.memory_write(0x4000, 0x10);
+        gb.memory_write(0xA001, 0x44);
+        gb.memory_write(0xA004, 0x20);
+        capture(&mut gb);
+        assert_eq!(tile_row(&mut gb, 7, 0), (0x00, 0xFF));
+        gb.memory_write(0x4000, 0x10);
+        gb.memory_write(0xA001, 0x24);
+        capture(&mut gb);
+        assert_eq!(tile_row(&mut gb, 7, 0), (0x01, 0xFF));
+        assert_eq!(tile_row(&mut gb, 8, 0), (0x7F, 0x00));
+    }
+
+    #[test]
+    fn test_image_and_saves() {
+        let mut gb = setup();
+        assert!(matches!(
+            gb.set_camera_image(&[0; 16]),
+            Err(RubcError::SizeMismatch { actual: 16, .. })
+        ));
+        assert!(gb.cart.has_battery());
+        assert_eq!(gb.cart.save_data().unwrap().len(), 16 * RAM_BANK_SIZE);
+    }
+}
@@ -0,0 +1,155 @@
+#[cfg(test)]
+mod tests {
+    use rubc_core::cartridge::Cartridge;
+    use rubc_core::gameboy::GameboyBuilder;
+    use rubc_core::globals::*;
+    use rubc_core::mbc::{IntoMBC, MBC1};
+    use rubc_core::validation::{self, ValidationPolicy, NINTENDO_LOGO};
+    use rubc_core::{header, RubcError};
+
+    fn rom(cart_type: u8, rom_size: u8, ram_size: u8, banks: usize) -> Vec<u8> {
+        let mut rom = vec![0u8; banks * ROM_BANK_SIZE];
+        rom[CART_TYPE as usize] = cart_type;
+        rom[CART_ROM_SIZE as usize] = rom_size;
+        rom[CART_SRAM_SIZE as usize] = ram_size;
+        rom[CART_NINTENDO_LOGO_START as usize..=CART_NINTENDO_LOGO_END as usize].copy_from_slice(&NINTENDO_LOGO);
+        rom[CART_HEADER_CHECKSUM as usize] = header::header_checksum(&rom);
+        with_global_checksum(rom)
+    }
+
+    fn with_global_checksum(mut rom: Vec<u8>) -> Vec<u8> {
+        let global = validation::global_checksum(&rom).to_be_bytes();
+        rom[CART_GLOBAL_CHECKSUM_START as usize..=CART_GLOBAL_CHECKSUM_END as usize].copy_from_slice(&global);
+        rom
+    }
+
+    fn load(name: &str, rom: &[u8]) -> rubc_core::Result<Cartridge> {
+        load_with(name, rom, ValidationPolicy::Strict)
+    }
+
+    fn load_with(name: &str, rom: &[u8], policy: ValidationPolicy) -> rubc_core::Result<Cartridge> {
+        let path = std::env::temp_dir().join(format!("rubc_cartridge_test_{}.gb", name));
+        std::fs::write(&path, rom).unwrap();
+        let cart = Cartridge::load(path.to_str().unwrap(), policy);
+        std::fs::remove_file(&path).unwrap();
+        cart
+    }
+
+    #[test]
+    fn test_load() {
+        let cart = load("mbc1", &rom(0x03, 0x02, 0x03, 8)).unwrap();
+        assert_eq!(cart.rom_banks(), 8);
+        assert_eq!(cart.ram_banks(), 4);
+
+        // the default ROM has no Nintendo logo
+        let cart = Cartridge::load("../assets/default_rom/default_rom.gb", ValidationPolicy::Warn).unwrap();
+        assert_eq!(cart.rom_banks(), 1);
+    }
+
+    #[test]
+    fn test_invalid_roms() {
+        assert!(matches!(
+            Cartridge::new("../assets/missing.gb"),
+            Err(RubcError::Io(_))
+        ));
+        assert!(matches!(
+            load("short", &[0u8; 0x100]),
+            Err(RubcError::SizeMismatch { actual: 0x100, .. })
+        ));
+        assert!(matches!(
+            load("rom_size", &rom(0x00, 0x09, 0x00, 2)),
+            Err(RubcError::InvalidHeader { field: "ROM size", value: 0x09 })
+        ));
+        assert!(matches!(
+            load("ram_size", &rom(0x00, 0x00, 0x06, 2)),
+            Err(RubcError::InvalidHeader { field: "RAM size", value: 0x06 })
+        ));
+        assert!(matches!(
+            load("truncated", &rom(0x01, 0x02, 0x00, 4)),
+            Err(RubcError::SizeMismatch {
+                expected: 0x20000,
+                actual: 0x10000
+            })
+        ));
+        // ROM only carts cannot map more than two banks
+        assert!(matches!(
+            load("mbc0", &rom(0x00, 0x01, 0x00, 4)),
+            Err(RubcError::InvalidHeader { field: "ROM size", value: 0x01 })
+        ));
+        assert!(matches!(
+            load("mbc1_size", &rom(0x01, 0x07, 0x00, 256)),
+            Err(RubcError::InvalidHeader { field: "ROM size", value: 0x07 })
+        ));
+        assert!(matches!(
+            load("mapper", &rom(0xFD, 0x00, 0x00, 2)),
+            Err(RubcError::UnsupportedMapper(0xFD))
+        ));
+
+        let mut bad_checksum = rom(0x00, 0x00, 0x00, 2);
+        bad_checksum[CART_HEADER_CHECKSUM as usize] ^= 0xFF;
+        match load("checksum", &bad_checksum) {
+            Err(error @ RubcError::ChecksumMismatch { .. }) => {
+                assert!(error.to_string().starts_with("Header checksum mismatch"))
+            }
+            _ => panic!("expected a checksum mismatch"),
+        }
+    }
+
+    #[test]
+    fn test_validation_policy() {
+        let mut bad_logo = rom(0x00, 0x00, 0x00, 2);
+        bad_logo[CART_NINTENDO_LOGO_END as usize] = 0x00;
+        assert!(matches!(
+            load("logo", &with_global_checksum(bad_logo.clone())),
+            Err(RubcError::InvalidLogo { top_half_valid: true })
+        ));
+
+        let mut bad_global = rom(0x00, 0x00, 0x00, 2);
+        bad_global[0x200] = 0x42;
+        assert!(matches!(
+            load("global", &bad_global),
+            Err(RubcError::GlobalChecksumMismatch { .. })
+        ));
+
+        // homebrew with a bad header checksum loads deliberately
+        let mut bad_checksum = rom(0x00, 0x00, 0x00, 2);
+        bad_checksum[CART_HEADER_CHECKSUM as usize] ^= 0xFF;
+        assert!(load_with("warn", &bad_checksum, ValidationPolicy::Warn).is_ok());
+        assert!(load_with("ignore", &bad_checksum, ValidationPolicy::Ignore).is_ok());
+
+        // by default only what the boot ROM checks is fatal
+        assert!(matches!(
+            Cartridge::from_bytes(&bad_checksum),
+            Err(RubcError::ChecksumMismatch { .. })
+        ));
+        assert!(matches!(
+            Cartridge::from_bytes(&with_global_checksum(bad_logo)),
+            Err(RubcError::InvalidLogo { .. })
+        ));
+        assert!(Cartridge::from_bytes(&bad_global).is_ok());
+
+        // the policy applies whether it is set before or after the cart
+        let path = std::env::temp_dir().join("rubc_cartridge_test_builder.gb");
+        std::fs::write(&path, &bad_checksum).unwrap();
+        let builder = || GameboyBuilder::new().with_cart(path.to_str().unwrap()).unwrap();
+        assert!(matches!(builder().build(), Err(RubcError::ChecksumMismatch { .. })));
+        assert!(builder().with_validation_policy(ValidationPolicy::Warn).build().is_ok());
+        std::fs::remove_file(&path).unwrap();
+
+        // validation does not excuse a broken header
+        assert!(load_with("ignore_size", &rom(0x00, 0x09, 0x00, 2), ValidationPolicy::Ignore).is_err());
+    }
+
+    #[test]
+    fn test_mbc1_without_ram() {
+        let mut mbc = MBC1::new(4, 0);
+        // enable RAM and select bank 3 in RAM banking mode
+        mbc.write(0x0000, 0x0A);
+        mbc.write(0x4000, 0x03);
+        mbc.write(0x6000, 0x01);
+
+        mbc.write_sram(0x1FFF, 0x42);
+        assert_eq!(mbc.read_sram(0x1FFF), 0xFF);
+        assert_eq!(mbc.read(0x8000), 0xFF);
+    }
+}
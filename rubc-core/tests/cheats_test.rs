@@ -0,0 +1,138 @@
+#[cfg(test)]
+mod tests {
+    use rubc_core::cheats::*;
+    use rubc_core::globals::*;
+    use rubc_core::mbc::DummyMBC;
+    use rubc_core::{cartridge, gameboy};
+
+    fn setup(cheats: Cheats) -> gameboy::Gameboy {
+        let mut gb = gameboy::GameboyBuilder::new().with_cheats(cheats).build().unwrap();
+        gb.cart = cartridge::Cartridge::DummyMBC(DummyMBC::new());
+        gb
+    }
+
+    #[test]
+    fn test_parse() {
+        let cheat = "3e1-23b-fae".parse::<Cheat>().unwrap();
+        assert_eq!(cheat.code, "3E1-23B-FAE");
+        assert_eq!(
+            cheat.kind,
+            CheatCode::GameGenie {
+                address: 0x4123,
+                value: 0x3E,
+                compare: Some(0x05)
+            }
+        );
+        assert_eq!(
+            "3E123B".parse::<Cheat>().unwrap().kind,
+            CheatCode::GameGenie {
+                address: 0x4123,
+                value: 0x3E,
+                compare: None
+            }
+        );
+
+        assert_eq!(
+            "0163D2C0".parse::<Cheat>().unwrap().kind,
+            CheatCode::GameShark {
+                bank: None,
+                address: 0xC0D2,
+                value: 0x63
+            }
+        );
+        assert_eq!(
+            "92FF00D0".parse::<Cheat>().unwrap().kind,
+            CheatCode::GameShark {
+                bank: Some(2),
+                address: 0xD000,
+                value: 0xFF
+            }
+        );
+
+        for invalid in ["", "3E1-23B-FA", "XYZ-123-456", "0163D2", "0163D240", "A163D2C0"] {
+            assert!(invalid.parse::<Cheat>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_cheat_file() {
+        let contents = "# lives\n+ 3E1-23B-FAE Infinite lives\n- 0163D2C0\n\n0100C0C0 Max health\n";
+        let cheats = Cheats::parse(contents).unwrap();
+        assert_eq!(cheats.len(), 3);
+        assert_eq!(cheats.cheats()[0].name, "Infinite lives");
+        assert!(!cheats.cheats()[1].enabled);
+        assert!(cheats.cheats()[2].enabled);
+        assert_eq!(
+            cheats.to_string(),
+            "+ 3E1-23B-FAE Infinite lives\n- 0163D2C0\n+ 0100C0C0 Max health\n"
+        );
+        assert_eq!(Cheats::parse(&cheats.to_string()).unwrap(), cheats);
+
+        assert!(Cheats::parse("+ 3E1-23B-FAE\n+ nonsense").is_err());
+        assert_eq!(
+            Cheats::sidecar_path("game.gb"),
+            std::path::PathBuf::from("game.gb.cht")
+        );
+    }
+
+    #[test]
+    fn test_load_for_rom() {
+        let rom = std::env::temp_dir().join("rubc_cheats_test.gb");
+        let path = Cheats::sidecar_path(&rom);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(Cheats::load_for_rom(&rom).unwrap(), None);
+
+        std::fs::write(&path, "+ 0100C0C0 Max health\n").unwrap();
+        assert_eq!(Cheats::load_for_rom(&rom).unwrap().unwrap().len(), 1);
+
+        // a broken file is an error, not an empty list that would be saved over it
+        std::fs::write(&path, "+ 0100C0C0 Max health\n+ nonsense\n").unwrap();
+        assert!(Cheats::load_for_rom(&rom).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_game_genie() {
+        let mut cheats = Cheats::new();
+        // $42 at $0200 while the original byte is $00
+        cheats.add("422-00F-E6A".parse().unwrap());
+        // $24 at $0201 while the original byte is $05, which it is not
+        cheats.add("242-01F-FAE".parse().unwrap());
+        let mut gb = setup(cheats);
+
+        assert_eq!(gb.debug_read(0x0200), 0x42);
+        assert_eq!(gb.memory_read(0x0200), 0x42);
+        assert_eq!(gb.debug_read(0x0201), 0x00);
+        assert_eq!(gb.cart.read(0x0200), 0x00);
+
+        assert!(gb.cheats_mut().set_enabled(0, false));
+        assert_eq!(gb.debug_read(0x0200), 0x00);
+        assert!(!gb.cheats_mut().set_enabled(5, false));
+    }
+
+    #[test]
+    fn test_gameshark() {
+        let mut cheats = Cheats::new();
+        cheats.add("0163D2C0".parse().unwrap());
+        // only while WRAM bank 2 is mapped
+        cheats.add("92FF00D0".parse().unwrap());
+        let mut gb = setup(cheats);
+
+        gb.memory_write(0xC0D2, 0x01);
+        let mut cycles = 0;
+        while cycles < CYCLES_PER_FRAME {
+            assert_eq!(gb.debug_read(0xC0D2), 0x01);
+            cycles += gb.tick().unwrap();
+        }
+        assert_eq!(gb.debug_read(0xC0D2), 0x63);
+        assert_eq!(gb.debug_read(0xD000), 0x00);
+
+        // the game writes over it, the next frame restores it
+        gb.memory_write(0xC0D2, 0x01);
+        gb.cheats_mut().remove(1);
+        while cycles < 2 * CYCLES_PER_FRAME {
+            cycles += gb.tick().unwrap();
+        }
+        assert_eq!(gb.debug_read(0xC0D2), 0x63);
+    }
+}
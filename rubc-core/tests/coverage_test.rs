@@ -0,0 +1,211 @@
+#[cfg(test)]
+mod tests {
+    use log::{Log, Metadata, Record};
+    use rubc_core::breakpoints::Breakpoint;
+    use rubc_core::coverage::{self, Coverage};
+    use rubc_core::disasm::Disassembler;
+    use rubc_core::globals::*;
+    use rubc_core::mbc::DummyMBC;
+    use rubc_core::validation::ValidationPolicy;
+    use rubc_core::{cartridge, gameboy};
+
+    #[test]
+    fn test_recording() {
+        let mut gb = gameboy::GameboyBuilder::new()
+            .enable_test_mode()
+            .with_coverage(Coverage::new())
+            .build()
+            .unwrap();
+        gb.cart = cartridge::Cartridge::DummyMBC(DummyMBC::new());
+
+        // ld a, [$0200]; ld b, $05; swap a; nop
+        let program = [0xFA, 0x00, 0x02, 0x06, 0x05, 0xCB, 0x37, 0x00];
+        for (i, byte) in program.iter().enumerate() {
+            gb.memory_write(0x0100 + i as u16, *byte);
+        }
+        for _ in 0..4 {
+            gb.tick().unwrap();
+        }
+
+        let coverage = gb.take_coverage().unwrap();
+        assert_eq!(coverage.flags(0, 0x0100), coverage::EXECUTED);
+        assert_eq!(coverage.flags(0, 0x0101), coverage::OPERAND);
+        assert_eq!(coverage.flags(0, 0x0102), coverage::OPERAND);
+        assert_eq!(coverage.flags(0, 0x0200), coverage::DATA);
+        assert_eq!(coverage.flags(0, 0x0105), coverage::EXECUTED);
+        assert_eq!(coverage.flags(0, 0x0106), coverage::OPERAND);
+        assert!(coverage.is_executed(0, 0x0107));
+        assert!(!coverage.is_executed(0, 0x0108));
+        assert_eq!(coverage.count(coverage::EXECUTED), 4);
+    }
+
+    struct NullLogger;
+
+    impl Log for NullLogger {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            let _ = format!("{}", record.args());
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: NullLogger = NullLogger;
+
+    #[test]
+    fn test_debugger_reads_not_recorded() {
+        // logged snapshots and dumps read the code around PC and the stack
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let mut gb = gameboy::GameboyBuilder::new()
+            .enable_test_mode()
+            .with_coverage(Coverage::new())
+            .with_breakpoints(vec![
+                "0101 do log".parse::<Breakpoint>().unwrap(),
+                "0102 if [$0200] == 0 do dump".parse::<Breakpoint>().unwrap(),
+            ])
+            .with_watchpoints(vec!["a/FF0F/log".parse().unwrap()])
+            .build()
+            .unwrap();
+        gb.cart = cartridge::Cartridge::DummyMBC(DummyMBC::new());
+        gb.interrupts_on = true;
+        for _ in 0..4 {
+            gb.tick().unwrap();
+        }
+
+        let coverage = gb.take_coverage().unwrap();
+        assert_eq!(coverage.count(coverage::EXECUTED), 4);
+        assert_eq!(coverage.count(coverage::DATA), 0);
+    }
+
+    #[test]
+    fn test_sidecar() {
+        let mut first = Coverage::new();
+        first.bind_rom(&[0x01, 0x02]).unwrap();
+        first.mark(0, 0x0150, coverage::EXECUTED);
+        first.mark(2, 0x4000, coverage::DATA);
+
+        let mut second = Coverage::new();
+        second.mark(0, 0x0150, coverage::OPERAND);
+        second.mark(1, 0x7FFF, coverage::EXECUTED);
+
+        let rom = std::env::temp_dir().join("rubc_coverage_test.gb");
+        let path = Coverage::sidecar_path(&rom);
+        assert_eq!(path.file_name().unwrap(), "rubc_coverage_test.gb.cov");
+
+        first.save(&path).unwrap();
+        let mut loaded = Coverage::load_for_rom(&rom).unwrap();
+        assert_eq!(loaded, first);
+
+        loaded.merge(&second).unwrap();
+        assert_eq!(loaded.flags(0, 0x0150), coverage::EXECUTED | coverage::OPERAND);
+        assert_eq!(loaded.flags(2, 0x4000), coverage::DATA);
+        assert!(loaded.is_executed(1, 0x7FFF));
+        assert_eq!(loaded.executed().collect::<Vec<_>>(), vec![(0, 0x0150), (1, 0x7FFF)]);
+
+        assert_eq!(loaded.rom_crc(), first.rom_crc());
+
+        std::fs::write(&path, b"garbage").unwrap();
+        assert!(Coverage::load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_disassemble_covered_code() {
+        let mut rom = vec![0u8; ROM_BANK_SIZE * 4];
+        // nop; jp $0150
+        rom[0x0100..0x0104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+        // ld a, $01; ret; jp $4000
+        rom[0x0150..0x0156].copy_from_slice(&[0x3E, 0x01, 0xC9, 0xC3, 0x00, 0x40]);
+        rom[3 * ROM_BANK_SIZE..3 * ROM_BANK_SIZE + 2].copy_from_slice(&[0x18, 0xFE]);
+
+        let mut coverage = Coverage::new();
+        for address in [0x0100, 0x0101, 0x0150, 0x0152, 0x0153] {
+            coverage.mark(0, address, coverage::EXECUTED);
+        }
+        coverage.mark(3, 0x4000, coverage::EXECUTED);
+        coverage.mark(2, 0x4000, coverage::DATA);
+
+        // static traversal decodes the fall through, coverage only what ran
+        let disassembly = Disassembler::new(&rom).disassemble();
+        assert!(disassembly.is_code(0, 0x0152));
+        assert!(!disassembly.is_code(3, 0x4000));
+
+        let disassembly = Disassembler::new(&rom).with_coverage(Some(&coverage)).disassemble();
+        assert!(disassembly.is_code(0, 0x0100));
+        assert!(disassembly.is_code(0, 0x0151));
+        assert!(!disassembly.is_code(0, 0x0156));
+        // the bank of the far jump is known from where its target executed
+        assert!(disassembly.is_code(3, 0x4000));
+        assert_eq!(disassembly.label_at(3, 0x4000), Some("Jump_003_4000"));
+
+        let table = disassembly.to_table();
+        assert!(table.contains("jp Jump_003_4000"));
+        assert!(table.contains("data (read)"));
+        assert!(table.contains("data (unreached)"));
+    }
+
+    // 1 MiB MBC1 ROM, large enough for mode 1 to bank $0000-$3FFF
+    fn mbc1_rom() -> Vec<u8> {
+        let mut rom = vec![0u8; 64 * ROM_BANK_SIZE];
+        rom[CART_TYPE as usize] = 0x01;
+        rom[CART_ROM_SIZE as usize] = 0x05;
+        rom
+    }
+
+    #[test]
+    fn test_lower_window_bank() {
+        let cart = cartridge::Cartridge::from_bytes_with_policy(&mbc1_rom(), ValidationPolicy::Ignore).unwrap();
+        let mut gb = gameboy::GameboyBuilder::new()
+            .enable_test_mode()
+            .set_cart(cart)
+            .with_coverage(Coverage::new())
+            .build()
+            .unwrap();
+
+        // mode 1 maps bank $20 at $0000, the nop at $0100 is in that bank
+        gb.memory_write(0x4000, 0x01);
+        gb.memory_write(0x6000, 0x01);
+        assert_eq!(gb.cart.bank(0x0100), 0x20);
+        gb.tick().unwrap();
+
+        let coverage = gb.take_coverage().unwrap();
+        assert!(coverage.is_executed(0x20, 0x0100));
+        assert!(!coverage.is_executed(0, 0x0100));
+        assert_eq!(coverage.executed().collect::<Vec<_>>(), vec![(0x20, 0x4100)]);
+    }
+
+    #[test]
+    fn test_rom_identity() {
+        let rom = mbc1_rom();
+        let mut other = rom.clone();
+        other[0x0150] = 0xFF;
+
+        let mut coverage = Coverage::new();
+        coverage.bind_rom(&rom).unwrap();
+        coverage.mark(0, 0x0150, coverage::EXECUTED);
+        assert!(coverage.bind_rom(&rom).is_ok());
+
+        let path = std::env::temp_dir().join("rubc_coverage_identity_test.gb.cov");
+        coverage.save(&path).unwrap();
+        let mut loaded = Coverage::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.bind_rom(&other).is_err());
+
+        let mut stale = Coverage::new();
+        stale.bind_rom(&other).unwrap();
+        assert!(coverage.merge(&stale).is_err());
+        assert!(Coverage::new().save(&path).is_err());
+
+        let cart = cartridge::Cartridge::from_bytes_with_policy(&other, ValidationPolicy::Ignore).unwrap();
+        let built = gameboy::GameboyBuilder::new()
+            .set_cart(cart)
+            .with_coverage(coverage)
+            .build();
+        assert!(built.is_err());
+    }
+}
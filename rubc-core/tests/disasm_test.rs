@@ -0,0 +1,146 @@
+#[cfg(test)]
+mod tests {
+    use rubc_core::disasm::*;
+    use rubc_core::globals::ROM_BANK_SIZE;
+    use rubc_core::symbols::SymbolTable;
+
+    const DEFAULT_ROM: &str = "../assets/default_rom/default_rom.gb";
+
+    fn decode(bytes: &[u8]) -> String {
+        Instruction::decode(0, 0x0150, bytes).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(&[0x00]), "nop");
+        assert_eq!(decode(&[0x21, 0x00, 0xC0]), "ld hl, $C000");
+        assert_eq!(decode(&[0x08, 0x34, 0x12]), "ld [$1234], sp");
+        assert_eq!(decode(&[0xC3, 0x50, 0x01]), "jp $0150");
+        assert_eq!(decode(&[0xC2, 0x00, 0x40]), "jp nz, $4000");
+        assert_eq!(decode(&[0x18, 0xFE]), "jr $0150");
+        assert_eq!(decode(&[0x38, 0x10]), "jr c, $0162");
+        assert_eq!(decode(&[0xE0, 0x44]), "ldh [$FF44], a");
+        assert_eq!(decode(&[0xF8, 0xFD]), "ld hl, sp - 3");
+        assert_eq!(decode(&[0xE8, 0x05]), "add sp, 5");
+        assert_eq!(decode(&[0x2A]), "ld a, [hli]");
+        assert_eq!(decode(&[0x96]), "sub [hl]");
+        assert_eq!(decode(&[0xCE, 0x01]), "adc a, $01");
+        assert_eq!(decode(&[0xCB, 0x37]), "swap a");
+        assert_eq!(decode(&[0xCB, 0x7E]), "bit 7, [hl]");
+        assert_eq!(decode(&[0xFF]), "rst $38");
+        assert_eq!(decode(&[0x76]), "halt");
+
+        assert!(Instruction::decode(0, 0, &[0xD3]).is_none());
+        assert!(Instruction::decode(0, 0, &[0xCD, 0x00]).is_none());
+
+        let call = Instruction::decode(0, 0x0150, &[0xCC, 0x00, 0x20]).unwrap();
+        assert_eq!(call.flow, Flow::CondCall(0x2000));
+        assert_eq!(call.length, 3);
+    }
+
+    #[test]
+    fn test_cycles() {
+        let cycles = |bytes: &[u8]| {
+            let instruction = Instruction::decode(0, 0x0150, bytes).unwrap();
+            (instruction.cycles, instruction.cycles_not_taken)
+        };
+
+        assert_eq!(cycles(&[0x00]), (4, None));
+        assert_eq!(cycles(&[0x36, 0x12]), (12, None));
+        assert_eq!(cycles(&[0xCD, 0x00, 0x40]), (24, None));
+        assert_eq!(cycles(&[0xC4, 0x00, 0x40]), (24, Some(12)));
+        assert_eq!(cycles(&[0x20, 0x00]), (12, Some(8)));
+        assert_eq!(cycles(&[0xC8]), (20, Some(8)));
+        assert_eq!(cycles(&[0xCB, 0x37]), (8, None));
+        assert_eq!(cycles(&[0xCB, 0x46]), (12, None));
+        assert_eq!(cycles(&[0xCB, 0xC6]), (16, None));
+    }
+
+    #[test]
+    fn test_formats() {
+        let rom = std::fs::read(DEFAULT_ROM).unwrap();
+        let symbols = SymbolTable::load_for_rom(DEFAULT_ROM);
+        let disassembly = Disassembler::new(&rom).with_symbols(symbols.as_ref()).disassemble();
+
+        let json: serde_json::Value = serde_json::from_str(&disassembly.to_json().unwrap()).unwrap();
+        let instructions = json.as_array().unwrap();
+        assert_eq!(instructions.len(), disassembly.instructions().count());
+
+        let jump = &instructions[1];
+        assert_eq!(jump["bank"], 0);
+        assert_eq!(jump["addr"], 0x0101);
+        assert_eq!(jump["bytes"], serde_json::json!([0xC3, 0x50, 0x01]));
+        assert_eq!(jump["mnemonic"], "jp");
+        assert_eq!(jump["operand"], "$0150");
+        assert_eq!(jump["length"], 3);
+        assert_eq!(jump["cycles"], 16);
+        assert_eq!(instructions[2]["label"], "Main");
+
+        let csv = disassembly.to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("bank,addr,bytes,mnemonic,operand,length,cycles,label"));
+        assert_eq!(lines.next(), Some("0,0100,00,nop,,1,4,EntryPoint"));
+        assert!(csv.contains("0,0159,38 FA,jr,\"c, $0155\",2,12/8,"));
+    }
+
+    #[test]
+    fn test_code_and_data() {
+        let rom = std::fs::read(DEFAULT_ROM).unwrap();
+        let symbols = SymbolTable::load_for_rom(DEFAULT_ROM);
+        let disassembly = Disassembler::new(&rom).with_symbols(symbols.as_ref()).disassemble();
+
+        // entry point and everything reachable from it is code
+        assert!(disassembly.is_code(0, 0x0100));
+        assert!(disassembly.is_code(0, 0x0150));
+        assert!(disassembly.is_code(0, 0x01BB));
+        // the header, tile data and unused vectors are not
+        assert!(!disassembly.is_code(0, 0x0104));
+        assert!(!disassembly.is_code(0, 0x0040));
+        assert!(!disassembly.is_code(0, 0x01CD));
+
+        let jump = disassembly.instruction_at(0, 0x0101).unwrap();
+        assert_eq!(jump.flow, Flow::Jump(0x0150));
+        assert_eq!(disassembly.label_at(0, 0x0150), Some("Main"));
+
+        let source = disassembly.to_rgbds();
+        assert!(source.contains("SECTION \"ROM Bank $000\", ROM0[$0000]"));
+        assert!(source.contains("    jp Main "));
+        assert!(source.contains("jr c, Main.waitVBlank"));
+        assert!(source.contains("; Nintendo logo"));
+    }
+
+    #[test]
+    fn test_bank_switches() {
+        let mut rom = vec![0u8; ROM_BANK_SIZE * 4];
+        // nop; jp $0150
+        rom[0x0100..0x0104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+        // ld a, 2; ld [$2000], a; call $4000; jp $4000
+        rom[0x0150..0x015B].copy_from_slice(&[0x3E, 0x02, 0xEA, 0x00, 0x20, 0xCD, 0x00, 0x40, 0xC3, 0x00, 0x40]);
+        // bank 2: ld b, $01; ret
+        rom[2 * ROM_BANK_SIZE..2 * ROM_BANK_SIZE + 3].copy_from_slice(&[0x06, 0x01, 0xC9]);
+        // bank 3 is only reachable with an unknown bank
+        rom[0x0200..0x0203].copy_from_slice(&[0xC3, 0x00, 0x40]);
+        rom[3 * ROM_BANK_SIZE] = 0xC9;
+
+        let disassembly = Disassembler::new(&rom).disassemble();
+
+        assert!(disassembly.is_code(2, 0x4000));
+        assert!(disassembly.is_code(2, 0x4002));
+        assert!(!disassembly.is_code(1, 0x4000));
+        assert!(!disassembly.is_code(3, 0x4000));
+        assert_eq!(disassembly.label_at(2, 0x4000), Some("Call_002_4000"));
+
+        let source = disassembly.to_rgbds();
+        assert!(source.contains("SECTION \"ROM Bank $002\", ROMX[$4000], BANK[$2]"));
+        assert!(source.contains("call Call_002_4000"));
+
+        // extra entry points pull in code not reachable by traversal
+        let disassembly = Disassembler::new(&rom)
+            .with_entry_point(0, 0x0200)
+            .with_entry_point(3, 0x4000)
+            .disassemble();
+        assert!(disassembly.is_code(0, 0x0200));
+        assert!(disassembly.is_code(3, 0x4000));
+        assert!(disassembly.to_rgbds().contains("bank unknown for $4000"));
+    }
+}
@@ -0,0 +1,202 @@
+#[cfg(test)]
+mod tests {
+    use rubc_core::gdb::GdbStub;
+    use rubc_core::mbc::DummyMBC;
+    use rubc_core::watchpoints::{WatchKind, Watchpoint};
+    use rubc_core::{cartridge, gameboy};
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+
+    struct Client {
+        stream: TcpStream,
+    }
+
+    impl Client {
+        fn send(&mut self, data: &str) -> String {
+            self.send_bytes(data.as_bytes())
+        }
+
+        fn send_bytes(&mut self, data: &[u8]) -> String {
+            let checksum = data.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+            let mut packet = vec![b'$'];
+            packet.extend(data);
+            packet.extend(format!("#{:02x}", checksum).bytes());
+            self.stream.write_all(&packet).unwrap();
+            self.recv()
+        }
+
+        fn recv(&mut self) -> String {
+            let mut reply = Vec::new();
+            let mut byte = [0u8; 1];
+            loop {
+                self.stream.read_exact(&mut byte).unwrap();
+                match byte[0] {
+                    b'+' if reply.is_empty() => continue,
+                    b'#' => break,
+                    b => reply.push(b),
+                }
+            }
+            let mut checksum = [0u8; 2];
+            self.stream.read_exact(&mut checksum).unwrap();
+            self.stream.write_all(b"+").unwrap();
+            String::from_utf8(reply[1..].to_vec()).unwrap()
+        }
+    }
+
+    fn setup(program: &[u8]) -> (Client, thread::JoinHandle<gameboy::Gameboy>) {
+        setup_with_watchpoints(program, Vec::new())
+    }
+
+    fn setup_with_watchpoints(
+        program: &[u8],
+        watchpoints: Vec<Watchpoint>,
+    ) -> (Client, thread::JoinHandle<gameboy::Gameboy>) {
+        let mut gb = gameboy::GameboyBuilder::new()
+            .enable_test_mode()
+            .with_watchpoints(watchpoints)
+            .build()
+            .unwrap();
+        gb.cart = cartridge::Cartridge::DummyMBC(DummyMBC::new());
+        for (i, byte) in program.iter().enumerate() {
+            gb.memory_write(0x0100 + i as u16, *byte);
+        }
+
+        let mut stub = GdbStub::bind("127.0.0.1:0").unwrap();
+        let addr = stub.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            stub.serve(&mut gb).unwrap();
+            gb
+        });
+
+        let stream = TcpStream::connect(addr).unwrap();
+        (Client { stream }, handle)
+    }
+
+    #[test]
+    fn test_registers_and_memory() {
+        let (mut client, handle) = setup(&[0x00]);
+
+        assert_eq!(client.send("?"), "S05");
+        // AF=01B0 BC=0013 DE=00D8 HL=014D SP=FFFE PC=0100
+        assert_eq!(client.send("g"), "b0011300d8004d01feff0001");
+        assert_eq!(client.send("P1=3412"), "OK");
+        assert_eq!(client.send("p1"), "3412");
+
+        assert_eq!(client.send("MC000,3:aabbcc"), "OK");
+        assert_eq!(client.send("mC000,3"), "aabbcc");
+
+        assert_eq!(client.send("D"), "OK");
+        let gb = handle.join().unwrap();
+        assert_eq!(gb.cpu.b, 0x12);
+        assert_eq!(gb.cpu.c, 0x34);
+    }
+
+    #[test]
+    fn test_step_and_breakpoints() {
+        // INC A; INC B; INC C; INC D; JP $0100
+        let (mut client, handle) = setup(&[0x3C, 0x04, 0x0C, 0x14, 0xC3, 0x00, 0x01]);
+
+        assert_eq!(client.send("s"), "S05");
+        assert_eq!(client.send("p5"), "0101");
+
+        assert_eq!(client.send("Z0,103,1"), "OK");
+        assert_eq!(client.send("c"), "S05");
+        assert_eq!(client.send("p5"), "0301");
+
+        assert_eq!(client.send("z0,103,1"), "OK");
+        assert_eq!(client.send("Z1,102,1"), "OK");
+        assert_eq!(client.send("c"), "S05");
+        assert_eq!(client.send("p5"), "0201");
+
+        client.send("D");
+        let gb = handle.join().unwrap();
+        assert_eq!(gb.cpu.a, 0x03);
+        assert_eq!(gb.cpu.b, 0x02);
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        // LD A, $42; LD ($C000), A; LD A, ($C001); JP $0100
+        let program = [0x3E, 0x42, 0xEA, 0x00, 0xC0, 0xFA, 0x01, 0xC0, 0xC3, 0x00, 0x01];
+        let (mut client, handle) = setup(&program);
+
+        assert_eq!(client.send("Z2,c000,1"), "OK");
+        assert_eq!(client.send("c"), "T05watch:c000;");
+        assert_eq!(client.send("mc000,1"), "42");
+
+        assert_eq!(client.send("z2,c000,1"), "OK");
+        assert_eq!(client.send("Z3,c001,1"), "OK");
+        assert_eq!(client.send("c"), "T05rwatch:c001;");
+
+        assert_eq!(client.send("z3,c001,1"), "OK");
+        assert_eq!(client.send("Z4,c000,2"), "OK");
+        assert_eq!(client.send("c"), "T05awatch:c000;");
+
+        client.send("D");
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_keeps_other_watchpoints() {
+        let watchpoint = Watchpoint::new(WatchKind::Write, 0xC000, 0xC000);
+        let (mut client, handle) = setup_with_watchpoints(&[0x00], vec![watchpoint.clone()]);
+
+        // the same watchpoint from the client, and one of its own
+        assert_eq!(client.send("Z2,c000,1"), "OK");
+        assert_eq!(client.send("Z3,c001,1"), "OK");
+        assert_eq!(client.send("z2,c000,1"), "OK");
+        client.send("D");
+
+        let gb = handle.join().unwrap();
+        assert_eq!(gb.watchpoints(), [watchpoint]);
+    }
+
+    #[test]
+    fn test_malformed_packets() {
+        let (mut client, handle) = setup(&[0x00]);
+        // invalid and multibyte UTF-8 commands are unsupported, not fatal
+        assert_eq!(client.send_bytes(&[0xFF, b'1']), "");
+        assert_eq!(client.send("\u{e9}"), "");
+        assert_eq!(client.send("?"), "S05");
+        client.send("D");
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_monitor_backtrace() {
+        // CALL $0104; NOP; NOP at $0104
+        let (mut client, handle) = setup(&[0xCD, 0x04, 0x01, 0x00, 0x00]);
+        let hex = |text: &str| text.bytes().map(|b| format!("{:02x}", b)).collect::<String>();
+
+        assert_eq!(client.send("s"), "S05");
+        assert_eq!(client.send(&format!("qRcmd,{}", hex("bt"))), hex("#0  00:0104\n#1  00:0100\n"));
+        assert_eq!(
+            client.send(&format!("qRcmd,{}", hex("history"))),
+            hex("00:0100  CD 04 01  call $0104\n")
+        );
+        assert_eq!(client.send("qRcmd,zz"), "E01");
+
+        client.send("D");
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_monitor_search() {
+        let (mut client, handle) = setup(&[0x00]);
+        let hex = |text: &str| text.bytes().map(|b| format!("{:02x}", b)).collect::<String>();
+
+        assert_eq!(
+            client.send(&format!("qRcmd,{}", hex("search start"))),
+            hex(&format!("{} candidates\n", 0x2000 + 0x7F + 0x2000))
+        );
+        assert_eq!(client.send("Mc123,1:05"), "OK");
+        assert_eq!(
+            client.send(&format!("qRcmd,{}", hex("search =5"))),
+            hex("1 candidates\nC123 = 5\n")
+        );
+
+        client.send("D");
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,158 @@
+#[cfg(test)]
+mod tests {
+    use rubc_core::globals::*;
+    use rubc_core::header::*;
+    use rubc_core::validation::*;
+
+    fn header_with(title: &[u8], bytes: &[(u16, u8)]) -> CartridgeHeader {
+        let mut rom = vec![0u8; 0x150];
+        rom[CART_TITLE_START as usize..CART_TITLE_START as usize + title.len()].copy_from_slice(title);
+        for (address, value) in bytes {
+            rom[*address as usize] = *value;
+        }
+        rom[CART_HEADER_CHECKSUM as usize] = header_checksum(&rom);
+        CartridgeHeader::parse(&rom).unwrap()
+    }
+
+    #[test]
+    fn test_default_rom() {
+        let rom = std::fs::read("../assets/default_rom/default_rom.gb").unwrap();
+        let header = CartridgeHeader::parse(&rom).unwrap();
+
+        assert_eq!(header.title, "NO-ROM");
+        assert_eq!(header.manufacturer_code, None);
+        assert_eq!(header.cgb, CgbSupport::None);
+        assert!(!header.sgb);
+        assert_eq!(header.mapper, Mapper::RomOnly);
+        assert_eq!(header.features, Features::default());
+        assert_eq!(header.rom_size, Some(0x8000));
+        assert_eq!(header.rom_banks(), Some(2));
+        assert_eq!(header.ram_banks(), Some(0));
+        assert_eq!(header.licensee, "None");
+        assert_eq!(header.destination, "Japanese");
+        assert_eq!(header.header_checksum, 0x2F);
+        assert!(header.header_checksum_valid);
+        assert_eq!(header.global_checksum, 0x892E);
+
+        assert!(CartridgeHeader::parse(&rom[..0x14F]).is_err());
+    }
+
+    #[test]
+    fn test_validation_report() {
+        // the default ROM has no logo but valid checksums
+        let rom = std::fs::read("../assets/default_rom/default_rom.gb").unwrap();
+        let report = ValidationReport::new(&rom).unwrap();
+        assert_eq!(report.logo, LogoCheck::Invalid);
+        assert!(report.header_checksum_valid());
+        assert_eq!(report.global_checksum_computed, 0x892E);
+        assert!(!report.is_valid());
+        assert!(report.apply(ValidationPolicy::Warn).is_ok());
+        assert!(report.apply(ValidationPolicy::Strict).is_err());
+
+        // the stored global checksum of cpu_instrs is wrong
+        let rom = std::fs::read("../assets/cpu_instrs/cpu_instrs.gb").unwrap();
+        let report = ValidationReport::new(&rom).unwrap();
+        assert_eq!(report.logo, LogoCheck::Valid);
+        assert!(report.header_checksum_valid());
+        assert_eq!(report.global_checksum, 0xF530);
+        assert_eq!(report.global_checksum_computed, 0xB171);
+        assert_eq!(report.errors().len(), 1);
+        assert!(report.to_table().contains("MISMATCH"));
+
+        let mut rom = rom;
+        rom[CART_NINTENDO_LOGO_START as usize] = 0x00;
+        assert_eq!(check_logo(&rom), LogoCheck::Invalid);
+        rom[CART_NINTENDO_LOGO_START as usize] = NINTENDO_LOGO[0];
+        rom[CART_NINTENDO_LOGO_END as usize] = 0x00;
+        assert_eq!(check_logo(&rom), LogoCheck::TopHalfOnly);
+    }
+
+    #[test]
+    fn test_title_variants() {
+        // DMG carts use all 16 bytes
+        let header = header_with(b"SIXTEEN CHAR NAM", &[]);
+        assert_eq!(header.title, "SIXTEEN CHAR NAM");
+        assert_eq!(header.cgb, CgbSupport::None);
+
+        // $0143 is the CGB flag
+        let header = header_with(b"FIFTEEN CHAR NA", &[(CART_CBG_FLAG, 0xC0)]);
+        assert_eq!(header.title, "FIFTEEN CHAR NA");
+        assert_eq!(header.cgb, CgbSupport::Only);
+        assert_eq!(header.manufacturer_code, None);
+
+        // 11 character title followed by a manufacturer code
+        let new_licensee = [(CART_CBG_FLAG, 0x80), (CART_OLD_LICENSEE_CODE, 0x33)];
+        let header = header_with(b"POKEMON_SLVAAXE", &new_licensee);
+        assert_eq!(header.title, "POKEMON_SLV");
+        assert_eq!(header.manufacturer_code.as_deref(), Some("AAXE"));
+        assert_eq!(header.cgb, CgbSupport::Compatible);
+
+        let header = header_with(b"PM_CRYSTAL\0BYTE", &new_licensee);
+        assert_eq!(header.title, "PM_CRYSTAL");
+        assert_eq!(header.manufacturer_code.as_deref(), Some("BYTE"));
+
+        // padding reaching into the code area is no manufacturer code
+        let header = header_with(b"ZELDA", &new_licensee);
+        assert_eq!(header.title, "ZELDA");
+        assert_eq!(header.manufacturer_code, None);
+    }
+
+    #[test]
+    fn test_type_sizes_and_licensee() {
+        let header = header_with(
+            b"GAME",
+            &[
+                (CART_TYPE, 0x1E),
+                (CART_ROM_SIZE, 0x05),
+                (CART_SRAM_SIZE, 0x03),
+                (CART_SGB_FLAG, 0x03),
+                (CART_OLD_LICENSEE_CODE, 0x33),
+                (CART_NEW_LICENSEE_CODE_START, b'0'),
+                (CART_NEW_LICENSEE_CODE_END, b'1'),
+                (CART_DESTINATION_CODE, 0x01),
+                (CART_MASK_ROM_VERSION_NUMBER, 0x02),
+            ],
+        );
+        assert_eq!(header.mapper, Mapper::Mbc5);
+        assert!(header.features.ram && header.features.battery && header.features.rumble);
+        assert!(!header.features.timer);
+        assert!(header.sgb);
+        assert_eq!(header.rom_size, Some(0x100000));
+        assert_eq!(header.ram_banks(), Some(4));
+        assert_eq!(header.licensee_code, "01");
+        assert_eq!(header.licensee, "Nintendo R&D1");
+        assert_eq!(header.destination, "Non-Japanese");
+        // the version byte is part of the header checksum
+        assert_eq!(header.version, 0x02);
+        assert!(header.header_checksum_valid);
+
+        let header = header_with(b"GAME", &[(CART_TYPE, 0xFE), (CART_ROM_SIZE, 0x52), (CART_OLD_LICENSEE_CODE, 0x01)]);
+        assert_eq!(header.mapper, Mapper::HuC3);
+        assert!(header.features.timer);
+        assert_eq!(header.rom_banks(), Some(72));
+        assert_eq!(header.licensee_code, "01");
+        assert_eq!(header.licensee, "Nintendo");
+
+        let header = header_with(b"GAME", &[(CART_TYPE, 0x42), (CART_ROM_SIZE, 0x42)]);
+        assert_eq!(header.mapper, Mapper::Unknown);
+        assert_eq!(header.rom_size, None);
+    }
+
+    #[test]
+    fn test_output() {
+        let header = header_with(b"JSON", &[(CART_TYPE, 0x03), (CART_ROM_SIZE, 0x01), (CART_SRAM_SIZE, 0x02)]);
+        let json: serde_json::Value = serde_json::from_str(&header.to_json().unwrap()).unwrap();
+        assert_eq!(json["title"], "JSON");
+        assert_eq!(json["cgb"], "none");
+        assert_eq!(json["mapper"], "MBC1");
+        assert_eq!(json["features"]["battery"], true);
+        assert_eq!(json["rom_size"], 0x10000);
+        assert_eq!(json["ram_size"], 0x2000);
+        assert_eq!(json["manufacturer_code"], serde_json::Value::Null);
+
+        let table = header.to_table();
+        assert!(table.contains("MBC1+RAM+BATTERY"));
+        assert!(table.contains("RAM+BATTERY"));
+        assert!(table.contains("64KB"));
+    }
+}
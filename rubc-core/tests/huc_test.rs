@@ -0,0 +1,141 @@
+#[cfg(test)]
+mod tests {
+    use rubc_core::cartridge::Cartridge;
+    use rubc_core::gameboy::{Gameboy, GameboyBuilder};
+    use rubc_core::globals::*;
+    use rubc_core::mbc::HUC3_RTC_SAVE_SIZE;
+    use rubc_core::validation::ValidationPolicy;
+    use rubc_core::RubcError;
+
+    // 8 ROM banks tagged with their number and 4 RAM banks
+    fn cart(cart_type: u8) -> Cartridge {
+        let mut rom = vec![0u8; 8 * ROM_BANK_SIZE];
+        for bank in 0..8 {
+            rom[bank * ROM_BANK_SIZE + 0x1000] = bank as u8;
+        }
+        rom[CART_TYPE as usize] = cart_type;
+        rom[CART_ROM_SIZE as usize] = 0x02;
+        rom[CART_SRAM_SIZE as usize] = 0x03;
+        Cartridge::from_bytes_with_policy(&rom, ValidationPolicy::Ignore).unwrap()
+    }
+
+    fn setup(cart_type: u8) -> Gameboy {
+        GameboyBuilder::new().set_cart(cart(cart_type)).build().unwrap()
+    }
+
+    // runs HuC3 commands, returning the response to the last one
+    fn huc3_commands(gb: &mut Gameboy, commands: &[u8]) -> u8 {
+        gb.memory_write(0x0000, 0x0B);
+        for command in commands {
+            gb.memory_write(0xA000, *command);
+        }
+        gb.memory_write(0x0000, 0x0C);
+        let response = gb.memory_read(0xA000);
+        gb.memory_write(0x0000, 0x00);
+        response
+    }
+
+    #[test]
+    fn test_huc1() {
+        let mut gb = setup(0xFF);
+        gb.memory_write(0x2000, 0x05);
+        assert_eq!(gb.memory_read(0x5000), 5);
+        gb.memory_write(0x2000, 0x00);
+        assert_eq!(gb.memory_read(0x5000), 1);
+
+        // RAM needs no enable
+        gb.memory_write(0x4000, 0x02);
+        gb.memory_write(0xA000, 0x42);
+        assert_eq!(gb.memory_read(0xA000), 0x42);
+        assert_eq!(gb.cart.bank(0xA000), 2);
+
+        gb.memory_write(0x0000, 0x0E);
+        assert_eq!(gb.memory_read(0xA000), 0xC0);
+        gb.memory_write(0xA000, 0x01);
+        match &mut gb.cart {
+            Cartridge::HuC1(mbc) => {
+                assert!(mbc.ir_led());
+                mbc.set_ir_light(true);
+            }
+            _ => panic!("expected a HuC1 cart"),
+        }
+        assert_eq!(gb.memory_read(0xA000), 0xC1);
+
+        gb.memory_write(0x0000, 0x00);
+        assert_eq!(gb.memory_read(0xA000), 0x42);
+    }
+
+    #[test]
+    fn test_huc3() {
+        let mut gb = setup(0xFE);
+        gb.memory_write(0x2000, 0x07);
+        assert_eq!(gb.memory_read(0x5000), 7);
+
+        gb.memory_write(0x0000, 0x0A);
+        gb.memory_write(0xA000, 0x42);
+        assert_eq!(gb.memory_read(0xA000), 0x42);
+        gb.memory_write(0x0000, 0x0D);
+        assert_eq!(gb.memory_read(0xA000), 0x01);
+        assert_eq!(huc3_commands(&mut gb, &[0x62]), 0x01);
+
+        // set the clock to 0x123 minutes and 0x45 days, then read it back
+        let set = [0x40, 0x50, 0x33, 0x32, 0x31, 0x35, 0x34, 0x30, 0x30, 0x61];
+        huc3_commands(&mut gb, &set);
+        let nibbles: Vec<u8> = (0..7)
+            .map(|i| huc3_commands(&mut gb, &[0x60, 0x40 | i, 0x50, 0x10]))
+            .collect();
+        assert_eq!(nibbles, [3, 2, 1, 5, 4, 0, 0]);
+
+        // tone 5 on the speaker
+        huc3_commands(&mut gb, &[0x46, 0x52, 0x35, 0x31]);
+        match &gb.cart {
+            Cartridge::HuC3(mbc) => {
+                assert_eq!(mbc.clock(), (0x123, 0x45));
+                assert_eq!(mbc.tone(), Some(5));
+            }
+            _ => panic!("expected a HuC3 cart"),
+        }
+
+        gb.memory_write(0x0000, 0x0E);
+        assert_eq!(gb.memory_read(0xA000), 0xC0);
+    }
+
+    #[test]
+    fn test_saves() {
+        let mut gb = setup(0xFE);
+        gb.memory_write(0xA123, 0x42);
+        huc3_commands(&mut gb, &[0x40, 0x50, 0x3A, 0x3B, 0x34, 0x31, 0x30, 0x30, 0x30, 0x61]);
+
+        let mut save = gb.cart.save_data().unwrap();
+        assert_eq!(save.len(), 4 * RAM_BANK_SIZE + HUC3_RTC_SAVE_SIZE);
+        assert_eq!(save[0x123], 0x42);
+
+        // saved a day and an hour ago
+        let footer = 4 * RAM_BANK_SIZE;
+        let last_second = u64::from_le_bytes(save[footer..footer + 8].try_into().unwrap());
+        save[footer..footer + 8].copy_from_slice(&(last_second - 25 * 3600).to_le_bytes());
+
+        let mut loaded = cart(0xFE);
+        loaded.load_save_data(&save).unwrap();
+        assert_eq!(loaded.sram().unwrap()[0x123], 0x42);
+        match &loaded {
+            Cartridge::HuC3(mbc) => assert_eq!(mbc.clock(), (0x4BA + 60, 0x02)),
+            _ => panic!("expected a HuC3 cart"),
+        }
+
+        assert!(matches!(
+            loaded.load_save_data(&save[..0x100]),
+            Err(RubcError::SizeMismatch { actual: 0x100, .. })
+        ));
+
+        let mut huc1 = cart(0xFF);
+        huc1.load_save_data(&save[..footer]).unwrap();
+        assert_eq!(huc1.save_data().unwrap(), &save[..footer]);
+        // MBC1+RAM has no battery
+        assert_eq!(cart(0x02).save_data(), None);
+        assert_eq!(
+            Cartridge::save_path("game.gb"),
+            std::path::PathBuf::from("game.sav")
+        );
+    }
+}
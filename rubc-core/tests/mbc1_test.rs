@@ -0,0 +1,135 @@
+#[cfg(test)]
+mod tests {
+    use rubc_core::cartridge::Cartridge;
+    use rubc_core::gameboy::{Gameboy, GameboyBuilder};
+    use rubc_core::globals::*;
+    use rubc_core::validation::{ValidationPolicy, NINTENDO_LOGO};
+
+    // MBC1+RAM+BATTERY with 4 RAM banks, each ROM bank tagged with its number
+    fn rom(banks: usize, rom_size: u8) -> Vec<u8> {
+        let mut rom = vec![0u8; banks * ROM_BANK_SIZE];
+        for bank in 0..banks {
+            rom[bank * ROM_BANK_SIZE + 0x1000] = bank as u8;
+        }
+        rom[CART_TYPE as usize] = 0x03;
+        rom[CART_ROM_SIZE as usize] = rom_size;
+        rom[CART_SRAM_SIZE as usize] = 0x03;
+        rom
+    }
+
+    fn boot(rom: &[u8]) -> Gameboy {
+        let cart = Cartridge::from_bytes_with_policy(rom, ValidationPolicy::Ignore).unwrap();
+        GameboyBuilder::new().set_cart(cart).build().unwrap()
+    }
+
+    fn setup() -> Gameboy {
+        boot(&rom(8, 0x02))
+    }
+
+    fn is_multicart(gb: &Gameboy) -> bool {
+        match &gb.cart {
+            Cartridge::MBC1(mbc) => mbc.is_multicart(),
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn test_ram_enable() {
+        let mut gb = setup();
+        gb.memory_write(0xA000, 0x42);
+        assert_eq!(gb.memory_read(0xA000), 0xFF);
+        assert_eq!(gb.memory_read(0xBFFF), 0xFF);
+
+        gb.memory_write(0x0000, 0x0A);
+        gb.memory_write(0xA000, 0x42);
+        gb.memory_write(0xB123, 0x24);
+        gb.memory_write(0xBFFF, 0x81);
+        assert_eq!(gb.memory_read(0xA000), 0x42);
+        assert_eq!(gb.memory_read(0xB123), 0x24);
+        assert_eq!(gb.memory_read(0xBFFF), 0x81);
+
+        // only the low nibble matters
+        gb.memory_write(0x1FFF, 0x00);
+        assert_eq!(gb.memory_read(0xA000), 0xFF);
+        gb.memory_write(0x1234, 0xFA);
+        assert_eq!(gb.memory_read(0xA000), 0x42);
+    }
+
+    #[test]
+    fn test_ram_banking() {
+        let mut gb = setup();
+        gb.memory_write(0x0000, 0x0A);
+        gb.memory_write(0x6000, 0x01);
+        for bank in 0..4 {
+            gb.memory_write(0x4000, bank);
+            gb.memory_write(0xA000, 0x10 + bank);
+            gb.memory_write(0xBFFF, 0x20 + bank);
+        }
+        for bank in 0..4 {
+            gb.memory_write(0x4000, bank);
+            assert_eq!(gb.memory_read(0xA000), 0x10 + bank);
+            assert_eq!(gb.memory_read(0xBFFF), 0x20 + bank);
+        }
+
+        // mode 0 always maps RAM bank 0
+        gb.memory_write(0x6000, 0x00);
+        assert_eq!(gb.memory_read(0xA000), 0x10);
+        assert_eq!(gb.debug_read(0xBFFF), 0x20);
+    }
+
+    #[test]
+    fn test_rom_banking() {
+        let mut gb = setup();
+        assert_eq!(gb.memory_read(0x1000), 0);
+        assert_eq!(gb.memory_read(0x5000), 1);
+
+        gb.memory_write(0x2000, 0x05);
+        assert_eq!(gb.memory_read(0x5000), 5);
+        // bank 0 selects bank 1
+        gb.memory_write(0x2000, 0x00);
+        assert_eq!(gb.memory_read(0x5000), 1);
+        // bits above the ROM size are ignored
+        gb.memory_write(0x2000, 0x0B);
+        assert_eq!(gb.memory_read(0x5000), 3);
+    }
+
+    #[test]
+    fn test_large_rom_banking() {
+        let mut gb = boot(&rom(64, 0x05));
+        assert!(!is_multicart(&gb));
+
+        gb.memory_write(0x2000, 0x03);
+        gb.memory_write(0x4000, 0x01);
+        assert_eq!(gb.memory_read(0x5000), 0x23);
+        // the zero check sees the bits the ROM doesn't decode
+        gb.memory_write(0x2000, 0x20);
+        assert_eq!(gb.memory_read(0x5000), 0x21);
+
+        assert_eq!(gb.memory_read(0x1000), 0x00);
+        gb.memory_write(0x6000, 0x01);
+        assert_eq!(gb.memory_read(0x1000), 0x20);
+    }
+
+    #[test]
+    fn test_multicart() {
+        let mut multicart = rom(64, 0x05);
+        let logo = 0x10 * ROM_BANK_SIZE + CART_NINTENDO_LOGO_START as usize;
+        multicart[logo..logo + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+        let mut gb = boot(&multicart);
+        assert!(is_multicart(&gb));
+
+        // the upper bits shift by 4, selecting a 16 bank game
+        gb.memory_write(0x4000, 0x01);
+        assert_eq!(gb.memory_read(0x5000), 0x11);
+        gb.memory_write(0x6000, 0x01);
+        assert_eq!(gb.memory_read(0x1000), 0x10);
+
+        // bit 4 of the bank register is not connected
+        gb.memory_write(0x2000, 0x10);
+        assert_eq!(gb.memory_read(0x5000), 0x10);
+        gb.memory_write(0x4000, 0x03);
+        gb.memory_write(0x2000, 0x12);
+        assert_eq!(gb.memory_read(0x5000), 0x32);
+        assert_eq!(gb.memory_read(0x1000), 0x30);
+    }
+}
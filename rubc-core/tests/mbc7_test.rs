@@ -0,0 +1,138 @@
+#[cfg(test)]
+mod tests {
+    use rubc_core::cartridge::Cartridge;
+    use rubc_core::gameboy::{Gameboy, GameboyBuilder};
+    use rubc_core::globals::*;
+    use rubc_core::mbc::MBC7_EEPROM_SIZE;
+    use rubc_core::validation::ValidationPolicy;
+
+    // MBC7+SENSOR+RUMBLE+RAM+BATTERY with 8 ROM banks tagged with their number
+    fn cart() -> Cartridge {
+        let mut rom = vec![0u8; 8 * ROM_BANK_SIZE];
+        for bank in 0..8 {
+            rom[bank * ROM_BANK_SIZE + 0x1000] = bank as u8;
+        }
+        rom[CART_TYPE as usize] = 0x22;
+        rom[CART_ROM_SIZE as usize] = 0x02;
+        Cartridge::from_bytes_with_policy(&rom, ValidationPolicy::Ignore).unwrap()
+    }
+
+    fn setup() -> Gameboy {
+        let mut gb = GameboyBuilder::new().set_cart(cart()).build().unwrap();
+        gb.memory_write(0x0000, 0x0A);
+        gb.memory_write(0x4000, 0x40);
+        gb
+    }
+
+    fn latch(gb: &mut Gameboy) -> (u16, u16) {
+        gb.memory_write(0xA000, 0x55);
+        gb.memory_write(0xA010, 0xAA);
+        let x = u16::from_le_bytes([gb.memory_read(0xA020), gb.memory_read(0xA030)]);
+        let y = u16::from_le_bytes([gb.memory_read(0xA040), gb.memory_read(0xA050)]);
+        (x, y)
+    }
+
+    // clocks a bit into DI with CS high, returning DO after the rising edge
+    fn clock(gb: &mut Gameboy, bit: bool) -> bool {
+        let di = (bit as u8) << 1;
+        gb.memory_write(0xA080, 0x80 | di);
+        gb.memory_write(0xA080, 0xC0 | di);
+        gb.memory_read(0xA080) & 0x01 != 0
+    }
+
+    // selects the EEPROM and sends a start bit, then count bits MSB first
+    fn send(gb: &mut Gameboy, bits: u32, count: u32) -> bool {
+        gb.memory_write(0xA080, 0x00);
+        clock(gb, true);
+        (0..count).rev().fold(false, |_, i| clock(gb, bits >> i & 1 != 0))
+    }
+
+    fn read_word(gb: &mut Gameboy) -> u16 {
+        (0..16).fold(0, |word, _| word << 1 | clock(gb, false) as u16)
+    }
+
+    fn write_word(gb: &mut Gameboy, address: u8, word: u16) {
+        send(gb, 0b01 << 24 | (address as u32) << 16 | word as u32, 26);
+    }
+
+    #[test]
+    fn test_accelerometer() {
+        let mut gb = setup();
+        assert_eq!(gb.memory_read(0x5000), 1);
+        gb.memory_write(0x2000, 0x05);
+        assert_eq!(gb.memory_read(0x5000), 5);
+
+        assert_eq!(latch(&mut gb), (0x81D0, 0x81D0));
+        gb.set_tilt(1.0, -0.5);
+        // needs erasing before the next latch
+        gb.memory_write(0xA010, 0xAA);
+        assert_eq!(gb.memory_read(0xA020), 0xD0);
+        gb.memory_write(0xA000, 0x55);
+        assert_eq!(gb.memory_read(0xA030), 0x80);
+        assert_eq!(latch(&mut gb), (0x8240, 0x8198));
+        assert_eq!(gb.memory_read(0xA060), 0x00);
+        assert_eq!(gb.memory_read(0xA070), 0xFF);
+        // bits outside 4-7 of the address are ignored
+        assert_eq!(gb.memory_read(0xA125), 0x40);
+        assert_eq!(gb.memory_read(0xB020), 0xFF);
+
+        gb.memory_write(0x4000, 0x00);
+        assert_eq!(gb.memory_read(0xA020), 0xFF);
+    }
+
+    #[test]
+    fn test_eeprom() {
+        let mut gb = setup();
+        // writes are ignored until EWEN
+        write_word(&mut gb, 0x05, 0x1234);
+        send(&mut gb, 0b10_0000_0101, 10);
+        assert_eq!(read_word(&mut gb), 0xFFFF);
+
+        send(&mut gb, 0b00_1100_0000, 10);
+        write_word(&mut gb, 0x05, 0xBEEF);
+        write_word(&mut gb, 0x06, 0x1234);
+        assert_eq!(gb.memory_read(0xA080) & 0x01, 0x01);
+
+        // READ shifts out a dummy 0, then carries on to the next word
+        assert!(!send(&mut gb, 0b10_0000_0101, 10));
+        assert_eq!(read_word(&mut gb), 0xBEEF);
+        assert_eq!(read_word(&mut gb), 0x1234);
+        assert_eq!(read_word(&mut gb), 0xFFFF);
+
+        // ERASE, then EWDS locks the contents
+        send(&mut gb, 0b11_0000_0101, 10);
+        send(&mut gb, 0b00_0000_0000, 10);
+        send(&mut gb, 0b11_0000_0110, 10);
+        send(&mut gb, 0b10_0000_0101, 10);
+        assert_eq!(read_word(&mut gb), 0xFFFF);
+        assert_eq!(read_word(&mut gb), 0x1234);
+
+        // WRAL and ERAL
+        send(&mut gb, 0b00_1100_0000, 10);
+        send(&mut gb, 0b00_0100_0000 << 16 | 0xA5A5, 26);
+        send(&mut gb, 0b10_0111_1111, 10);
+        assert_eq!(read_word(&mut gb), 0xA5A5);
+        send(&mut gb, 0b00_1000_0000, 10);
+        send(&mut gb, 0b10_0000_0000, 10);
+        assert_eq!(read_word(&mut gb), 0xFFFF);
+    }
+
+    #[test]
+    fn test_saves() {
+        let mut gb = setup();
+        send(&mut gb, 0b00_1100_0000, 10);
+        write_word(&mut gb, 0x05, 0xBEEF);
+
+        let save = gb.cart.save_data().unwrap();
+        assert_eq!(save.len(), MBC7_EEPROM_SIZE);
+        assert_eq!(save[0x0A..0x0C], [0xEF, 0xBE]);
+
+        let mut loaded = cart();
+        loaded.load_save_data(&save).unwrap();
+        let mut gb = GameboyBuilder::new().set_cart(loaded).build().unwrap();
+        gb.memory_write(0x0000, 0x0A);
+        gb.memory_write(0x4000, 0x40);
+        send(&mut gb, 0b10_0000_0101, 10);
+        assert_eq!(read_word(&mut gb), 0xBEEF);
+    }
+}
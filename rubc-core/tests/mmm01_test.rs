@@ -0,0 +1,135 @@
+#[cfg(test)]
+mod tests {
+    use rubc_core::cartridge::Cartridge;
+    use rubc_core::gameboy::{Gameboy, GameboyBuilder};
+    use rubc_core::globals::*;
+    use rubc_core::header::{self, Mapper};
+    use rubc_core::validation::{ValidationPolicy, NINTENDO_LOGO};
+
+    // 1 MiB MMM01+RAM+BATTERY with 16 RAM banks, each ROM bank tagged with its
+    // number; $0100 holds the first game's MBC1 header
+    fn rom() -> Vec<u8> {
+        let mut rom = vec![0u8; 64 * ROM_BANK_SIZE];
+        for bank in 0..64 {
+            rom[bank * ROM_BANK_SIZE + 0x1000] = bank as u8;
+        }
+        rom[CART_TYPE as usize] = 0x01;
+        rom[CART_ROM_SIZE as usize] = 0x03;
+
+        let boot = 62 * ROM_BANK_SIZE;
+        rom[boot + CART_TYPE as usize] = 0x0D;
+        rom[boot + CART_ROM_SIZE as usize] = 0x05;
+        rom[boot + CART_SRAM_SIZE as usize] = 0x04;
+        with_logo_and_checksum(&mut rom[boot..]);
+        rom
+    }
+
+    fn with_logo_and_checksum(area: &mut [u8]) {
+        area[CART_NINTENDO_LOGO_START as usize..=CART_NINTENDO_LOGO_END as usize].copy_from_slice(&NINTENDO_LOGO);
+        area[CART_HEADER_CHECKSUM as usize] = header::header_checksum(area);
+    }
+
+    fn setup() -> Gameboy {
+        let cart = Cartridge::from_bytes_with_policy(&rom(), ValidationPolicy::Ignore).unwrap();
+        GameboyBuilder::new().set_cart(cart).build().unwrap()
+    }
+
+    // maps the 16 bank game at bank 0x20, RAM banks 4-7 with the low RAM bank unmasked
+    fn map_game(gb: &mut Gameboy) {
+        gb.memory_write(0x2000, 0x20);
+        gb.memory_write(0x4000, 0x04);
+        // bit 4 of the low ROM bank stays from the menu
+        gb.memory_write(0x6000, 0x08 << 2);
+        gb.memory_write(0x0000, 0x40);
+    }
+
+    #[test]
+    fn test_header_detection() {
+        let rom = rom();
+        assert_eq!(header::header_area(&rom).as_ptr(), rom[62 * ROM_BANK_SIZE..].as_ptr());
+        assert_eq!(header::header_area(&rom[..ROM_BANK_SIZE * 2]).len(), ROM_BANK_SIZE * 2);
+
+        let gb = setup();
+        assert_eq!(gb.cart.header().unwrap().mapper, Mapper::Mmm01);
+        assert_eq!(gb.cart.rom_banks(), 64);
+        assert_eq!(gb.cart.ram_banks(), 16);
+    }
+
+    #[test]
+    fn test_not_mmm01() {
+        // a 256 KiB MBC1 ROM whose byte at the would-be MMM01 type is $0B
+        let mut mbc1 = vec![0u8; 16 * ROM_BANK_SIZE];
+        mbc1[CART_TYPE as usize] = 0x01;
+        mbc1[CART_ROM_SIZE as usize] = 0x03;
+        with_logo_and_checksum(&mut mbc1);
+        mbc1[14 * ROM_BANK_SIZE + CART_TYPE as usize] = 0x0B;
+        assert_eq!(header::header_area(&mbc1).as_ptr(), mbc1.as_ptr());
+        let cart = Cartridge::from_bytes_with_policy(&mbc1, ValidationPolicy::Warn).unwrap();
+        assert_eq!(cart.header().unwrap().mapper, Mapper::Mbc1);
+
+        // even with a bootable trailing header, a bootable $0100 header for the whole ROM wins
+        with_logo_and_checksum(&mut mbc1[14 * ROM_BANK_SIZE..]);
+        assert_eq!(header::header_area(&mbc1).as_ptr(), mbc1.as_ptr());
+
+        // a trailing MMM01 type byte without a bootable header is ignored
+        let mut rom = rom();
+        rom[62 * ROM_BANK_SIZE + CART_HEADER_CHECKSUM as usize] ^= 0xFF;
+        assert_eq!(header::header_area(&rom).as_ptr(), rom.as_ptr());
+    }
+
+    #[test]
+    fn test_unmapped() {
+        let mut gb = setup();
+        assert_eq!(gb.memory_read(0x1000), 62);
+        assert_eq!(gb.memory_read(0x5000), 63);
+
+        gb.memory_write(0x2000, 0x05);
+        gb.memory_write(0x4000, 0x30);
+        assert_eq!(gb.memory_read(0x1000), 62);
+        assert_eq!(gb.memory_read(0x5000), 63);
+    }
+
+    #[test]
+    fn test_mapped() {
+        let mut gb = setup();
+        map_game(&mut gb);
+        assert_eq!(gb.memory_read(0x1000), 0x20);
+        assert_eq!(gb.memory_read(0x5000), 0x21);
+
+        gb.memory_write(0x2000, 0x05);
+        assert_eq!(gb.memory_read(0x5000), 0x25);
+        // masked and outer bits are locked
+        gb.memory_write(0x2000, 0x15);
+        assert_eq!(gb.memory_read(0x5000), 0x25);
+        gb.memory_write(0x2000, 0x60);
+        assert_eq!(gb.memory_read(0x5000), 0x21);
+        gb.memory_write(0x6000, 0x00);
+        gb.memory_write(0x0000, 0x00);
+        gb.memory_write(0x2000, 0x1F);
+        assert_eq!(gb.memory_read(0x5000), 0x2F);
+        assert_eq!(gb.memory_read(0x1000), 0x20);
+    }
+
+    #[test]
+    fn test_ram() {
+        let mut gb = setup();
+        map_game(&mut gb);
+        gb.memory_write(0xA000, 0x42);
+        assert_eq!(gb.memory_read(0xA000), 0xFF);
+
+        gb.memory_write(0x0000, 0x0A);
+        gb.memory_write(0x4000, 0x01);
+        assert_eq!(gb.cart.bank(0xA000), 5);
+        gb.memory_write(0xA000, 0x42);
+        gb.memory_write(0x4000, 0x00);
+        assert_eq!(gb.cart.bank(0xA000), 4);
+        gb.memory_write(0xA000, 0x24);
+
+        gb.memory_write(0x4000, 0x01);
+        assert_eq!(gb.memory_read(0xA000), 0x42);
+        // the high RAM bank bits are locked
+        gb.memory_write(0x4000, 0x0C);
+        assert_eq!(gb.cart.bank(0xA000), 4);
+        assert_eq!(gb.memory_read(0xA000), 0x24);
+    }
+}
@@ -0,0 +1,170 @@
+#[cfg(test)]
+mod tests {
+    use rubc_core::cartridge::Cartridge;
+    use rubc_core::gameboy::{Gameboy, GameboyBuilder};
+    use rubc_core::globals::*;
+    use rubc_core::mbc::DummyMBC;
+    use rubc_core::oam_bug::*;
+
+    fn oam(rows: &[(usize, [u16; 4])]) -> Vec<u8> {
+        let mut oam = vec![0u8; OAM_ROWS * OAM_ROW_SIZE];
+        for (row, words) in rows {
+            for (i, word) in words.iter().enumerate() {
+                let offset = row * OAM_ROW_SIZE + i * 2;
+                oam[offset..offset + 2].copy_from_slice(&word.to_le_bytes());
+            }
+        }
+        oam
+    }
+
+    fn row(oam: &[u8], row: usize) -> [u16; 4] {
+        let word = |i: usize| {
+            let offset = row * OAM_ROW_SIZE + i * 2;
+            u16::from_le_bytes([oam[offset], oam[offset + 1]])
+        };
+        [word(0), word(1), word(2), word(3)]
+    }
+
+    const PRECEDING: [u16; 4] = [0xF0F0, 0x1111, 0x0FF0, 0x3333];
+    const CURRENT: [u16; 4] = [0xAAAA, 0x5555, 0x6666, 0x7777];
+
+    #[test]
+    fn test_corruption_patterns() {
+        let mut memory = oam(&[(4, PRECEDING), (5, CURRENT)]);
+        corrupt(&mut memory, 5, Corruption::Write);
+        assert_eq!(row(&memory, 5), [0xAAF0, 0x1111, 0x0FF0, 0x3333]);
+        assert_eq!(row(&memory, 4), PRECEDING);
+
+        let mut memory = oam(&[(4, PRECEDING), (5, CURRENT)]);
+        corrupt(&mut memory, 5, Corruption::Read);
+        assert_eq!(row(&memory, 5), [0xFAF0, 0x1111, 0x0FF0, 0x3333]);
+
+        let mut memory = oam(&[(2, [0x1234, 0, 0, 0]), (3, PRECEDING), (4, CURRENT)]);
+        corrupt(&mut memory, 4, Corruption::ReadIncrease);
+        for corrupted in 2..=4 {
+            assert_eq!(row(&memory, corrupted), [0xB2F0, 0x1111, 0x0FF0, 0x3333]);
+        }
+
+        // the first four rows only get the read corruption
+        let mut memory = oam(&[(1, [0x1234, 0, 0, 0]), (2, PRECEDING), (3, CURRENT)]);
+        corrupt(&mut memory, 3, Corruption::ReadIncrease);
+        assert_eq!(row(&memory, 1), [0x1234, 0, 0, 0]);
+        assert_eq!(row(&memory, 3), [0xFAF0, 0x1111, 0x0FF0, 0x3333]);
+
+        let untouched = oam(&[(0, CURRENT), (19, PRECEDING)]);
+        let mut memory = untouched.clone();
+        corrupt(&mut memory, 0, Corruption::Write);
+        corrupt(&mut memory, OAM_ROWS, Corruption::Write);
+        assert_eq!(memory, untouched);
+    }
+
+    // scanning OAM row 5 with rows 4 and 5 set
+    fn setup(builder: GameboyBuilder) -> Gameboy {
+        let mut gb = builder.build().unwrap();
+        gb.cart = Cartridge::DummyMBC(DummyMBC::new());
+        for (address, word) in (0xFE20..).step_by(2).zip(PRECEDING.iter().chain(CURRENT.iter())) {
+            gb.debug_write(address, *word as u8);
+            gb.debug_write(address + 1, (*word >> 8) as u8);
+        }
+        gb.memory_write(IO_LCDC, 0x80);
+        gb.set_frame_cycles(0);
+        // NOPs, one row per M-cycle
+        for _ in 0..5 {
+            gb.tick().unwrap();
+        }
+        gb
+    }
+
+    fn oam_row(gb: &Gameboy, row: u16) -> [u16; 4] {
+        let word = |i: u16| {
+            let address = OAM_ADDRESS_START + row * 8 + i * 2;
+            u16::from_le_bytes([gb.debug_read(address), gb.debug_read(address + 1)])
+        };
+        [word(0), word(1), word(2), word(3)]
+    }
+
+    #[test]
+    fn test_instructions() {
+        let mut gb = setup(GameboyBuilder::new());
+        gb.cpu.b = 0xFE;
+        gb.cpu.c = 0x10;
+        gb.execute_op_code(0x03, 0).unwrap();
+        assert_eq!(oam_row(&gb, 5), [0xAAF0, 0x1111, 0x0FF0, 0x3333]);
+
+        // POP reads with an increase, which also mixes in the empty row 3, then reads
+        let mut gb = setup(GameboyBuilder::new());
+        gb.cpu.sp = 0xFE80;
+        gb.execute_op_code(0xC1, 0).unwrap();
+        for corrupted in 3..=5 {
+            assert_eq!(oam_row(&gb, corrupted), [0xA0F0, 0x1111, 0x0FF0, 0x3333]);
+        }
+        assert_eq!(gb.cpu.sp, 0xFE82);
+
+        // LD A, (HL+) reads with an increase, LD (HL-), A only gets the write corruption
+        let mut gb = setup(GameboyBuilder::new());
+        gb.cpu.h = 0xFE;
+        gb.cpu.l = 0x10;
+        gb.execute_op_code(0x2A, 0).unwrap();
+        for corrupted in 3..=5 {
+            assert_eq!(oam_row(&gb, corrupted), [0xA0F0, 0x1111, 0x0FF0, 0x3333]);
+        }
+        assert_eq!((gb.cpu.h, gb.cpu.l), (0xFE, 0x11));
+        let mut gb = setup(GameboyBuilder::new());
+        gb.cpu.h = 0xFE;
+        gb.cpu.l = 0x10;
+        gb.execute_op_code(0x32, 0).unwrap();
+        assert_eq!(oam_row(&gb, 5), [0xAAF0, 0x1111, 0x0FF0, 0x3333]);
+        assert_eq!(oam_row(&gb, 3), [0x0000; 4]);
+
+                // outside of OAM
+        let mut gb = setup(GameboyBuilder::new());
+        gb.cpu.sp = 0xDFF0;
+        gb.execute_op_code(0xC5, 0).unwrap();
+        gb.execute_op_code(0x33, 0).unwrap();
+        assert_eq!(oam_row(&gb, 5), CURRENT);
+    }
+
+    #[test]
+    fn test_scanned_row() {
+        // the row follows the cycles into OAM scan on any visible line
+        let mut gb = setup(GameboyBuilder::new());
+        gb.set_frame_cycles(100 * CYCLES_PER_SCANLINE + 20);
+        gb.cpu.sp = 0xFE80;
+        gb.execute_op_code(0x3B, 0).unwrap();
+        assert_eq!(oam_row(&gb, 5), [0xAAF0, 0x1111, 0x0FF0, 0x3333]);
+
+        let mut gb = setup(GameboyBuilder::new());
+        gb.set_frame_cycles(100 * CYCLES_PER_SCANLINE + 24);
+        gb.cpu.sp = 0xFE80;
+        gb.execute_op_code(0x3B, 0).unwrap();
+        assert_eq!(oam_row(&gb, 5), CURRENT);
+        assert_eq!(oam_row(&gb, 6), [0x2222, 0x5555, 0x6666, 0x7777]);
+
+        // the same point in a VBlank line
+        let mut gb = setup(GameboyBuilder::new());
+        gb.set_frame_cycles(150 * CYCLES_PER_SCANLINE + 20);
+        gb.cpu.sp = 0xFE80;
+        gb.execute_op_code(0x3B, 0).unwrap();
+        assert_eq!(oam_row(&gb, 5), CURRENT);
+    }
+
+    #[test]
+    fn test_not_triggered() {
+        let mut gb = setup(GameboyBuilder::new().enable_cgb_mode());
+        gb.cpu.sp = 0xFE80;
+        gb.execute_op_code(0xF5, 0).unwrap();
+        assert_eq!(oam_row(&gb, 5), CURRENT);
+
+        let mut gb = setup(GameboyBuilder::new());
+        gb.set_frame_cycles(CYCLES_PER_SCANLINE - 100);
+        gb.cpu.sp = 0xFE80;
+        gb.execute_op_code(0x3B, 0).unwrap();
+        assert_eq!(oam_row(&gb, 5), CURRENT);
+
+        let mut gb = setup(GameboyBuilder::new());
+        gb.memory_write(IO_LCDC, 0x00);
+        gb.cpu.sp = 0xFE80;
+        gb.execute_op_code(0xD1, 0).unwrap();
+        assert_eq!(oam_row(&gb, 5), CURRENT);
+    }
+}
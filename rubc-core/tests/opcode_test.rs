@@ -203,7 +203,7 @@ mod tests {
             }
 
             let mut s = format!("Testing OpCode: {:?}.......", file.file_name().unwrap());
-            let mut mb = gameboy::GameboyBuilder::new().enable_test_mode().build();
+            let mut mb = gameboy::GameboyBuilder::new().enable_test_mode().build().unwrap();
             mb.cart = cartridge::Cartridge::DummyMBC(DummyMBC::new());
             let tests = read_test_file(file.as_path());
 
@@ -0,0 +1,225 @@
+#[cfg(test)]
+mod tests {
+    use rubc_core::gameboy::GameboyBuilder;
+    use rubc_core::patch::*;
+    use rubc_core::utils::crc32;
+    use rubc_core::validation::ValidationPolicy;
+    use rubc_core::RubcError;
+
+    fn number(mut value: usize, out: &mut Vec<u8>) {
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                out.push(byte | 0x80);
+                return;
+            }
+            out.push(byte);
+            value -= 1;
+        }
+    }
+
+    fn with_footer(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+        patch.extend(crc32(source).to_le_bytes());
+        patch.extend(crc32(target).to_le_bytes());
+        patch.extend(crc32(&patch).to_le_bytes());
+        patch
+    }
+
+    fn ups(source: &[u8], target: &[u8]) -> Vec<u8> {
+        let xor = |i: usize| target[i] ^ source.get(i).copied().unwrap_or(0);
+        let mut patch = b"UPS1".to_vec();
+        number(source.len(), &mut patch);
+        number(target.len(), &mut patch);
+
+        let (mut position, mut i) = (0, 0);
+        while i < target.len() {
+            if xor(i) == 0 {
+                i += 1;
+                continue;
+            }
+            number(i - position, &mut patch);
+            while i < target.len() && xor(i) != 0 {
+                patch.push(xor(i));
+                i += 1;
+            }
+            // the terminator also steps over a byte
+            patch.push(0x00);
+            i += 1;
+            position = i;
+        }
+        with_footer(patch, source, target)
+    }
+
+    #[test]
+    fn test_ips() {
+        let rom = vec![0u8; 8];
+        let mut patch = b"PATCH".to_vec();
+        // 2 bytes at 0x0001
+        patch.extend([0x00, 0x00, 0x01, 0x00, 0x02, 0xAA, 0xBB]);
+        // RLE record filling 0x0006-0x0009, growing the ROM
+        patch.extend([0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x04, 0xCC]);
+        patch.extend(b"EOF");
+        assert_eq!(
+            apply(&patch, &rom).unwrap(),
+            [0x00, 0xAA, 0xBB, 0x00, 0x00, 0x00, 0xCC, 0xCC, 0xCC, 0xCC]
+        );
+
+        // truncation extension
+        patch.extend([0x00, 0x00, 0x03]);
+        assert_eq!(apply(&patch, &rom).unwrap(), [0x00, 0xAA, 0xBB]);
+
+        assert!(matches!(apply(b"PATCH\x00\x00", &rom), Err(RubcError::Patch(_))));
+        assert!(matches!(apply(b"NOPE", &rom), Err(RubcError::Patch(_))));
+    }
+
+    #[test]
+    fn test_ups() {
+        let source = b"Hello world";
+        let target = b"Hello, World!";
+        let patch = ups(source, target);
+        assert_eq!(PatchFormat::detect(&patch), Some(PatchFormat::Ups));
+        assert_eq!(apply(&patch, source).unwrap(), target);
+
+        match apply(&patch, b"Hello there") {
+            Err(error @ RubcError::Patch(_)) => assert!(error.to_string().contains("Source CRC32 mismatch")),
+            _ => panic!("expected a source CRC mismatch"),
+        }
+
+        let mut corrupt = patch.clone();
+        corrupt[6] ^= 0x01;
+        match apply(&corrupt, source) {
+            Err(error @ RubcError::Patch(_)) => assert!(error.to_string().contains("Patch CRC32 mismatch")),
+            _ => panic!("expected a patch CRC mismatch"),
+        }
+    }
+
+    #[test]
+    fn test_bps() {
+        let source = b"Hello world";
+        let target = b"Hello, World! Hello";
+
+        let mut patch = b"BPS1".to_vec();
+        number(source.len(), &mut patch);
+        number(target.len(), &mut patch);
+        number(4, &mut patch);
+        patch.extend(b"meta");
+        // source read "Hello"
+        number(4 << 2, &mut patch);
+        // target read ", W"
+        number((2 << 2) | 1, &mut patch);
+        patch.extend(b", W");
+        // source copy "orld" from +7
+        number((3 << 2) | 2, &mut patch);
+        number(7 << 1, &mut patch);
+        // target read "! "
+        number((1 << 2) | 1, &mut patch);
+        patch.extend(b"! ");
+        // target copy "Hello" from the start
+        number((4 << 2) | 3, &mut patch);
+        number(0, &mut patch);
+        let patch = with_footer(patch, source, target);
+
+        assert_eq!(PatchFormat::detect(&patch), Some(PatchFormat::Bps));
+        assert_eq!(apply(&patch, source).unwrap(), target);
+        assert!(matches!(
+            apply(&patch, b"Hello"),
+            Err(RubcError::SizeMismatch {
+                expected: 11,
+                actual: 5
+            })
+        ));
+    }
+
+    #[test]
+    fn test_malformed_sizes() {
+        let source = b"Hello world";
+        for magic in [b"UPS1", b"BPS1"] {
+            let mut patch = magic.to_vec();
+            number(source.len(), &mut patch);
+            number(1 << 60, &mut patch);
+            number(0, &mut patch);
+            let patch = with_footer(patch, source, b"");
+            match apply(&patch, source) {
+                Err(error @ RubcError::Patch(_)) => assert!(error.to_string().contains("exceeds the largest ROM size")),
+                other => panic!("expected a target size error, got {:?}", other),
+            }
+        }
+
+        // numbers overflowing usize, and endless continuation bytes
+        let mut patch = b"UPS1".to_vec();
+        patch.extend([0x7F; 9]);
+        patch.push(0xFF);
+        let patch = with_footer(patch, source, b"");
+        assert!(matches!(apply(&patch, source), Err(RubcError::Patch(_))));
+        let mut patch = b"UPS1".to_vec();
+        patch.extend([0x00; 16]);
+        let patch = with_footer(patch, source, b"");
+        assert!(matches!(apply(&patch, source), Err(RubcError::Patch(_))));
+
+        // a BPS action longer than the declared target
+        let mut patch = b"BPS1".to_vec();
+        number(source.len(), &mut patch);
+        number(4, &mut patch);
+        number(0, &mut patch);
+        number(5 << 2, &mut patch);
+        let patch = with_footer(patch, source, b"Hello");
+        assert!(matches!(apply(&patch, source), Err(RubcError::Patch(_))));
+    }
+
+    #[test]
+    fn test_soft_patching() {
+        let dir = std::env::temp_dir().join("rubc_patch_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let rom_path = dir.join("game.gb");
+        std::fs::copy("../assets/default_rom/default_rom.gb", &rom_path).unwrap();
+
+        // write $42 at 0x0200
+        let mut ips = b"PATCH".to_vec();
+        ips.extend([0x00, 0x02, 0x00, 0x00, 0x01, 0x42]);
+        ips.extend(b"EOF");
+        std::fs::write(dir.join("game.ips"), &ips).unwrap();
+        assert_eq!(find_for_rom(&rom_path), Some(dir.join("game.ips")));
+
+        // the default ROM has no Nintendo logo
+        let rom_file = rom_path.to_str().unwrap();
+        let builder = || {
+            GameboyBuilder::new()
+                .with_validation_policy(ValidationPolicy::Warn)
+                .with_cart(rom_file)
+                .unwrap()
+        };
+        let gb = builder().build().unwrap();
+        assert_eq!(gb.debug_read(0x0200), 0x42);
+
+        // an explicit patch replaces the one next to the ROM
+        let mut ips = b"PATCH".to_vec();
+        ips.extend([0x00, 0x02, 0x01, 0x00, 0x01, 0x24]);
+        ips.extend(b"EOF");
+        let explicit = dir.join("explicit.ips");
+        std::fs::write(&explicit, &ips).unwrap();
+        let gb = GameboyBuilder::new()
+            .with_validation_policy(ValidationPolicy::Warn)
+            .with_patch(explicit.to_str().unwrap())
+            .with_cart(rom_file)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(gb.debug_read(0x0200), 0x00);
+        assert_eq!(gb.debug_read(0x0201), 0x24);
+
+        // the order of `with_patch` and `with_cart` does not matter
+        let gb = builder().with_patch(explicit.to_str().unwrap()).build().unwrap();
+        assert_eq!(gb.debug_read(0x0200), 0x00);
+        assert_eq!(gb.debug_read(0x0201), 0x24);
+
+        // a bad patch fails the build
+        std::fs::write(&explicit, b"PATCH").unwrap();
+        assert!(matches!(
+            builder().with_patch(explicit.to_str().unwrap()).build(),
+            Err(RubcError::Patch(_))
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
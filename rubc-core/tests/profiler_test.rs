@@ -0,0 +1,85 @@
+#[cfg(test)]
+mod tests {
+    use rubc_core::mbc::DummyMBC;
+    use rubc_core::profiler::*;
+    use rubc_core::symbols::SymbolTable;
+    use rubc_core::{cartridge, gameboy};
+
+    #[test]
+    fn test_profile_calls() {
+        let mut gb = gameboy::GameboyBuilder::new()
+            .enable_test_mode()
+            .with_profiler(Profiler::new())
+            .build()
+            .unwrap();
+        gb.cart = cartridge::Cartridge::DummyMBC(DummyMBC::new());
+
+        // call $0110; call $0110; nop
+        let program = [0xCD, 0x10, 0x01, 0xCD, 0x10, 0x01, 0x00];
+        // $0110: nop; ret
+        let subroutine = [0x00, 0xC9];
+        for (i, byte) in program.iter().enumerate() {
+            gb.memory_write(0x0100 + i as u16, *byte);
+        }
+        for (i, byte) in subroutine.iter().enumerate() {
+            gb.memory_write(0x0110 + i as u16, *byte);
+        }
+
+        for _ in 0..7 {
+            gb.tick().unwrap();
+        }
+
+        let profiler = gb.profiler().unwrap();
+        assert_eq!(profiler.total_cycles(), 24 + 4 + 16 + 24 + 4 + 16 + 4);
+        assert_eq!(profiler.cycles_at(0, 0x0100), 24);
+        assert_eq!(profiler.cycles_at(0, 0x0111), 32);
+        assert_eq!(profiler.bank_cycles().collect::<Vec<_>>(), vec![(0, 92)]);
+
+        let functions = profiler.functions();
+        let root = functions[&(0, 0x0100)];
+        let subroutine = functions[&(0, 0x0110)];
+        assert_eq!(subroutine.calls, 2);
+        assert_eq!(subroutine.exclusive, 40);
+        assert_eq!(subroutine.inclusive, 40);
+        assert_eq!(root.exclusive, 52);
+        assert_eq!(root.inclusive, 92);
+
+        let mut symbols = SymbolTable::new();
+        symbols.insert(0, 0x0110, "Sub");
+        assert_eq!(profiler.collapsed_stacks(Some(&symbols)), "00:0100 52\n00:0100;Sub 40\n");
+        assert!(profiler.report(None).contains("Subroutines by inclusive cycles"));
+    }
+
+    #[test]
+    fn test_unwinding() {
+        let mut profiler = Profiler::new();
+        profiler.record(0, 0x0150, 24);
+        profiler.call(0, 0x0200, 0x0153);
+        profiler.record(0, 0x0200, 24);
+        // recursive call, only counted once towards inclusive cycles
+        profiler.call(0, 0x0200, 0x0203);
+        profiler.record(0, 0x0200, 24);
+        profiler.call(1, 0x4000, 0x0203);
+        profiler.record(1, 0x4000, 8);
+
+        // a RET that matches no call is a jump
+        profiler.ret(0x1234);
+        profiler.record(1, 0x4001, 8);
+
+        // returning straight to the outermost caller unwinds every frame
+        profiler.ret(0x0153);
+        profiler.record(0, 0x0153, 4);
+
+        let functions = profiler.functions();
+        assert_eq!(functions[&(0, 0x0200)].calls, 2);
+        assert_eq!(functions[&(0, 0x0200)].exclusive, 48);
+        assert_eq!(functions[&(0, 0x0200)].inclusive, 64);
+        assert_eq!(functions[&(1, 0x4000)].inclusive, 16);
+        assert_eq!(functions[&(0, 0x0100)].inclusive, 92);
+        assert_eq!(profiler.bank_cycles().collect::<Vec<_>>(), vec![(0, 76), (1, 16)]);
+
+        profiler.reset();
+        assert_eq!(profiler.total_cycles(), 0);
+        assert_eq!(profiler.collapsed_stacks(None), "\n");
+    }
+}
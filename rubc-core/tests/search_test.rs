@@ -0,0 +1,69 @@
+#[cfg(test)]
+mod tests {
+    use rubc_core::gameboy::{Gameboy, GameboyBuilder};
+    use rubc_core::search::*;
+
+    fn setup() -> Gameboy {
+        GameboyBuilder::new().build().unwrap()
+    }
+
+    fn addresses(search: &RamSearch) -> Vec<u16> {
+        search.candidates().iter().map(|c| c.address).collect()
+    }
+
+    #[test]
+    fn test_byte_search() {
+        let mut gb = setup();
+        let mut search = RamSearch::default().with_regions(&[SearchRegion::Wram, SearchRegion::Hram]);
+        assert_eq!(search.start(&gb), 0x2000 + 0x7F);
+
+        // lives at $C100 drop from 3 to 2, a timer at $FF90 counts up
+        gb.memory_write(0xC100, 3);
+        gb.memory_write(0xFF90, 10);
+        assert_eq!(search.filter(&gb, SearchFilter::Changed), 2);
+
+        gb.memory_write(0xC100, 2);
+        gb.memory_write(0xFF90, 11);
+        search.filter(&gb, SearchFilter::Decreased);
+        assert_eq!(addresses(&search), [0xC100]);
+        assert_eq!(search.candidates()[0].value, 2);
+
+        search.filter(&gb, SearchFilter::Unchanged);
+        assert_eq!(search.filter(&gb, SearchFilter::Equal(2)), 1);
+        assert_eq!(search.filter(&gb, SearchFilter::Increased), 0);
+        assert!(search.is_empty());
+    }
+
+    #[test]
+    fn test_word_and_bcd() {
+        let mut gb = setup();
+        gb.memory_write(0xC200, 0x34);
+        gb.memory_write(0xC201, 0x12);
+
+        let mut search = RamSearch::new(ValueSize::Word, ValueEncoding::Binary).with_regions(&[SearchRegion::Wram]);
+        assert_eq!(search.start(&gb), 0x2000 - 1);
+        search.filter(&gb, SearchFilter::Equal(0x1234));
+        assert_eq!(addresses(&search), [0xC200]);
+
+        // a score of 1234 in BCD
+        let mut search = RamSearch::new(ValueSize::Word, ValueEncoding::Bcd).with_regions(&[SearchRegion::Wram]);
+        search.start(&gb);
+        search.filter(&gb, SearchFilter::Equal(1234));
+        assert_eq!(addresses(&search), [0xC200]);
+
+        // invalid BCD never matches
+        gb.memory_write(0xC201, 0x1A);
+        assert_eq!(search.read(&gb, 0xC200), None);
+        assert_eq!(search.filter(&gb, SearchFilter::Changed), 0);
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        assert_eq!("=3".parse::<SearchFilter>().unwrap(), SearchFilter::Equal(3));
+        assert_eq!("= $FF".parse::<SearchFilter>().unwrap(), SearchFilter::Equal(0xFF));
+        assert_eq!("Changed".parse::<SearchFilter>().unwrap(), SearchFilter::Changed);
+        assert_eq!(">".parse::<SearchFilter>().unwrap(), SearchFilter::Increased);
+        assert!("=lots".parse::<SearchFilter>().is_err());
+        assert!("bigger".parse::<SearchFilter>().is_err());
+    }
+}
@@ -0,0 +1,78 @@
+#[cfg(test)]
+mod tests {
+    use rubc_core::breakpoints::Breakpoint;
+    use rubc_core::mbc::DummyMBC;
+    use rubc_core::symbols::SymbolTable;
+    use rubc_core::{cartridge, gameboy};
+
+    const DEFAULT_ROM_SYM: &str = "../assets/default_rom/default_rom.sym";
+
+    #[test]
+    fn test_parse_sym() {
+        let symbols = SymbolTable::load(DEFAULT_ROM_SYM).unwrap();
+
+        assert_eq!(symbols.resolve("Main"), Some((0, 0x0150)));
+        assert_eq!(symbols.resolve("Main.loop"), Some((0, 0x01AB)));
+        assert_eq!(symbols.name_at(0, 0x0100), Some("EntryPoint"));
+        assert_eq!(symbols.resolve("Missing"), None);
+
+        let symbols = SymbolTable::parse_sym("; comment\n01:4000 Banked ; trailing\n\n").unwrap();
+        assert_eq!(symbols.resolve("Banked"), Some((1, 0x4000)));
+        assert!(SymbolTable::parse_sym("0150 Main").is_err());
+    }
+
+    #[test]
+    fn test_parse_noi() {
+        let symbols =
+            SymbolTable::parse_noi("DEF _main 0x150\nDEF _banked 0x24010\nLOAD game.ihx\n").unwrap();
+
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols.resolve("_main"), Some((0, 0x0150)));
+        assert_eq!(symbols.resolve("_banked"), Some((2, 0x4010)));
+    }
+
+    #[test]
+    fn test_nearest() {
+        let mut symbols = SymbolTable::new();
+        symbols.insert(0, 0x0150, "Main");
+        symbols.insert(1, 0x4000, "BankOne");
+        symbols.insert(2, 0x4000, "BankTwo");
+        // bank is ignored outside of ROM1 and SRAM
+        symbols.insert(3, 0xC000, "wBuffer");
+
+        assert_eq!(symbols.describe(0, 0x0150), Some("Main".to_string()));
+        assert_eq!(symbols.describe(0, 0x0155), Some("Main+5".to_string()));
+        assert_eq!(symbols.describe(2, 0x4010), Some("BankTwo+16".to_string()));
+        assert_eq!(symbols.describe(3, 0x4010), None);
+        assert_eq!(symbols.describe(0, 0xC001), Some("wBuffer+1".to_string()));
+        // symbols never leak across regions
+        assert_eq!(symbols.describe(0, 0x8000), None);
+    }
+
+    #[test]
+    fn test_symbolic_breakpoints() {
+        let symbols = SymbolTable::load(DEFAULT_ROM_SYM).unwrap();
+
+        let breakpoint = Breakpoint::parse_with("Main", &symbols).unwrap();
+        assert_eq!(breakpoint, Breakpoint::at(0x0150).with_bank(0));
+
+        let mut breakpoint = Breakpoint::parse_with("PC == Main.loop", &symbols).unwrap();
+        assert!(breakpoint.range.is_none());
+
+        let mut gb = gameboy::GameboyBuilder::new()
+            .enable_test_mode()
+            .with_symbols(symbols)
+            .build()
+            .unwrap();
+        gb.cart = cartridge::Cartridge::DummyMBC(DummyMBC::new());
+
+        gb.cpu.pc = 0x01AB;
+        assert!(breakpoint.check(&gb));
+        assert_eq!(gb.describe_address(0x01AD), Some("Main.loop+2".to_string()));
+
+        gb.cpu.pc = 0x01AC;
+        assert!(!breakpoint.check(&gb));
+
+        assert!(Breakpoint::parse_with("Missing", &SymbolTable::new()).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+#[cfg(test)]
+mod tests {
+    use log::{Log, Metadata, Record};
+    use rubc_core::mbc::DummyMBC;
+    use rubc_core::watchpoints::*;
+    use rubc_core::{cartridge, gameboy};
+
+    fn setup(watchpoints: Vec<Watchpoint>) -> gameboy::Gameboy {
+        let mut gb = gameboy::GameboyBuilder::new()
+            .enable_test_mode()
+            .with_watchpoints(watchpoints)
+            .build()
+            .unwrap();
+        gb.cart = cartridge::Cartridge::DummyMBC(DummyMBC::new());
+        gb
+    }
+
+    #[test]
+    fn test_parse() {
+        let w: Watchpoint = "w/C000".parse().unwrap();
+        assert_eq!(w, Watchpoint::new(WatchKind::Write, 0xC000, 0xC000));
+
+        let w: Watchpoint = "a/01:A000-01:A0FF/==FF/log".parse().unwrap();
+        assert_eq!(
+            w,
+            Watchpoint::new(WatchKind::Access, 0xA000, 0xA0FF)
+                .with_bank(1)
+                .with_condition(WatchCondition::Equal(0xFF))
+                .with_action(WatchAction::Log)
+        );
+
+        let w: Watchpoint = "r/$C000-0xC00F/changed".parse().unwrap();
+        assert_eq!(w.kind, WatchKind::Read);
+        assert_eq!(w.condition, Some(WatchCondition::Changed));
+        assert_eq!(w.to_string(), "r/C000-C00F/changed/pause");
+
+        assert!("x/C000".parse::<Watchpoint>().is_err());
+        assert!("w/02:C000".parse::<Watchpoint>().is_err());
+        assert!("w/01:4000-02:4000".parse::<Watchpoint>().is_err());
+        assert!("w/C010-C000".parse::<Watchpoint>().is_err());
+        assert!("w/C000/maybe".parse::<Watchpoint>().is_err());
+    }
+
+    #[test]
+    fn test_read_write_access() {
+        let mut gb = setup(vec![
+            "w/C000".parse().unwrap(),
+            "r/C100-C1FF".parse().unwrap(),
+        ]);
+
+        gb.memory_read(0xC000);
+        assert_eq!(gb.take_watch_hit(), None);
+
+        gb.memory_write(0xC000, 0x12);
+        let hit = gb.take_watch_hit().unwrap();
+        assert_eq!((hit.kind, hit.address, hit.value), (WatchKind::Write, 0xC000, 0x12));
+        assert_eq!(gb.take_watch_hit(), None);
+
+        gb.memory_write(0xC180, 0x34);
+        assert_eq!(gb.take_watch_hit(), None);
+        gb.memory_read(0xC180);
+        let hit = gb.take_watch_hit().unwrap();
+        assert_eq!((hit.kind, hit.address, hit.value), (WatchKind::Read, 0xC180, 0x34));
+    }
+
+    #[test]
+    fn test_conditions() {
+        let mut gb = setup(vec![
+            "w/C000/==42".parse().unwrap(),
+            "w/C001/!=00".parse().unwrap(),
+            "w/C002/changed".parse().unwrap(),
+        ]);
+
+        gb.memory_write(0xC000, 0x41);
+        assert_eq!(gb.take_watch_hit(), None);
+        gb.memory_write(0xC000, 0x42);
+        assert!(gb.take_watch_hit().is_some());
+
+        gb.memory_write(0xC001, 0x00);
+        assert_eq!(gb.take_watch_hit(), None);
+        gb.memory_write(0xC001, 0x01);
+        assert!(gb.take_watch_hit().is_some());
+
+        gb.memory_write(0xC002, 0x00);
+        assert_eq!(gb.take_watch_hit(), None);
+        gb.memory_write(0xC002, 0x07);
+        assert!(gb.take_watch_hit().is_some());
+        gb.memory_write(0xC002, 0x07);
+        assert_eq!(gb.take_watch_hit(), None);
+    }
+
+    #[test]
+    fn test_log_action_does_not_pause() {
+        let mut gb = setup(vec!["a/C000/log".parse().unwrap()]);
+        gb.memory_write(0xC000, 0x01);
+        gb.memory_read(0xC000);
+        assert_eq!(gb.take_watch_hit(), None);
+    }
+
+    #[test]
+    fn test_changed_per_address() {
+        let mut gb = setup(vec!["r/C000-C001/changed".parse().unwrap()]);
+        gb.memory_write(0xC000, 0x01);
+        gb.memory_write(0xC001, 0x02);
+        gb.memory_read(0xC000);
+        gb.memory_read(0xC001);
+        gb.memory_read(0xC000);
+        assert_eq!(gb.take_watch_hit(), None);
+
+        gb.memory_write(0xC000, 0x05);
+        gb.memory_read(0xC001);
+        assert_eq!(gb.take_watch_hit(), None);
+        gb.memory_read(0xC000);
+        assert_eq!(gb.take_watch_hit().map(|hit| hit.value), Some(0x05));
+    }
+
+    struct NullLogger;
+
+    impl Log for NullLogger {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            let _ = format!("{}", record.args());
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: NullLogger = NullLogger;
+
+    #[test]
+    fn test_log_action_on_code() {
+        // the logged snapshot reads the code around PC without re-entering the watchpoint
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let mut gb = setup(vec!["r/0100-0110/log".parse().unwrap()]);
+        for _ in 0..4 {
+            gb.tick().unwrap();
+        }
+        assert_eq!(gb.cpu.pc, 0x0104);
+        assert_eq!(gb.take_watch_hit(), None);
+    }
+
+    #[test]
+    fn test_bank_qualified() {
+        let mut gb = setup(vec!["r/01:4000-01:7FFF".parse().unwrap()]);
+        gb.memory_read(0x4000);
+        let hit = gb.take_watch_hit().unwrap();
+        assert_eq!(hit.address, 0x4000);
+
+        let mut gb = setup(vec!["r/02:4000-02:7FFF".parse().unwrap()]);
+        gb.memory_read(0x4000);
+        assert_eq!(gb.take_watch_hit(), None);
+    }
+
+    #[test]
+    fn test_add_remove() {
+        let mut gb = setup(vec![]);
+        let w = Watchpoint::new(WatchKind::Write, 0xC000, 0xC0FF);
+        gb.add_watchpoint(w.clone());
+        gb.add_watchpoint(w.clone());
+        assert_eq!(gb.watchpoints().len(), 1);
+        assert!(gb.remove_watchpoint(&w));
+        assert!(!gb.remove_watchpoint(&w));
+    }
+}
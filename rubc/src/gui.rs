@@ -1,6 +1,7 @@
 use egui::{ClippedPrimitive, Context, TexturesDelta};
 use egui_wgpu::renderer::{Renderer, ScreenDescriptor};
 use pixels::{wgpu, PixelsContext};
+use rubc_core::cheats::{Cheat, Cheats};
 use winit::event_loop::EventLoopWindowTarget;
 use winit::window::Window;
 
@@ -22,6 +23,12 @@ pub(crate) struct Framework {
 struct Gui {
     /// Only show the egui window when true.
     window_open: bool,
+    /// Show the cheats window.
+    cheats_open: bool,
+    /// Code typed into the cheats window.
+    cheat_code: String,
+    /// Why the last code could not be added.
+    cheat_error: Option<String>,
 }
 
 impl Framework {
@@ -76,12 +83,12 @@ impl Framework {
     }
 
     /// Prepare egui.
-    pub(crate) fn prepare(&mut self, window: &Window) {
+    pub(crate) fn prepare(&mut self, window: &Window, cheats: &mut Cheats, error: Option<&str>) {
         // Run the egui frame and create all paint jobs to prepare for rendering.
         let raw_input = self.egui_state.take_egui_input(window);
         let output = self.egui_ctx.run(raw_input, |egui_ctx| {
             // Draw the demo application.
-            self.gui.ui(egui_ctx);
+            self.gui.ui(egui_ctx, cheats, error);
         });
 
         self.textures.append(output.textures_delta);
@@ -140,11 +147,16 @@ impl Framework {
 impl Gui {
     /// Create a `Gui`.
     fn new() -> Self {
-        Self { window_open: true }
+        Self {
+            window_open: true,
+            cheats_open: false,
+            cheat_code: String::new(),
+            cheat_error: None,
+        }
     }
 
     /// Create the UI using egui.
-    fn ui(&mut self, ctx: &Context) {
+    fn ui(&mut self, ctx: &Context, cheats: &mut Cheats, error: Option<&str>) {
         egui::TopBottomPanel::top("menubar_container").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
                 ui.menu_button("File", |ui| {
@@ -152,10 +164,65 @@ impl Gui {
                         self.window_open = true;
                         ui.close_menu();
                     }
-                })
+                });
+                ui.menu_button("Tools", |ui| {
+                    if ui.button("Cheats...").clicked() {
+                        self.cheats_open = true;
+                        ui.close_menu();
+                    }
+                });
             });
         });
 
+        egui::Window::new("Cheats")
+            .open(&mut self.cheats_open)
+            .show(ctx, |ui| {
+                let mut removed = None;
+                for (index, cheat) in cheats.cheats().to_vec().iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        let mut enabled = cheat.enabled;
+                        if ui.checkbox(&mut enabled, &cheat.code).changed() {
+                            cheats.set_enabled(index, enabled);
+                        }
+                        ui.label(&cheat.name);
+                        if ui.small_button("Remove").clicked() {
+                            removed = Some(index);
+                        }
+                    });
+                }
+                if let Some(index) = removed {
+                    cheats.remove(index);
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.cheat_code);
+                    if ui.button("Add").clicked() {
+                        match self.cheat_code.parse::<Cheat>() {
+                            Ok(cheat) => {
+                                cheats.add(cheat);
+                                self.cheat_code.clear();
+                                self.cheat_error = None;
+                            }
+                            Err(e) => self.cheat_error = Some(e.to_string()),
+                        }
+                    }
+                });
+                if let Some(error) = &self.cheat_error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+            });
+
+        // a crash or stuck CPU, with the report the core gives for it
+        if let Some(error) = error {
+            egui::Window::new("Emulation stopped").show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.monospace(error);
+                });
+                ui.label("Press Escape to quit.");
+            });
+        }
+
         // egui::Window::new("Hello, egui!")
         //     .open(&mut self.window_open)
         //     .show(ctx, |ui| {
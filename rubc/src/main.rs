@@ -3,35 +3,158 @@
 
 use crate::gui::Framework;
 
-use clap::Parser;
+use anyhow::Context;
+use clap::{Parser, Subcommand, ValueEnum};
 use pixels::Pixels;
-use rayon::prelude::*;
-use rubc_core::globals::ROM_BANK_SIZE;
+use rubc_core::breakpoints::Breakpoint;
+use rubc_core::cartridge::Cartridge;
+use rubc_core::cheats::{Cheat, Cheats};
+use rubc_core::coverage::Coverage;
+use rubc_core::disasm::Disassembler;
+use rubc_core::header::{self, CartridgeHeader};
 use rubc_core::logger;
-use std::sync::Mutex;
+use rubc_core::mbc::{CAMERA_HEIGHT, CAMERA_WIDTH};
+use rubc_core::profiler::Profiler;
+use rubc_core::symbols::SymbolTable;
+use rubc_core::validation::{ValidationPolicy, ValidationReport};
+use rubc_core::watchpoints::Watchpoint;
 use std::time;
-use winit::dpi::LogicalSize;
+use winit::dpi::{LogicalSize, PhysicalSize};
 use winit::event::{Event, VirtualKeyCode};
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::window::WindowBuilder;
 use winit_input_helper::WinitInputHelper;
 mod gui;
+mod png;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Format {
+    // ASCII table
+    Table,
+    // re-assemblable RGBDS source
+    Rgbds,
+    Json,
+    Csv,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Validation {
+    // refuse ROMs with a bad logo or checksum
+    Strict,
+    // refuse ROMs the boot ROM would, only warn about the global checksum
+    Boot,
+    Warn,
+    Ignore,
+}
+
+impl From<Validation> for ValidationPolicy {
+    fn from(validation: Validation) -> Self {
+        match validation {
+            Validation::Strict => ValidationPolicy::Strict,
+            Validation::Boot => ValidationPolicy::Boot,
+            Validation::Warn => ValidationPolicy::Warn,
+            Validation::Ignore => ValidationPolicy::Ignore,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the cartridge header of a ROM, .zip or .gz and exit.
+    Info {
+        rom_file: String,
+
+        #[clap(long, help = "Print the header as JSON.")]
+        json: bool,
+    },
+}
 
 #[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    rom_file: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[clap(required = true)]
+    rom_file: Option<String>,
 
-    #[clap(long, help = "Disassemble the ROM as <ROM_FILE>.txt and exit.")]
+    #[clap(long, help = "Disassemble the ROM as <ROM_FILE>.<txt|asm|json|csv> and exit.")]
     disassemble: bool,
 
-    #[clap(long, help = "Print CPU state between PC addresses. i.e. --breakpoints=0x100,0x150,0x170-0x180", num_args=1.., value_terminator=";", value_delimiter=',',value_name="PCn")]
+    #[clap(long, value_enum, default_value_t = Format::Table, requires = "disassemble", help = "Disassembly output format.")]
+    format: Format,
+
+    #[clap(long, value_enum, default_value_t = Validation::Boot, help = "How to handle a bad Nintendo logo, header or global checksum.")]
+    validation: Validation,
+
+    #[clap(
+        long,
+        help = "IPS, UPS or BPS patch applied to the ROM, repeatable. Defaults to <ROM_FILE>.ips/.ups/.bps if present.",
+        value_name = "FILE"
+    )]
+    patch: Vec<String>,
+
+    #[clap(long, help = "Game Genie (ABC-DEF-GHI) or GameShark (01VVLLHH) codes, added to <ROM_FILE>.cht.", value_delimiter = ',', value_name = "CODE")]
+    cheats: Vec<String>,
+
+    #[clap(long, help = "Breakpoints as [LOCATION] [if CONDITION] [after N] [do log|pause|dump]. i.e. --breakpoints=0x100,01:4000-01:4010,'0150 if A>=$10 && [$C000]==$FF do pause'", num_args=1.., value_terminator=";", value_delimiter=',',value_name="PCn")]
     breakpoints: Vec<String>,
 
+    #[clap(long, help = "Watch memory accesses as KIND/RANGE[/CONDITION][/ACTION]. i.e. --watchpoints=w/C000,a/01:A000-01:A0FF/==FF/log,r/C100-C10F/changed", num_args=1.., value_terminator=";", value_delimiter=',',value_name="WATCHn")]
+    watchpoints: Vec<String>,
+
+    #[clap(
+        long,
+        help = "RGBDS .sym or NoICE .noi symbol file. Defaults to <ROM_FILE>.sym/.noi if present.",
+        value_name = "FILE"
+    )]
+    symbols: Option<String>,
+
+    #[clap(
+        long,
+        help = "Record executed code to <ROM_FILE>.cov, merged across runs. --disassemble only decodes recorded code when the file exists."
+    )]
+    coverage: bool,
+
+    #[clap(
+        long,
+        help = "Profile cycles per instruction, subroutine and ROM bank, written to <ROM_FILE>.profile.txt on exit."
+    )]
+    profile: bool,
+
+    #[clap(
+        long,
+        requires = "profile",
+        help = "Also write the profiled call stacks to <ROM_FILE>.folded for flamegraph tools."
+    )]
+    collapsed_stacks: bool,
+
+    #[clap(
+        long,
+        help = "PNG image seen by the Pocket Camera's sensor, scaled to 128x112 grayscale.",
+        value_name = "FILE"
+    )]
+    camera_image: Option<String>,
+
     #[clap(
         long,
         help = "Panic if the emulator gets stuck processing instructions."
     )]
     panic_on_stuck: bool,
+
+    #[clap(
+        long,
+        help = "Run headless and wait for a GDB client on 127.0.0.1:<PORT>.",
+        value_name = "PORT"
+    )]
+    gdb: Option<u16>,
+}
+
+impl Args {
+    // clap requires ROM_FILE unless a subcommand is given
+    fn rom_file(&self) -> &str {
+        self.rom_file.as_deref().unwrap_or_default()
+    }
 }
 
 const WIDTH: u32 = 160;
@@ -41,87 +164,64 @@ const TITLE: &str = "RuBC";
 const FPS_US: u64 = 16_740;
 const CPU_HZ: u64 = 4_194_304;
 
-fn parse_cpu_log_arg<'a>(addresses: &'a Vec<String>) -> Vec<usize> {
-    let result = Mutex::new(Vec::<usize>::new());
-
-    let remove_prefixes = |s: &'a str| -> &'a str {
-        if s.starts_with("0x") {
-            &s[2..]
-        } else if s.starts_with("$") {
-            &s[1..]
-        } else {
-            &s
-        }
-    };
-
-    let flatten_expr = |addr: u16, bank: usize| -> usize { (bank * ROM_BANK_SIZE) + addr as usize };
-    let str_to_usize = |s: &'a str| -> usize {
-        usize::from_str_radix(s, 16).expect(&format!("Invalid format: {:?}", s))
+// tilt in g from held keys, falling back to the mouse relative to the window's centre
+fn tilt(input: &WinitInputHelper, size: PhysicalSize<u32>) -> (f32, f32) {
+    let keys = |negative, positive| match (input.key_held(negative), input.key_held(positive)) {
+        (true, false) => Some(-1.0),
+        (false, true) => Some(1.0),
+        _ => None,
     };
-
-    addresses.par_iter().for_each(|address| {
-        if address.contains('-') {
-            // let range: Vec<&'a str> = address.split('-').map(|a| remove_prefixes(a)).collect();
-            let range: Vec<&'a str> = address.split('-').collect();
-            if range.len() != 2 {
-                panic!("Invalid address range: {:?}", address);
-            }
-
-            let range1 = range[0];
-            let range2 = range[1];
-
-            let pair1 = range1.split(':').collect::<Vec<&str>>();
-            let pair2 = range2.split(':').collect::<Vec<&str>>();
-
-            if pair1.len() != 2 || pair2.len() != 2 {
-                panic!("Invalid address range: {:?}", address);
-            }
-
-            let bank1 = str_to_usize(remove_prefixes(pair1[0]));
-            let addr1 = str_to_usize(remove_prefixes(pair1[1]));
-            let addr1 = flatten_expr(addr1 as u16, bank1);
-
-            let bank2 = str_to_usize(remove_prefixes(pair2[0]));
-            let addr2 = str_to_usize(remove_prefixes(pair2[1]));
-            let addr2 = flatten_expr(addr2 as u16, bank2);
-
-            for continugous_address in addr1..addr2 {
-                result.lock().unwrap().push(continugous_address);
-            }
-        } else {
-            let pair = address.split(':').collect::<Vec<&str>>();
-            if pair.len() == 2 {
-                let bank = u16::from_str_radix(remove_prefixes(pair[0]), 16)
-                    .expect(&format!("Invalid address: {:?}", address));
-
-                let addr = u16::from_str_radix(remove_prefixes(pair[1]), 16)
-                    .expect(&format!("Invalid address: {:?}", address));
-
-                let continugous_address = (bank as usize * ROM_BANK_SIZE) + addr as usize;
-                result.lock().unwrap().push(continugous_address);
-            } else {
-                // atempt to parse as a single address without bank
-                let addr = u16::from_str_radix(remove_prefixes(address), 16)
-                    .expect(&format!("Invalid address: {:?}", address));
-                result.lock().unwrap().push(addr as usize);
-            }
-        }
-    });
-    result.into_inner().unwrap()
+    let (mouse_x, mouse_y) = input
+        .mouse()
+        .map(|(x, y)| {
+            let x = x / size.width.max(1) as f32 * 2.0 - 1.0;
+            let y = y / size.height.max(1) as f32 * 2.0 - 1.0;
+            (x.clamp(-1.0, 1.0), y.clamp(-1.0, 1.0))
+        })
+        .unwrap_or((0.0, 0.0));
+    (
+        keys(VirtualKeyCode::J, VirtualKeyCode::L).unwrap_or(mouse_x),
+        keys(VirtualKeyCode::I, VirtualKeyCode::K).unwrap_or(mouse_y),
+    )
 }
 
-fn main() -> rubc_core::Result<()> {
+fn main() -> anyhow::Result<()> {
     logger::setup_logger()?;
 
     let args = Args::parse();
+    if let Some(Command::Info { rom_file, json }) = &args.command {
+        return info(rom_file, *json);
+    }
+
     let mut emulator = Rubc::new(&args)?;
     if args.disassemble {
         log::info!("Dumping instruction set");
-        let x = rubc_core::utils::disassemble(&emulator.gameboy.cart);
+        let mut coverage = Coverage::load_for_rom(args.rom_file());
+        if let Some(coverage) = &mut coverage {
+            coverage.bind_rom(emulator.gameboy.cart.rom())?;
+        }
+        let disassembly = Disassembler::new(emulator.gameboy.cart.rom())
+            .with_symbols(emulator.gameboy.symbols())
+            .with_coverage(coverage.as_ref())
+            .disassemble();
+        let (x, ext) = match args.format {
+            Format::Table => (disassembly.to_table(), "txt"),
+            Format::Rgbds => (disassembly.to_rgbds(), "asm"),
+            Format::Json => (disassembly.to_json()?, "json"),
+            Format::Csv => (disassembly.to_csv(), "csv"),
+        };
         // print to file
-        std::fs::write(format!("{}.txt", args.rom_file), x)?;
-        log::debug!("Dumped instruction set to {}.txt", args.rom_file);
-        println!("Dumped instruction set to {}.txt", args.rom_file);
+        std::fs::write(format!("{}.{}", args.rom_file(), ext), x)?;
+        log::debug!("Dumped instruction set to {}.{}", args.rom_file(), ext);
+        println!("Dumped instruction set to {}.{}", args.rom_file(), ext);
+        return Ok(());
+    }
+
+    if let Some(port) = args.gdb {
+        let mut stub = rubc_core::gdb::GdbStub::bind(("127.0.0.1", port))?;
+        println!("Waiting for GDB client on {}", stub.local_addr()?);
+        stub.serve(&mut emulator.gameboy)?;
+        emulator.save_reports();
         return Ok(());
     }
 
@@ -165,10 +265,17 @@ fn main() -> rubc_core::Result<()> {
 
             // Close events
             if input.key_pressed(VirtualKeyCode::Escape) || input.close_requested() {
+                emulator.save_reports();
                 *control_flow = ControlFlow::Exit;
                 return;
             }
 
+            // Resume after a breakpoint or watchpoint paused emulation
+            if input.key_pressed(VirtualKeyCode::F5) && emulator.paused {
+                log::info!("Resuming emulation");
+                emulator.paused = false;
+            }
+
             // Update the scale factor
             if let Some(scale_factor) = input.scale_factor() {
                 framework.scale_factor(scale_factor);
@@ -184,6 +291,10 @@ fn main() -> rubc_core::Result<()> {
                 framework.resize(size.width, size.height);
             }
 
+            // Tilt MBC7 carts with I/J/K/L, or by the mouse's place in the window
+            let (x, y) = tilt(&input, window.inner_size());
+            emulator.gameboy.set_tilt(x, y);
+
             // Update internal state and request a redraw
             emulator.update();
             window.request_redraw();
@@ -208,7 +319,7 @@ fn main() -> rubc_core::Result<()> {
                 emulator.draw(pixels.frame_mut());
 
                 // Prepare egui
-                framework.prepare(&window);
+                framework.prepare(&window, emulator.gameboy.cheats_mut(), emulator.error.as_deref());
 
                 // Render everything together
                 let render_result = pixels.render_with(|encoder, render_target, context| {
@@ -233,34 +344,209 @@ fn main() -> rubc_core::Result<()> {
     });
 } // Add a closing parenthesis here
 
+fn info(rom_file: &str, json: bool) -> anyhow::Result<()> {
+    let rom = rubc_core::archive::extract_rom(&std::fs::read(rom_file)?)?;
+    let header_area = header::header_area(&rom);
+    let header = CartridgeHeader::parse(header_area)?;
+    match json {
+        true => println!("{}", header.to_json()?),
+        false => println!("{}\n{}", header.to_table(), ValidationReport::new(header_area)?.to_table()),
+    }
+    Ok(())
+}
+
 struct Rubc {
     gameboy: rubc_core::gameboy::Gameboy,
+    rom_file: String,
+    collapsed_stacks: bool,
+    paused: bool,
+    // why emulation stopped, shown in the GUI until the window is closed
+    error: Option<String>,
 }
 
 impl Rubc {
     fn new(args: &Args) -> anyhow::Result<Self> {
-        let mut builder = rubc_core::gameboy::GameboyBuilder::new().with_cart(&args.rom_file)?;
-        if args.breakpoints.len() > 0 {
-            log::info!("Logging CPU state at PC addresses: {:?}", args.breakpoints);
-            let mut breakpoints = parse_cpu_log_arg(&args.breakpoints);
-            breakpoints.sort();
-            breakpoints.dedup();
-            log::debug!("Parsed breakpoints: {:x?}", breakpoints);
-            builder = builder.with_cpu_breakpoints(breakpoints);
+        let mut builder = rubc_core::gameboy::GameboyBuilder::new()
+            .with_validation_policy(args.validation.into());
+        for patch in &args.patch {
+            builder = builder.with_patch(patch);
+        }
+        let mut builder = builder.with_cart(args.rom_file())?;
+
+        let symbols = match &args.symbols {
+            Some(path) => SymbolTable::load(path)?,
+            None => SymbolTable::load_for_rom(args.rom_file()).unwrap_or_default(),
+        };
+
+        if !args.breakpoints.is_empty() {
+            log::info!("Setting breakpoints: {:?}", args.breakpoints);
+            let breakpoints = args
+                .breakpoints
+                .iter()
+                .map(|b| Breakpoint::parse_with(b, &symbols))
+                .collect::<rubc_core::Result<Vec<_>>>()?;
+            log::debug!("Parsed breakpoints: {:?}", breakpoints);
+            builder = builder.with_breakpoints(breakpoints);
+        }
+
+        if !args.watchpoints.is_empty() {
+            log::info!("Watching memory: {:?}", args.watchpoints);
+            let watchpoints = args
+                .watchpoints
+                .iter()
+                .map(|w| w.parse::<Watchpoint>())
+                .collect::<rubc_core::Result<Vec<_>>>()?;
+            builder = builder.with_watchpoints(watchpoints);
+        }
+
+        if !symbols.is_empty() {
+            log::info!("Loaded {} symbols", symbols.len());
+            builder = builder.with_symbols(symbols);
+        }
+
+        if args.profile {
+            builder = builder.with_profiler(Profiler::new());
+        }
+
+        if args.coverage {
+            builder = builder.with_coverage(Coverage::load_for_rom(args.rom_file()).unwrap_or_default());
+        }
+
+        let cheats_path = Cheats::sidecar_path(args.rom_file());
+        let mut cheats = Cheats::load_for_rom(args.rom_file())
+            .with_context(|| format!("Unable to load cheats from {}", cheats_path.display()))?
+            .unwrap_or_default();
+        for code in &args.cheats {
+            cheats.add(code.parse::<Cheat>()?);
+        }
+        if !cheats.is_empty() {
+            log::info!("Loaded {} cheats", cheats.len());
+            builder = builder.with_cheats(cheats);
         }
 
         if args.panic_on_stuck {
             builder = builder.panic_on_stuck();
         }
+
+        let mut gameboy = builder.build()?;
+        let save = Cartridge::save_path(args.rom_file());
+        // refuse to start rather than overwrite a save we could not read on exit
+        if gameboy.cart.has_battery() && save.is_file() {
+            gameboy.cart.load_save(&save).with_context(|| {
+                format!(
+                    "Unable to load save from {}, move it aside to start without it",
+                    save.display()
+                )
+            })?;
+            log::info!("Loaded save from {}", save.display());
+        }
+        if let Some(path) = &args.camera_image {
+            let image = png::decode_grayscale(&std::fs::read(path)?)?.resize(CAMERA_WIDTH, CAMERA_HEIGHT);
+            gameboy.set_camera_image(&image.pixels)?;
+            log::info!("Loaded camera image from {}", path);
+        }
         Ok(Rubc {
-            gameboy: builder.build(),
+            gameboy,
+            rom_file: args.rom_file().to_string(),
+            collapsed_stacks: args.collapsed_stacks,
+            paused: false,
+            error: None,
         })
     }
 
+    // writes coverage and profiling results when enabled, the cheats and the save
+    fn save_reports(&self) {
+        self.save_coverage();
+        self.save_profile();
+        self.save_cheats();
+        self.save_ram();
+    }
+
+    fn save_ram(&self) {
+        if !self.gameboy.cart.has_battery() {
+            return;
+        }
+        let path = Cartridge::save_path(&self.rom_file);
+        match self.gameboy.cart.save(&path) {
+            Ok(()) => log::info!("Saved to {}", path.display()),
+            Err(e) => log::error!("Unable to save to {}: {}", path.display(), e),
+        }
+    }
+
+    fn save_cheats(&self) {
+        let path = Cheats::sidecar_path(&self.rom_file);
+        if self.gameboy.cheats().is_empty() && !path.is_file() {
+            return;
+        }
+        match self.gameboy.cheats().save(&path) {
+            Ok(()) => log::info!("Saved cheats to {}", path.display()),
+            Err(e) => log::error!("Unable to save cheats to {}: {}", path.display(), e),
+        }
+    }
+
+    fn save_profile(&self) {
+        let profiler = match self.gameboy.profiler() {
+            Some(profiler) => profiler,
+            None => return,
+        };
+        let symbols = self.gameboy.symbols();
+
+        let mut outputs = vec![(format!("{}.profile.txt", self.rom_file), profiler.report(symbols))];
+        if self.collapsed_stacks {
+            outputs.push((format!("{}.folded", self.rom_file), profiler.collapsed_stacks(symbols)));
+        }
+        for (path, contents) in outputs {
+            match std::fs::write(&path, contents) {
+                Ok(()) => log::info!("Saved profile to {}", path),
+                Err(e) => log::error!("Unable to save profile to {}: {}", path, e),
+            }
+        }
+    }
+
+    fn save_coverage(&self) {
+        if let Some(coverage) = self.gameboy.coverage() {
+            let path = Coverage::sidecar_path(&self.rom_file);
+            match coverage.save(&path) {
+                Ok(()) => log::info!(
+                    "Saved coverage of {} executed bytes to {}",
+                    coverage.count(rubc_core::coverage::EXECUTED),
+                    path.display()
+                ),
+                Err(e) => log::error!("Unable to save coverage to {}: {}", path.display(), e),
+            }
+        }
+    }
+
     fn update(&mut self) {
+        if self.paused || self.error.is_some() {
+            return;
+        }
+
         let cycles = CPU_HZ as f64 * ((FPS_US as f64) / 1_000_000.0);
         for _ in 0..cycles as u64 {
-            self.gameboy.tick().unwrap();
+            if let Err(e) = self.gameboy.tick() {
+                log::error!("Emulation stopped: {}", e);
+                self.error = Some(e.to_string());
+                break;
+            }
+
+            if let Some(hit) = self.gameboy.take_watch_hit() {
+                println!(
+                    "Watchpoint hit: {:?} {:04X}={:02X} at PC {:04X}, press F5 to resume",
+                    hit.kind, hit.address, hit.value, hit.pc
+                );
+                self.paused = true;
+                break;
+            }
+
+            if let Some(hit) = self.gameboy.take_break_hit() {
+                println!(
+                    "Breakpoint #{} hit at {:02X}:{:04X}, press F5 to resume",
+                    hit.index, hit.bank, hit.pc
+                );
+                self.paused = true;
+                break;
+            }
         }
         // log::trace!("processed {} cycles", cycles as u64);
     }
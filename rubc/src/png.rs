@@ -0,0 +1,335 @@
+// PNG images, decoded to grayscale.
+//
+// Enough of PNG to feed pictures to the Pocket Camera's sensor: every colour
+// type and bit depth is read, colour is converted to luma and alpha is
+// ignored. Interlaced images are not supported.
+
+use anyhow::anyhow;
+use rubc_core::{archive, utils, RubcError};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+const COLOR_GRAY: u8 = 0;
+const COLOR_RGB: u8 = 2;
+const COLOR_PALETTE: u8 = 3;
+const COLOR_GRAY_ALPHA: u8 = 4;
+const COLOR_RGBA: u8 = 6;
+
+// 8-bit grayscale pixels, row by row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    // nearest neighbour scaling
+    pub fn resize(&self, width: usize, height: usize) -> Image {
+        let pixels = (0..height)
+            .flat_map(|y| {
+                let row = y * self.height / height * self.width;
+                (0..width).map(move |x| self.pixels[row + x * self.width / width])
+            })
+            .collect();
+        Image { width, height, pixels }
+    }
+}
+
+fn malformed(message: &str) -> anyhow::Error {
+    anyhow!("Invalid image: {}", message)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> anyhow::Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| malformed("Unexpected end of image"))
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), byte| {
+        let a = (a + *byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    b << 16 | a
+}
+
+// zlib (RFC 1950) wrapped deflate data
+fn decompress(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    match data {
+        [cmf, flg, ..]
+            if cmf & 0x0F == 8 && (*cmf as u16 * 256 + *flg as u16).is_multiple_of(31) && flg & 0x20 == 0 => {}
+        _ => return Err(malformed("Not a deflated zlib stream")),
+    }
+    let (out, consumed) = archive::inflate(&data[2..]).map_err(|e| match e {
+        RubcError::Archive(message) => malformed(&message),
+        e => e.into(),
+    })?;
+    match read_u32(data, 2 + consumed)? == adler32(&out) {
+        true => Ok(out),
+        false => Err(malformed("Adler-32 mismatch in image data")),
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+// undoes the per row filters, each row in data starts with its filter type
+fn unfilter(data: &[u8], stride: usize, height: usize, pixel_size: usize) -> anyhow::Result<Vec<u8>> {
+    let mut out = vec![0u8; stride * height];
+    for y in 0..height {
+        let filter = data[y * (stride + 1)];
+        let row = &data[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+        for x in 0..stride {
+            let a = if x >= pixel_size {
+                out[y * stride + x - pixel_size]
+            } else {
+                0
+            };
+            let b = if y > 0 { out[(y - 1) * stride + x] } else { 0 };
+            let c = if x >= pixel_size && y > 0 {
+                out[(y - 1) * stride + x - pixel_size]
+            } else {
+                0
+            };
+            out[y * stride + x] = row[x].wrapping_add(match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(malformed("Invalid filter type")),
+            });
+        }
+    }
+    Ok(out)
+}
+
+pub fn decode_grayscale(data: &[u8]) -> anyhow::Result<Image> {
+    if !data.starts_with(&SIGNATURE) {
+        return Err(malformed("Not a PNG file"));
+    }
+
+    let mut header = None;
+    let mut palette = Vec::new();
+    let mut compressed = Vec::new();
+    let mut offset = SIGNATURE.len();
+    loop {
+        let len = read_u32(data, offset)? as usize;
+        let chunk = data
+            .get(offset + 4..offset + 8 + len)
+            .ok_or_else(|| malformed("Unexpected end of image"))?;
+        if read_u32(data, offset + 8 + len)? != utils::crc32(chunk) {
+            return Err(malformed("CRC mismatch in image"));
+        }
+        let (kind, body) = chunk.split_at(4);
+        match kind {
+            b"IHDR" if body.len() == 13 => header = Some(body.to_vec()),
+            b"PLTE" => palette = body.to_vec(),
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+        offset += len + 12;
+    }
+
+    let header = header.ok_or_else(|| malformed("Missing IHDR chunk"))?;
+    let width = read_u32(&header, 0)? as usize;
+    let height = read_u32(&header, 4)? as usize;
+    let (depth, color) = (header[8], header[9]);
+    if header[12] != 0 {
+        return Err(malformed("Interlaced images are not supported"));
+    }
+    let channels = match (color, depth) {
+        (COLOR_GRAY, 1 | 2 | 4 | 8 | 16) => 1,
+        (COLOR_PALETTE, 1 | 2 | 4 | 8) => 1,
+        (COLOR_GRAY_ALPHA, 8 | 16) => 2,
+        (COLOR_RGB, 8 | 16) => 3,
+        (COLOR_RGBA, 8 | 16) => 4,
+        _ => return Err(malformed("Unsupported colour type or bit depth")),
+    };
+    if width == 0 || height == 0 {
+        return Err(malformed("Empty image"));
+    }
+
+    // the header can claim sizes that overflow before any data is read
+    let bits_per_pixel = channels * depth as usize;
+    let stride = width
+        .checked_mul(bits_per_pixel)
+        .map(|bits| bits.div_ceil(8))
+        .ok_or_else(|| malformed("Image too large"))?;
+    let size = (stride + 1)
+        .checked_mul(height)
+        .filter(|_| width.checked_mul(height).is_some())
+        .ok_or_else(|| malformed("Image too large"))?;
+    let filtered = decompress(&compressed)?;
+    if filtered.len() < size {
+        return Err(malformed("Image data too short"));
+    }
+    let raw = unfilter(&filtered, stride, height, bits_per_pixel.div_ceil(8))?;
+
+    // the high byte of 16-bit samples, or a packed sample scaled to 8 bits
+    let sample = |row: &[u8], index: usize| -> u8 {
+        match depth {
+            16 => row[index * 2],
+            8 => row[index],
+            _ => {
+                let bit = index * depth as usize;
+                let max = (1u16 << depth) - 1;
+                let value = (row[bit / 8] >> (8 - depth as usize - bit % 8)) as u16 & max;
+                match color {
+                    COLOR_PALETTE => value as u8,
+                    _ => (value * 255 / max) as u8,
+                }
+            }
+        }
+    };
+    let luma = |r: u8, g: u8, b: u8| ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8;
+
+    let mut pixels = Vec::with_capacity(width * height);
+    for row in raw.chunks(stride) {
+        for x in 0..width {
+            pixels.push(match color {
+                COLOR_GRAY | COLOR_GRAY_ALPHA => sample(row, x * channels),
+                COLOR_PALETTE => {
+                    let index = sample(row, x) as usize * 3;
+                    let rgb = palette
+                        .get(index..index + 3)
+                        .ok_or_else(|| malformed("Palette index out of range"))?;
+                    luma(rgb[0], rgb[1], rgb[2])
+                }
+                _ => luma(
+                    sample(row, x * channels),
+                    sample(row, x * channels + 1),
+                    sample(row, x * channels + 2),
+                ),
+            });
+        }
+    }
+    Ok(Image { width, height, pixels })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rubc_core::utils::crc32;
+
+    fn chunk(kind: &[u8], body: &[u8], out: &mut Vec<u8>) {
+        out.extend((body.len() as u32).to_be_bytes());
+        let start = out.len();
+        out.extend(kind);
+        out.extend(body);
+        let crc = crc32(&out[start..]);
+        out.extend(crc.to_be_bytes());
+    }
+
+    // zlib stream holding a single stored deflate block
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut out = vec![0x78, 0x01, 0x01];
+        out.extend((data.len() as u16).to_le_bytes());
+        out.extend((!(data.len() as u16)).to_le_bytes());
+        out.extend(data);
+        let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), byte| {
+            let a = (a + *byte as u32) % 65521;
+            (a, (b + a) % 65521)
+        });
+        out.extend((b << 16 | a).to_be_bytes());
+        out
+    }
+
+    // rows of filtered scanlines, each starting with its filter type
+    fn png(width: u32, height: u32, depth: u8, color: u8, palette: &[u8], rows: &[u8]) -> Vec<u8> {
+        let mut out = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        let mut header = width.to_be_bytes().to_vec();
+        header.extend(height.to_be_bytes());
+        header.extend([depth, color, 0, 0, 0]);
+        chunk(b"IHDR", &header, &mut out);
+        if !palette.is_empty() {
+            chunk(b"PLTE", palette, &mut out);
+        }
+        let data = zlib(rows);
+        // image data may be split across chunks
+        let (first, second) = data.split_at(data.len() / 2);
+        chunk(b"IDAT", first, &mut out);
+        chunk(b"tEXt", b"Comment\0ignored", &mut out);
+        chunk(b"IDAT", second, &mut out);
+        chunk(b"IEND", &[], &mut out);
+        out
+    }
+
+    #[test]
+    fn test_grayscale() {
+        // none, sub, up and paeth filters
+        let rows = [0, 10, 20, 30, 1, 40, 5, 5, 2, 1, 1, 1, 4, 1, 2, 3];
+        let image = decode_grayscale(&png(3, 4, 8, 0, &[], &rows)).unwrap();
+        assert_eq!((image.width, image.height), (3, 4));
+        assert_eq!(image.pixels, [10, 20, 30, 40, 45, 50, 41, 46, 51, 42, 48, 54]);
+
+        // 2-bit samples are scaled to 8 bits
+        let image = decode_grayscale(&png(5, 1, 2, 0, &[], &[3, 0x1B, 0xC0])).unwrap();
+        assert_eq!(image.pixels, [0, 85, 170, 255, 255]);
+
+        // 16-bit gray with alpha keeps the high byte of the gray sample
+        let image = decode_grayscale(&png(1, 1, 16, 4, &[], &[0, 0x12, 0x34, 0x00, 0x00])).unwrap();
+        assert_eq!(image.pixels, [0x12]);
+    }
+
+    #[test]
+    fn test_color() {
+        let rows = [0, 255, 0, 0, 0, 0, 255, 0, 0];
+        let image = decode_grayscale(&png(2, 1, 8, 6, &[], &rows)).unwrap();
+        assert_eq!(image.pixels, [76, 149]);
+
+        let palette = [0, 0, 0, 255, 255, 255, 0, 0, 255];
+        let image = decode_grayscale(&png(4, 1, 4, 3, &palette, &[0, 0x12, 0x01])).unwrap();
+        assert_eq!(image.pixels, [255, 29, 0, 255]);
+        assert!(decode_grayscale(&png(1, 1, 8, 3, &palette, &[0, 3])).is_err());
+    }
+
+    #[test]
+    fn test_malformed() {
+        let image = png(1, 1, 8, 0, &[], &[0, 0]);
+        assert!(decode_grayscale(b"GIF89a").is_err());
+        assert!(decode_grayscale(&image[..30]).is_err());
+
+        let mut corrupt = image.clone();
+        corrupt[20] ^= 0xFF;
+        let error = decode_grayscale(&corrupt).unwrap_err();
+        assert!(error.to_string().contains("CRC mismatch"));
+
+        let mut interlaced = png(1, 1, 8, 0, &[], &[0, 0]);
+        interlaced[28] = 1;
+        let crc = crc32(&interlaced[12..29]);
+        interlaced[29..33].copy_from_slice(&crc.to_be_bytes());
+        assert!(decode_grayscale(&interlaced).is_err());
+
+        assert!(decode_grayscale(&png(2, 2, 8, 0, &[], &[0, 1, 2])).is_err());
+        assert!(decode_grayscale(&png(1, 1, 8, 0, &[], &[5, 0])).is_err());
+    }
+
+    #[test]
+    fn test_dimensions_overflow() {
+        let error = decode_grayscale(&png(u32::MAX, u32::MAX, 16, 6, &[], &[0, 0])).unwrap_err();
+        assert!(error.to_string().contains("Image too large"));
+    }
+
+    #[test]
+    fn test_resize() {
+        let image = Image {
+            width: 2,
+            height: 2,
+            pixels: vec![1, 2, 3, 4],
+        };
+        assert_eq!(image.resize(4, 1).pixels, [1, 1, 2, 2]);
+        assert_eq!(image.resize(1, 2).pixels, [1, 3]);
+    }
+}